@@ -0,0 +1,3 @@
+ALTER TABLE relays DROP COLUMN write;
+ALTER TABLE relays DROP COLUMN read;
+ALTER TABLE relays DROP COLUMN enabled;
@@ -0,0 +1,7 @@
+-- Relays are known either because user added them or because they
+-- were seen in events. Only the enabled ones are used by client.
+ALTER TABLE relays ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT FALSE;
+-- Whether events are read from relay.
+ALTER TABLE relays ADD COLUMN read BOOLEAN NOT NULL DEFAULT TRUE;
+-- Whether events are published to relay.
+ALTER TABLE relays ADD COLUMN write BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1 @@
+DROP TABLE replaceable_events;
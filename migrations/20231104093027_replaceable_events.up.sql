@@ -0,0 +1,12 @@
+-- Latest replaceable events (kinds 0, 3, 10000–19999) of each author.
+CREATE TABLE replaceable_events (
+       -- Pubkey of author.
+       author BLOB NOT NULL,
+       -- Kind of event.
+       kind INTEGER NOT NULL,
+       -- Creation time of event, newer event replaces older.
+       created_at INTEGER NOT NULL,
+       -- The complete event as JSON.
+       event TEXT NOT NULL,
+       PRIMARY KEY (author, kind)
+);
@@ -0,0 +1,11 @@
+-- Lanes open in main window, restored on next start.
+CREATE TABLE lanes (
+       -- Order of the lane from the left.
+       position INTEGER NOT NULL PRIMARY KEY,
+       -- Subscription of the lane as a lane query.
+       query TEXT NOT NULL,
+       -- ID of event the lane is focused on, if any.
+       focused BLOB,
+       -- Width of the lane in pixels.
+       width INTEGER NOT NULL
+);
@@ -0,0 +1,19 @@
+CREATE TABLE textnotes (
+       id BLOB PRIMARY KEY ON CONFLICT IGNORE,
+       event TEXT NOT NULL
+);
+
+CREATE TABLE textnotes_relays (
+       textnote BLOB NOT NULL,
+       relay TEXT NOT NULL,
+       PRIMARY KEY (textnote, relay) ON CONFLICT IGNORE
+);
+
+INSERT INTO textnotes (id, event) SELECT id, event FROM events WHERE kind = 1;
+
+INSERT INTO textnotes_relays (textnote, relay)
+SELECT event, relay FROM events_relays WHERE event IN (SELECT id FROM textnotes);
+
+DROP TABLE events_relays;
+DROP TABLE event_tags;
+DROP TABLE events;
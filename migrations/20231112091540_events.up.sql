@@ -0,0 +1,58 @@
+-- Events of all kinds.
+CREATE TABLE events (
+       -- Event id.
+       id BLOB PRIMARY KEY ON CONFLICT IGNORE,
+       -- Pubkey of author.
+       pubkey BLOB NOT NULL,
+       -- Kind of event.
+       kind INTEGER NOT NULL,
+       -- Creation time of event as unix timestamp.
+       created_at INTEGER NOT NULL,
+       -- Original event JSON.
+       event TEXT NOT NULL
+);
+
+CREATE INDEX events_kind_created_at ON events (kind, created_at);
+CREATE INDEX events_pubkey_created_at ON events (pubkey, created_at);
+
+-- Tags of events referring to events (e), pubkeys (p) and hashtags (t).
+CREATE TABLE event_tags (
+       -- Id of tagged event.
+       event BLOB NOT NULL,
+       -- Name of the tag: 'e', 'p' or 't'.
+       name TEXT NOT NULL,
+       -- First value of the tag: hex of event id or pubkey, or hashtag.
+       value TEXT NOT NULL,
+       PRIMARY KEY (event, name, value) ON CONFLICT IGNORE
+);
+
+CREATE INDEX event_tags_name_value ON event_tags (name, value);
+
+-- m-to-n relationship between events and relays.
+CREATE TABLE events_relays (
+       -- Event id.
+       event BLOB NOT NULL,
+       -- Relay URL.
+       relay TEXT NOT NULL,
+       PRIMARY KEY (event, relay) ON CONFLICT IGNORE
+);
+
+INSERT INTO events (id, pubkey, kind, created_at, event)
+SELECT id,
+       unhex(json_extract(event, '$.pubkey')),
+       json_extract(event, '$.kind'),
+       json_extract(event, '$.created_at'),
+       event
+FROM textnotes;
+
+INSERT INTO event_tags (event, name, value)
+SELECT textnotes.id, json_extract(tag.value, '$[0]'), json_extract(tag.value, '$[1]')
+FROM textnotes, json_each(textnotes.event, '$.tags') AS tag
+WHERE json_extract(tag.value, '$[0]') IN ('e', 'p', 't')
+  AND json_extract(tag.value, '$[1]') IS NOT NULL;
+
+INSERT INTO events_relays (event, relay)
+SELECT textnote, relay FROM textnotes_relays;
+
+DROP TABLE textnotes_relays;
+DROP TABLE textnotes;
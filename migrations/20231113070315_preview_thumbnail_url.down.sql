@@ -0,0 +1 @@
+ALTER TABLE previews DROP COLUMN thumbnail_url;
@@ -0,0 +1,2 @@
+-- URL from which thumbnail of link preview was obtained.
+ALTER TABLE previews ADD COLUMN thumbnail_url TEXT NULL;
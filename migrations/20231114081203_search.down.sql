@@ -0,0 +1,9 @@
+DROP TRIGGER metadata_update_profile_text;
+DROP TRIGGER metadata_insert_profile_text;
+DROP TABLE profiles_search;
+DROP TABLE profile_texts;
+
+DROP TRIGGER events_delete_note_text;
+DROP TRIGGER events_insert_note_text;
+DROP TABLE notes_search;
+DROP TABLE note_texts;
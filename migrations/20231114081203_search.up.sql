@@ -0,0 +1,97 @@
+-- Searchable text of text notes, kept in sync with events by triggers.
+-- The full-text index refers to its stable integer id, rows of events
+-- may be renumbered by VACUUM.
+CREATE TABLE note_texts (
+       -- Id referred to by the full-text index.
+       id INTEGER PRIMARY KEY,
+       -- Id of the text note.
+       event BLOB NOT NULL UNIQUE,
+       -- Content of the text note.
+       content TEXT NOT NULL
+);
+
+-- Full-text index of content of text notes.
+CREATE VIRTUAL TABLE notes_search USING fts5 (
+       content,
+       content = 'note_texts',
+       content_rowid = 'id',
+       tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER note_texts_insert AFTER INSERT ON note_texts BEGIN
+       INSERT INTO notes_search (rowid, content) VALUES (new.id, new.content);
+END;
+
+CREATE TRIGGER note_texts_delete AFTER DELETE ON note_texts BEGIN
+       INSERT INTO notes_search (notes_search, rowid, content)
+       VALUES ('delete', old.id, old.content);
+END;
+
+CREATE TRIGGER events_insert_note_text AFTER INSERT ON events WHEN new.kind = 1 BEGIN
+       INSERT INTO note_texts (event, content)
+       VALUES (new.id, json_extract(new.event, '$.content'));
+END;
+
+CREATE TRIGGER events_delete_note_text AFTER DELETE ON events WHEN old.kind = 1 BEGIN
+       DELETE FROM note_texts WHERE event = old.id;
+END;
+
+-- Searchable fields of profiles, kept in sync with metadata by triggers.
+CREATE TABLE profile_texts (
+       -- Id referred to by the full-text index.
+       id INTEGER PRIMARY KEY,
+       -- Pubkey of the profile.
+       author BLOB NOT NULL UNIQUE,
+       -- Fields of the metadata, NULL if missing.
+       name TEXT,
+       display_name TEXT,
+       about TEXT,
+       nip05 TEXT
+);
+
+-- Full-text index of profiles.
+CREATE VIRTUAL TABLE profiles_search USING fts5 (
+       name,
+       display_name,
+       about,
+       nip05,
+       content = 'profile_texts',
+       content_rowid = 'id',
+       tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER profile_texts_insert AFTER INSERT ON profile_texts BEGIN
+       INSERT INTO profiles_search (rowid, name, display_name, about, nip05)
+       VALUES (new.id, new.name, new.display_name, new.about, new.nip05);
+END;
+
+CREATE TRIGGER profile_texts_delete AFTER DELETE ON profile_texts BEGIN
+       INSERT INTO profiles_search (profiles_search, rowid, name, display_name, about, nip05)
+       VALUES ('delete', old.id, old.name, old.display_name, old.about, old.nip05);
+END;
+
+-- Metadata are replaced by newer ones, so are their searchable fields.
+-- Content of metadata events that is not valid JSON is not searchable.
+CREATE TRIGGER metadata_insert_profile_text AFTER INSERT ON metadata BEGIN
+       DELETE FROM profile_texts WHERE author = new.author;
+       INSERT INTO profile_texts (author, name, display_name, about, nip05)
+       SELECT new.author, c ->> '$.name', c ->> '$.display_name', c ->> '$.about', c ->> '$.nip05'
+       FROM (SELECT new.event ->> '$.content' AS c)
+       WHERE json_valid(c);
+END;
+
+CREATE TRIGGER metadata_update_profile_text AFTER UPDATE OF event ON metadata BEGIN
+       DELETE FROM profile_texts WHERE author = new.author;
+       INSERT INTO profile_texts (author, name, display_name, about, nip05)
+       SELECT new.author, c ->> '$.name', c ->> '$.display_name', c ->> '$.about', c ->> '$.nip05'
+       FROM (SELECT new.event ->> '$.content' AS c)
+       WHERE json_valid(c);
+END;
+
+INSERT INTO note_texts (event, content)
+SELECT id, json_extract(event, '$.content') FROM events WHERE kind = 1;
+
+INSERT INTO profile_texts (author, name, display_name, about, nip05)
+SELECT author, c ->> '$.name', c ->> '$.display_name', c ->> '$.about', c ->> '$.nip05'
+FROM (SELECT author, event ->> '$.content' AS c FROM metadata)
+WHERE json_valid(c);
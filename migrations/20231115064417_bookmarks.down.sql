@@ -0,0 +1,2 @@
+DROP TRIGGER events_delete_references;
+DROP TABLE bookmarks;
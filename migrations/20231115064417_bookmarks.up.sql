@@ -0,0 +1,13 @@
+-- Notes bookmarked by user. They are never pruned.
+CREATE TABLE bookmarks (
+       -- Id of the bookmarked event.
+       event BLOB PRIMARY KEY ON CONFLICT IGNORE,
+       -- When the event was bookmarked, as unix timestamp.
+       created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+-- Pruned events take their tags and relays with them.
+CREATE TRIGGER events_delete_references AFTER DELETE ON events BEGIN
+       DELETE FROM event_tags WHERE event = old.id;
+       DELETE FROM events_relays WHERE event = old.id;
+END;
@@ -0,0 +1,2 @@
+-- Export goes through all events in order of their creation.
+CREATE INDEX events_created_at_id ON events (created_at, id);
@@ -0,0 +1,8 @@
+-- Notes being written, kept so that they survive a crash.
+CREATE TABLE drafts (
+       -- What is being written: a new note, a reply or a quote of an event.
+       key TEXT PRIMARY KEY ON CONFLICT REPLACE,
+       content TEXT NOT NULL,
+       -- When the draft was saved, as unix timestamp.
+       updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
@@ -0,0 +1 @@
+DROP INDEX event_tags_name_lower_value;
@@ -0,0 +1,2 @@
+-- Hashtags are looked up regardless of case.
+CREATE INDEX event_tags_name_lower_value ON event_tags (name, lower(value));
@@ -1,9 +1,13 @@
+use std::str::FromStr;
+
 use gtk::gdk;
 use gtk::gio::SimpleActionGroup;
 use gtk::prelude::DisplayExt;
+use nostr_sdk::prelude::{EventId, XOnlyPublicKey};
 use relm4::actions::{RelmAction, RelmActionGroup};
 use relm4::AsyncComponentSender;
 
+use crate::nostr::subscriptions::Subscription;
 use crate::ui::main::{Main, MainInput};
 
 /// Creates a GTK action group for app-scoped actions.
@@ -26,14 +30,132 @@ relm4::new_stateful_action!(pub CopyText, AppActionGroup, "copy-text", String, (
 
 relm4::new_action_group!(pub MainMenuActionGroup, "main");
 relm4::new_stateless_action!(pub EditProfile, MainMenuActionGroup, "profile");
+relm4::new_stateless_action!(pub OpenSink, MainMenuActionGroup, "sink");
+relm4::new_stateless_action!(pub CompactDatabase, MainMenuActionGroup, "compact");
+relm4::new_stateless_action!(pub ExportEvents, MainMenuActionGroup, "export");
+relm4::new_stateless_action!(pub ImportEvents, MainMenuActionGroup, "import");
+relm4::new_stateless_action!(pub ManageIdentities, MainMenuActionGroup, "identities");
+relm4::new_stateful_action!(pub SwitchIdentity, MainMenuActionGroup, "identity", u32, ());
 
 pub fn make_main_menu_actions(sender: AsyncComponentSender<Main>) -> SimpleActionGroup {
     let mut group = RelmActionGroup::<MainMenuActionGroup>::new();
 
-    group.add_action(profile_action(sender));
+    group.add_action(profile_action(sender.clone()));
+    group.add_action(sink_action(sender.clone()));
+    group.add_action(compact_action(sender.clone()));
+    group.add_action(export_action(sender.clone()));
+    group.add_action(import_action(sender.clone()));
+    group.add_action(identities_action(sender.clone()));
+    group.add_action(switch_identity_action(sender));
     group.into_action_group()
 }
 
+fn identities_action(sender: AsyncComponentSender<Main>) -> RelmAction<ManageIdentities> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::ShowIdentities))
+}
+
+fn switch_identity_action(sender: AsyncComponentSender<Main>) -> RelmAction<SwitchIdentity> {
+    RelmAction::new_with_target_value(move |_, index: u32| {
+        sender.input(MainInput::SwitchIdentity(index as usize))
+    })
+}
+
+fn export_action(sender: AsyncComponentSender<Main>) -> RelmAction<ExportEvents> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::Export(Subscription::Sink)))
+}
+
+fn import_action(sender: AsyncComponentSender<Main>) -> RelmAction<ImportEvents> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::Import))
+}
+
+fn compact_action(sender: AsyncComponentSender<Main>) -> RelmAction<CompactDatabase> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::CompactDatabase))
+}
+
+fn sink_action(sender: AsyncComponentSender<Main>) -> RelmAction<OpenSink> {
+    RelmAction::new_stateless(move |_| sender.input(MainInput::OpenSink))
+}
+
 fn profile_action(sender: AsyncComponentSender<Main>) -> RelmAction<EditProfile> {
     RelmAction::new_stateless(move |_| sender.input(MainInput::EditProfile))
 }
+
+relm4::new_action_group!(pub AuthorActionGroup, "author");
+relm4::new_stateful_action!(pub FollowAuthor, AuthorActionGroup, "follow", String, ());
+relm4::new_stateful_action!(pub UnfollowAuthor, AuthorActionGroup, "unfollow", String, ());
+
+/// Creates actions that can be performed on an author. The target of each
+/// action is author's pubkey in hex.
+pub fn make_author_actions(sender: AsyncComponentSender<Main>) -> SimpleActionGroup {
+    let mut group = RelmActionGroup::<AuthorActionGroup>::new();
+
+    group.add_action(follow_action(sender.clone()));
+    group.add_action(unfollow_action(sender));
+    group.into_action_group()
+}
+
+fn follow_action(sender: AsyncComponentSender<Main>) -> RelmAction<FollowAuthor> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::Follow(pubkey, true));
+        }
+    })
+}
+
+fn unfollow_action(sender: AsyncComponentSender<Main>) -> RelmAction<UnfollowAuthor> {
+    RelmAction::new_with_target_value(move |_, pubkey: String| {
+        if let Ok(pubkey) = XOnlyPublicKey::from_str(&pubkey) {
+            sender.input(MainInput::Follow(pubkey, false));
+        }
+    })
+}
+
+relm4::new_action_group!(pub NoteActionGroup, "note");
+relm4::new_stateful_action!(pub BookmarkNote, NoteActionGroup, "bookmark", String, ());
+relm4::new_stateful_action!(pub UnbookmarkNote, NoteActionGroup, "unbookmark", String, ());
+relm4::new_stateful_action!(pub RepostNote, NoteActionGroup, "repost", String, ());
+relm4::new_stateful_action!(pub QuoteNote, NoteActionGroup, "quote", String, ());
+
+/// Creates actions that can be performed on a note. The target of each
+/// action is note's event ID in hex.
+pub fn make_note_actions(sender: AsyncComponentSender<Main>) -> SimpleActionGroup {
+    let mut group = RelmActionGroup::<NoteActionGroup>::new();
+
+    group.add_action(bookmark_action(sender.clone()));
+    group.add_action(unbookmark_action(sender.clone()));
+    group.add_action(repost_action(sender.clone()));
+    group.add_action(quote_action(sender));
+    group.into_action_group()
+}
+
+fn bookmark_action(sender: AsyncComponentSender<Main>) -> RelmAction<BookmarkNote> {
+    RelmAction::new_with_target_value(move |_, id: String| {
+        if let Ok(id) = EventId::from_hex(id) {
+            sender.input(MainInput::Bookmark(id, true));
+        }
+    })
+}
+
+fn unbookmark_action(sender: AsyncComponentSender<Main>) -> RelmAction<UnbookmarkNote> {
+    RelmAction::new_with_target_value(move |_, id: String| {
+        if let Ok(id) = EventId::from_hex(id) {
+            sender.input(MainInput::Bookmark(id, false));
+        }
+    })
+}
+
+fn repost_action(sender: AsyncComponentSender<Main>) -> RelmAction<RepostNote> {
+    RelmAction::new_with_target_value(move |_, id: String| {
+        if let Ok(id) = EventId::from_hex(id) {
+            sender.input(MainInput::Repost(id));
+        }
+    })
+}
+
+fn quote_action(sender: AsyncComponentSender<Main>) -> RelmAction<QuoteNote> {
+    RelmAction::new_with_target_value(move |_, id: String| {
+        if let Ok(id) = EventId::from_hex(id) {
+            sender.input(MainInput::Quote(id));
+        }
+    })
+}
@@ -6,8 +6,7 @@ use futures_util::future;
 use nostr_sdk::nostr::nips::nip11;
 use nostr_sdk::Url;
 use relm4::AsyncComponentSender;
-use sqlx::query;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::gnostique::Gnostique;
 use crate::ui::main::{Main, MainInput};
@@ -24,6 +23,27 @@ pub async fn receive_events(gnostique: Gnostique, sender: AsyncComponentSender<M
         .await;
 }
 
+/// Regularly, and in the background, prune database and bitmap
+/// cache according to configured limits.
+pub async fn prune(gnostique: Gnostique) {
+    let minutes = gnostique.config().retention.interval_minutes;
+    let mut int = tokio::time::interval(Duration::from_secs(minutes * 60));
+    loop {
+        int.tick().await;
+
+        match gnostique.prune().await {
+            Ok(deleted) => info!("Pruned {deleted} stored events."),
+            Err(e) => warn!("Could not prune database: {e}"),
+        }
+
+        let max_bytes = gnostique.config().cache.bitmaps_mb * 1024 * 1024;
+        match gnostique.download().prune(max_bytes).await {
+            Ok(removed) => info!("Pruned {removed} bytes of bitmap cache."),
+            Err(e) => warn!("Could not prune bitmap cache: {e}"),
+        }
+    }
+}
+
 /// Regularly, and in the background, obtain information about relays.
 pub async fn refresh_relay_information(gnostique: Arc<Gnostique>) {
     let mut int = tokio::time::interval(Duration::from_secs(60));
@@ -33,28 +53,16 @@ pub async fn refresh_relay_information(gnostique: Arc<Gnostique>) {
         let client_relays = gnostique.client().relays().await;
         let mut client_relays: HashSet<Url> = client_relays.keys().cloned().collect();
 
-        let old_info = query!(
-            r#"
-SELECT
-  url,
-  information IS NULL OR unixepoch('now') - unixepoch(updated) > 60 * 60 AS "old: bool"
-FROM relays
-"#
-        )
-        .fetch_all(gnostique.pool())
-        .await;
+        let old_info = gnostique
+            .store()
+            .outdated_relay_information(Duration::from_secs(60 * 60))
+            .await;
 
         let old_info: HashSet<_> = if let Ok(rec) = old_info {
-            rec.iter()
-                .filter_map(|r| {
-                    let url = r.url.parse().unwrap();
+            rec.into_iter()
+                .filter_map(|(url, old)| {
                     client_relays.remove(&url);
-
-                    if r.old {
-                        Some(url)
-                    } else {
-                        None
-                    }
+                    old.then_some(url)
                 })
                 .collect()
         } else {
@@ -63,21 +71,10 @@ FROM relays
 
         for url in old_info.union(&client_relays) {
             if let Ok(info) = nip11::RelayInformationDocument::get(url.clone(), None).await {
-                let url_s = url.to_string();
-                let info_json = serde_json::to_string(&info).unwrap();
-                let _ = query!(
-                    r#"
-INSERT INTO relays(url, information, updated)
-VALUES (?, ?, CURRENT_TIMESTAMP)
-ON CONFLICT(url) DO UPDATE SET
-  information = EXCLUDED.information,
-  updated = EXCLUDED.updated
-"#,
-                    url_s,
-                    info_json
-                )
-                .execute(gnostique.pool())
-                .await;
+                if let Err(e) = gnostique.store().store_relay_information(url, &info).await {
+                    warn!("Could not store relay information of {url}: {e}");
+                    continue;
+                }
 
                 info!("Stored fresh relay information of {}", url);
             }
@@ -1,8 +1,433 @@
-use std::path::PathBuf;
+//! Configuration of Gnostique.
+//!
+//! Configuration is assembled from several layers, each overriding the previous:
+//!
+//! 1. built-in defaults,
+//! 2. main configuration file `gnostique.toml` in config directory,
+//! 3. all `*.toml` files in directory `gnostique.d` next to it, in alphabetical order,
+//! 4. environment variables prefixed with `GNOSTIQUE_`, nested keys separated
+//!    by double underscore (e. g. `GNOSTIQUE_CACHE__LANE_NOTES=50`).
 
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use ::config::{Environment, File, FileFormat};
+use directories::ProjectDirs;
+use nostr_sdk::Url;
 use serde::{Deserialize, Serialize};
 
+use crate::gnostique::LoadError;
+
+/// Name of the main configuration file.
+const CONFIG_FILE: &str = "gnostique.toml";
+
+/// Name of directory with additional configuration files.
+const CONFIG_DIR: &str = "gnostique.d";
+
+/// Name of file in [`CONFIG_DIR`] with lane presets saved by user.
+const LANES_FILE: &str = "lanes.toml";
+
+/// Prefix of environment variables overriding configuration.
+const ENV_PREFIX: &str = "GNOSTIQUE";
+
+/// Part of configuration could not be saved.
+#[derive(Debug)]
+pub enum SaveError {
+    Serialize(toml::ser::Error),
+    File(std::io::Error),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Serialize(e) => write!(f, "Could not serialize configuration: {e}"),
+            SaveError::File(e) => write!(f, "Could not write configuration file: {e}"),
+        }
+    }
+}
+
+impl From<toml::ser::Error> for SaveError {
+    fn from(e: toml::ser::Error) -> Self {
+        SaveError::Serialize(e)
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> Self {
+        SaveError::File(e)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Path to SQLite database.
     pub db_file: PathBuf,
+
+    /// Name of identity that is unlocked by default.
+    #[serde(default)]
+    pub default_identity: Option<String>,
+
+    /// Relays to connect to.
+    #[serde(default)]
+    pub relays: Vec<RelayConfig>,
+
+    /// Limits of various caches.
+    #[serde(default)]
+    pub cache: CacheConfig,
+
+    /// What is kept in database and for how long.
+    #[serde(default)]
+    pub retention: RetentionConfig,
+
+    /// Named lanes that can be opened quickly.
+    #[serde(default)]
+    pub lanes: Vec<LanePreset>,
+
+    /// User interface preferences.
+    #[serde(default)]
+    pub ui: UiConfig,
+}
+
+/// Relay and how it is supposed to be used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayConfig {
+    /// URL of the relay (`ws://` or `wss://`).
+    pub url: String,
+
+    /// Whether events are read from the relay.
+    #[serde(default = "yes")]
+    pub read: bool,
+
+    /// Whether events are published to the relay.
+    #[serde(default = "yes")]
+    pub write: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Maximum number of text notes held by a lane in memory.
+    pub lane_notes: usize,
+
+    /// Maximum size of bitmap cache in megabytes.
+    pub bitmaps_mb: u64,
+}
+
+/// Limits of stored events. Events of the current identity, of followed
+/// authors and bookmarked ones are kept regardless. Zero means no limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Events older than this many days are pruned.
+    pub max_age_days: u64,
+
+    /// Maximum number of events kept of each author, the newest are kept.
+    pub events_per_author: u64,
+
+    /// Maximum size of stored events in megabytes, the oldest events are pruned.
+    pub database_mb: u64,
+
+    /// How often the database is pruned, in minutes.
+    pub interval_minutes: u64,
+}
+
+/// Lane saved under a name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanePreset {
+    pub name: String,
+    pub query: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// Whether dark variant of theme is preferred.
+    pub dark_theme: bool,
+
+    /// Default width of a lane in pixels.
+    pub lane_width: i32,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            lane_notes: 10,
+            bitmaps_mb: 200,
+        }
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            max_age_days: 90,
+            events_per_author: 1000,
+            database_mb: 1000,
+            interval_minutes: 60,
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            dark_theme: true,
+            lane_width: 600,
+        }
+    }
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl Config {
+    /// Configuration used when nothing else is specified.
+    pub fn defaults(dirs: &ProjectDirs) -> Config {
+        Config {
+            // Name kept for compatibility with existing installations.
+            db_file: dirs.data_dir().join("databaze.db"),
+            default_identity: None,
+            relays: vec![RelayConfig {
+                url: "wss://nos.lol".to_string(),
+                read: true,
+                write: true,
+            }],
+            cache: Default::default(),
+            retention: Default::default(),
+            lanes: vec![],
+            ui: Default::default(),
+        }
+    }
+
+    /// Loads configuration from all its sources and validates it. If the main
+    /// configuration file does not exist yet, it is created with default values
+    /// except for path to database.
+    pub async fn load(dirs: &ProjectDirs) -> Result<Config, LoadError> {
+        let defaults = Config::defaults(dirs);
+        let file = dirs.config_dir().join(CONFIG_FILE);
+
+        let exists = tokio::fs::try_exists(&file)
+            .await
+            .map_err(LoadError::ConfigFile)?;
+
+        if !exists {
+            // Path to database is left out, so that it follows data directory.
+            let mut table =
+                toml::Table::try_from(&defaults).map_err(|e| LoadError::Invalid(e.to_string()))?;
+            table.remove("db_file");
+            let toml =
+                toml::to_string_pretty(&table).map_err(|e| LoadError::Invalid(e.to_string()))?;
+            tokio::fs::write(&file, toml)
+                .await
+                .map_err(LoadError::ConfigFile)?;
+        }
+
+        let mut builder = ::config::Config::builder()
+            .add_source(File::from_str(
+                &toml::to_string(&defaults).map_err(|e| LoadError::Invalid(e.to_string()))?,
+                FileFormat::Toml,
+            ))
+            .add_source(File::from(file.as_ref()).required(false));
+
+        for f in config_files(&dirs.config_dir().join(CONFIG_DIR)).await? {
+            builder = builder.add_source(File::from(f.as_ref()));
+        }
+
+        let config: Config = builder
+            .add_source(environment())
+            .build()
+            .and_then(|c| c.try_deserialize())
+            .map_err(LoadError::Config)?;
+
+        config.validate().map_err(LoadError::Invalid)?;
+
+        Ok(config)
+    }
+
+    /// Checks that the configuration makes sense.
+    pub fn validate(&self) -> Result<(), String> {
+        for relay in &self.relays {
+            match Url::parse(&relay.url) {
+                Ok(url) if url.scheme() == "ws" || url.scheme() == "wss" => {}
+                Ok(_) => Err(format!("Relay {} is not a websocket URL.", relay.url))?,
+                Err(e) => Err(format!("Relay {} is not a valid URL: {e}.", relay.url))?,
+            }
+        }
+
+        if self.cache.lane_notes == 0 {
+            Err("Lanes have to be allowed to hold at least one note.")?;
+        }
+
+        if self.retention.interval_minutes == 0 {
+            Err("Database pruning interval has to be at least a minute.")?;
+        }
+
+        if self.ui.lane_width < 200 {
+            Err(format!(
+                "Lane width {} is too small, it has to be at least 200.",
+                self.ui.lane_width
+            ))?;
+        }
+
+        let mut names = HashSet::new();
+        for preset in &self.lanes {
+            if preset.name.trim().is_empty() {
+                Err("Lane preset has to have a name.")?;
+            }
+            if !names.insert(preset.name.as_str()) {
+                Err(format!("Lane preset '{}' is defined twice.", preset.name))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Source of configuration from environment variables. Prefix is separated
+/// by single underscore, nested keys by double one.
+fn environment() -> Environment {
+    Environment::with_prefix(ENV_PREFIX)
+        .prefix_separator("_")
+        .separator("__")
+        .try_parsing(true)
+}
+
+/// Saves lane `presets` into a file in configuration directory, replacing
+/// presets defined in files before it.
+pub async fn save_lane_presets(
+    dirs: &ProjectDirs,
+    presets: &[LanePreset],
+) -> Result<(), SaveError> {
+    #[derive(Serialize)]
+    struct Lanes<'a> {
+        lanes: &'a [LanePreset],
+    }
+
+    let dir = dirs.config_dir().join(CONFIG_DIR);
+    let toml = toml::to_string_pretty(&Lanes { lanes: presets })?;
+
+    tokio::fs::create_dir_all(&dir).await?;
+    Ok(tokio::fs::write(dir.join(LANES_FILE), toml).await?)
+}
+
+/// Lists all TOML files in `dir` sorted by their names. Returns nothing
+/// if the directory does not exist.
+async fn config_files(dir: &Path) -> Result<Vec<PathBuf>, LoadError> {
+    if !tokio::fs::try_exists(dir)
+        .await
+        .map_err(LoadError::ConfigFile)?
+    {
+        return Ok(vec![]);
+    }
+
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .map_err(LoadError::ConfigFile)?;
+
+    while let Some(entry) = entries.next_entry().await.map_err(LoadError::ConfigFile)? {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "toml") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            db_file: PathBuf::from("test.db"),
+            default_identity: None,
+            relays: vec![RelayConfig {
+                url: "wss://relay.example.com".to_string(),
+                read: true,
+                write: false,
+            }],
+            cache: Default::default(),
+            retention: Default::default(),
+            lanes: vec![],
+            ui: Default::default(),
+        }
+    }
+
+    #[test]
+    fn valid_config() {
+        assert!(config().validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_relay() {
+        let mut c = config();
+        c.relays[0].url = "https://relay.example.com".to_string();
+        assert!(c.validate().is_err());
+    }
+
+    #[test]
+    fn zero_pruning_interval() {
+        let mut c = config();
+        c.retention.interval_minutes = 0;
+        assert!(c.validate().is_err());
+    }
+
+    #[test]
+    fn duplicate_presets() {
+        let mut c = config();
+        let preset = LanePreset {
+            name: "Bitcoin".to_string(),
+            query: "#bitcoin".to_string(),
+        };
+        c.lanes = vec![preset.clone(), preset];
+        assert!(c.validate().is_err());
+    }
+
+    #[test]
+    fn parse_toml() {
+        let c: Config = toml::from_str(
+            r#"
+db_file = "/tmp/gnostique.db"
+
+[[relays]]
+url = "wss://nos.lol"
+write = false
+
+[cache]
+lane_notes = 50
+"#,
+        )
+        .unwrap();
+
+        assert!(c.relays[0].read);
+        assert!(!c.relays[0].write);
+        assert_eq!(c.cache.lane_notes, 50);
+        assert_eq!(c.ui.lane_width, 600);
+    }
+
+    #[test]
+    fn environment_overrides() {
+        let variables =
+            ::config::Map::from([("GNOSTIQUE_CACHE__LANE_NOTES".to_string(), "42".to_string())]);
+
+        let c: Config = ::config::Config::builder()
+            .add_source(File::from_str(
+                &toml::to_string(&config()).unwrap(),
+                FileFormat::Toml,
+            ))
+            .add_source(environment().source(Some(variables)))
+            .build()
+            .and_then(|c| c.try_deserialize())
+            .unwrap();
+
+        assert_eq!(c.cache.lane_notes, 42);
+        assert_eq!(c.relays[0].url, "wss://relay.example.com");
+    }
 }
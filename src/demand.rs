@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -8,6 +8,7 @@ use tracing::{debug, info};
 
 use crate::incoming::Incoming;
 use crate::nostr::preview::Preview;
+use crate::nostr::subscriptions::AUTHORS_PER_FILTER;
 
 #[derive(Clone)]
 pub struct Demand(Arc<DemandInner>);
@@ -32,6 +33,8 @@ impl Demand {
 
     // TODO: Clean up `notes` and `metadata`
 
+    /// Requests metadata of `pubkey` from `relay`s, or from all relays
+    /// if client is not connected to any of them.
     pub async fn metadata(&self, pubkey: XOnlyPublicKey, relay: Vec<Url>) {
         let elapsed = self
             .0
@@ -52,23 +55,104 @@ impl Demand {
 
                 info!("Requesting metadata {}.", pubkey.to_bech32().unwrap());
 
+                let sub = vec![Filter::new()
+                    .kind(Kind::Metadata)
+                    .author(pubkey.to_string())
+                    .limit(1)];
+
                 let relays = self.0.client.relays().await;
-                // TODO: Try more relays.
-                let relay = relay.first().expect("Relays should be multiple");
-                if let Some(r) = relays.get(relay) {
-                    r.req_events_of(
-                        vec![Filter::new()
-                            .kind(Kind::Metadata)
-                            .author(pubkey.to_string())
-                            .limit(1)],
-                        Duration::from_secs(3),
-                        FilterOptions::ExitOnEOSE,
-                    );
+                let known = relays
+                    .iter()
+                    .filter(|(url, _)| relay.contains(url))
+                    .collect::<Vec<_>>();
+
+                if known.is_empty() {
+                    self.0.client.req_events_of(sub, None).await;
+                } else {
+                    for (_, r) in known {
+                        r.req_events_of(
+                            sub.clone(),
+                            Duration::from_secs(3),
+                            FilterOptions::ExitOnEOSE,
+                        );
+                    }
                 }
             }
         };
     }
 
+    /// Requests relay lists (NIP-65) of `authors` from all relays.
+    pub async fn relay_lists(&self, authors: Vec<XOnlyPublicKey>) {
+        if !authors.is_empty() {
+            info!("Requesting relay lists of {} authors.", authors.len());
+
+            let filters = authors
+                .chunks(AUTHORS_PER_FILTER)
+                .map(|authors| {
+                    Filter::new()
+                        .kind(Kind::RelayList)
+                        .authors(authors.iter().map(|pk| pk.to_string()).collect())
+                })
+                .collect();
+
+            self.0.client.req_events_of(filters, None).await;
+        }
+    }
+
+    /// Looks up contact list (NIP-02) of `author` at all relays. Returns what
+    /// they sent once all of them finished sending it, or after a timeout.
+    pub async fn contact_list(
+        &self,
+        author: XOnlyPublicKey,
+    ) -> Result<Vec<Event>, nostr_sdk::client::Error> {
+        info!(
+            "Requesting contact list of {}.",
+            author.to_bech32().unwrap()
+        );
+
+        self.0
+            .client
+            .get_events_of(
+                vec![Filter::new()
+                    .kind(Kind::ContactList)
+                    .author(author.to_string())
+                    .limit(1)],
+                Some(Duration::from_secs(10)),
+            )
+            .await
+    }
+
+    /// Requests history described by `filters` from all relays. Filters
+    /// with search (NIP-50) are sent only to `search_relays`, others
+    /// would ignore the search.
+    pub async fn history(&self, filters: Vec<Filter>, search_relays: &HashSet<Url>) {
+        let (search, filters): (Vec<_>, Vec<_>) =
+            filters.into_iter().partition(|f| f.search.is_some());
+
+        if !filters.is_empty() {
+            debug!("Requesting history {filters:?}.");
+            self.0.client.req_events_of(filters, None).await;
+        }
+
+        if !search.is_empty() {
+            debug!("Requesting search history {search:?}.");
+            for (_, r) in self
+                .0
+                .client
+                .relays()
+                .await
+                .iter()
+                .filter(|(url, _)| search_relays.contains(url))
+            {
+                r.req_events_of(
+                    search.clone(),
+                    Duration::from_secs(3),
+                    FilterOptions::ExitOnEOSE,
+                );
+            }
+        }
+    }
+
     pub async fn text_note(&self, event_id: EventId, relay: Option<Url>) {
         let elapsed = self
             .0
@@ -106,12 +190,13 @@ impl Demand {
         };
     }
 
-    pub async fn link_preview(&self, url: &reqwest::Url) {
+    pub async fn link_preview(&self, url: &reqwest::Url) -> Preview {
         info!("Requesting preview for {}", url);
         let preview = Preview::create(url.clone()).await;
         self.0
             .external
-            .send(Incoming::Preview(preview))
+            .send(Incoming::Preview(preview.clone()))
             .unwrap_or_default();
+        preview
     }
 }
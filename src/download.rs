@@ -59,6 +59,43 @@ impl Download {
         }
     }
 
+    /// Removes the least recently used files from cache until it takes
+    /// at most `max_bytes`. Returns number of bytes removed.
+    pub async fn prune(&self, max_bytes: u64) -> std::io::Result<u64> {
+        let cache = self.0.dirs.cache_dir().join("bitmaps");
+
+        let mut entries = match tokio::fs::read_dir(&cache).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            // Files being downloaded have an extension, they are left alone.
+            if metadata.is_file() && entry.path().extension().is_none() {
+                let used = metadata.accessed().or_else(|_| metadata.modified())?;
+                files.push((used, metadata.len(), entry.path()));
+            }
+        }
+
+        let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+        let mut removed = 0;
+
+        files.sort();
+        for (_, len, file) in files {
+            if size <= max_bytes {
+                break;
+            }
+            tokio::fs::remove_file(&file).await?;
+            size -= len;
+            removed += len;
+        }
+
+        Ok(removed)
+    }
+
     pub async fn to_cached_file(&self, url: &Url) -> DownloadResult {
         let url_s = url.to_string();
         let filename = sha256::Hash::hash(url_s.as_bytes()).to_string();
@@ -1,41 +1,151 @@
 use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use nostr_sdk::prelude::*;
 
-fn following() -> Vec<XOnlyPublicKey> {
-    [
-        "npub1xtscya34g58tk0z605fvr788k263gsu6cy9x0mhnm87echrgufzsevkk5s", // jb55
-        "npub17u5dneh8qjp43ecfxr6u5e9sjamsmxyuekrg2nlxrrk6nj9rsyrqywt4tp", // lopp
-        "npub1ktt8phjnkfmfrsxrgqpztdjuxk3x6psf80xyray0l3c7pyrln49qhkyhz0", // 0xtr1
-        "npub1pyp9fqq60689ppds9ec3vghsm7s6s4grfya0y342g2hs3a0y6t0segc0qq", // DylanLeClair_
-        "npub1rxysxnjkhrmqd3ey73dp9n5y5yvyzcs64acc9g0k2epcpwwyya4spvhnp8", // BTCsessions
-    ]
-    .into_iter()
-    .map(|s| XOnlyPublicKey::from_bech32(s).unwrap())
-    .collect()
-}
-
-#[derive(Clone, Debug)]
+/// Contact list (NIP-02) of an identity.
+#[derive(Clone, Debug, Default)]
 pub struct Follow {
     following: HashSet<XOnlyPublicKey>,
+
+    /// Contact list event this follow list was made of, if any. Its tags
+    /// (including petnames and relays) and content are preserved when
+    /// a new contact list is published.
+    event: Option<Arc<Event>>,
 }
 
 impl Follow {
+    /// Creates an empty follow list.
     pub fn new() -> Follow {
+        Default::default()
+    }
+
+    /// Creates follow list from contact list `event`.
+    pub fn from_event(event: Event) -> Follow {
+        let following = event.tags.iter().filter_map(followed_pubkey).collect();
+
         Follow {
-            following: following().into_iter().collect(),
+            following,
+            event: Some(Arc::new(event)),
         }
     }
 
-    // TODO: Batch
-    pub fn subscriptions(&self) -> Filter {
-        Filter::new()
-            .kinds(vec![Kind::TextNote, Kind::Repost])
-            .authors(self.following.iter().map(|pk| pk.to_string()).collect())
-            .limit(30)
+    /// Time of creation of the contact list event, if this follow list has one.
+    pub fn created_at(&self) -> Option<Timestamp> {
+        self.event.as_ref().map(|e| e.created_at)
+    }
+
+    /// Creates new follow list that additionally contains `pubkey`.
+    pub fn follow(&self, pubkey: XOnlyPublicKey) -> Follow {
+        let mut follow = self.clone();
+        follow.following.insert(pubkey);
+        follow
+    }
+
+    /// Creates new follow list without `pubkey`.
+    pub fn unfollow(&self, pubkey: &XOnlyPublicKey) -> Follow {
+        let mut follow = self.clone();
+        follow.following.remove(pubkey);
+        follow
+    }
+
+    /// Prepares contact list event of this follow list. Tags of the original
+    /// event are kept as they were, unless they belong to unfollowed authors,
+    /// newly followed authors are appended.
+    pub fn to_event_builder(&self) -> EventBuilder {
+        let mut tags = self
+            .event
+            .iter()
+            .flat_map(|e| e.tags.iter())
+            .filter(|t| followed_pubkey(t).map_or(true, |pk| self.following.contains(&pk)))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let present = tags
+            .iter()
+            .filter_map(followed_pubkey)
+            .collect::<HashSet<_>>();
+
+        tags.extend(
+            self.following
+                .difference(&present)
+                .map(|pk| Tag::PubKey(*pk, None)),
+        );
+
+        let content = self
+            .event
+            .as_ref()
+            .map(|e| e.content.clone())
+            .unwrap_or_default();
+
+        EventBuilder::new(Kind::ContactList, content, &tags)
+    }
+
+    /// Public keys of all followed authors.
+    pub fn pubkeys(&self) -> impl Iterator<Item = &XOnlyPublicKey> {
+        self.following.iter()
     }
 
     pub fn follows(&self, pubkey: &XOnlyPublicKey) -> bool {
         self.following.contains(pubkey)
     }
 }
+
+impl FromIterator<XOnlyPublicKey> for Follow {
+    fn from_iter<T: IntoIterator<Item = XOnlyPublicKey>>(iter: T) -> Follow {
+        Follow {
+            following: iter.into_iter().collect(),
+            event: None,
+        }
+    }
+}
+
+/// If the tag is a contact (`p` tag), returns its pubkey.
+fn followed_pubkey(tag: &Tag) -> Option<XOnlyPublicKey> {
+    match tag.as_vec().as_slice() {
+        [p, pubkey, ..] if p == "p" => XOnlyPublicKey::from_str(pubkey).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::*;
+
+    use super::Follow;
+
+    #[test]
+    fn petnames_kept() {
+        let keys = Keys::generate();
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+        let carol = Keys::generate().public_key();
+
+        let tags = vec![
+            Tag::parse(vec![
+                "p",
+                &alice.to_string(),
+                "wss://relay.example.com",
+                "alice",
+            ])
+            .unwrap(),
+            Tag::parse(vec!["p", &bob.to_string(), "", "bob"]).unwrap(),
+        ];
+        let event = EventBuilder::new(Kind::ContactList, "{}", &tags)
+            .to_event(&keys)
+            .unwrap();
+
+        let follow = Follow::from_event(event).unfollow(&bob).follow(carol);
+        let event = follow.to_event_builder().to_event(&keys).unwrap();
+
+        assert_eq!(event.content, "{}");
+        assert_eq!(event.tags.len(), 2);
+        assert_eq!(event.tags[0], tags[0]);
+
+        let follow = Follow::from_event(event);
+        assert!(follow.follows(&alice));
+        assert!(!follow.follows(&bob));
+        assert!(follow.follows(&carol));
+    }
+}
@@ -1,23 +1,86 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 
-use age::Decryptor;
 use directories::ProjectDirs;
-use gtk::{gdk, glib};
-use nostr_sdk::prelude::{Event, EventId, Metadata, XOnlyPublicKey};
-use nostr_sdk::{Client, Filter, Options, Relay, RelayPoolOptions, Timestamp, Url};
+use futures_util::future;
+use nostr_sdk::prelude::{Event, EventBuilder, EventId, Keys, Kind, XOnlyPublicKey};
+use nostr_sdk::{Client, Options, Relay, RelayOptions, RelayPoolOptions, Url};
 use secrecy::SecretString;
-use sqlx::{query, SqlitePool};
-use tokio::io::AsyncReadExt;
-use tokio::sync::broadcast;
+use sqlx::SqlitePool;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{debug, info, warn};
 
+use crate::config::{save_lane_presets, Config, LanePreset, SaveError};
 use crate::demand::Demand;
 use crate::download::Download;
-use crate::identity::Identity;
-use crate::incoming::Incoming;
+use crate::follow::Follow;
+use crate::identity::{Identity, Keyring, KeyringError, KeyringFile};
+use crate::incoming::{published_event, Incoming};
+use crate::nostr::nip46::{BunkerUri, Nip46Error, RemoteSigner};
 use crate::nostr::preview::Preview;
-use crate::nostr::{Persona, ReceivedEvent};
+use crate::nostr::subscriptions::Subscription;
+use crate::nostr::{EventExt, Persona, ReceivedEvent};
+use crate::relays::{
+    parse_relay_list, relay_list_tags, route_filters, select_outboxes, RelayPolicy,
+};
+use crate::store::{EventStore, SavedLane, SqliteStore, StoreError};
+
+/// Maximum number of write relays of another author to connect to.
+const OUTBOX_RELAYS: usize = 3;
+
+/// Maximum number of relays connected to in order to reach other authors,
+/// besides relays of the user.
+const MAX_OUTBOX_RELAYS: usize = 30;
+
+#[derive(Debug)]
+pub enum PublishError {
+    /// Event could not be signed by keys of the identity.
+    Sign(nostr_sdk::nostr::event::builder::Error),
+    /// Remote signer did not sign the event.
+    Remote(Nip46Error),
+    /// Remote signer of the identity has not been connected yet.
+    NotConnected,
+    /// Contact list of the identity has not been looked up yet, so a new one
+    /// could replace an existing one by only what has been changed.
+    NoContactList,
+    Client(nostr_sdk::client::Error),
+}
+
+impl std::fmt::Display for PublishError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PublishError::Sign(e) => write!(f, "Could not sign event: {e}"),
+            PublishError::Remote(e) => write!(f, "{e}"),
+            PublishError::NotConnected => write!(f, "Remote signer is not connected yet."),
+            PublishError::NoContactList => write!(
+                f,
+                "Contact list has not been loaded yet, try again once it is."
+            ),
+            PublishError::Client(e) => write!(f, "Could not send event: {e}"),
+        }
+    }
+}
+
+impl From<nostr_sdk::nostr::event::builder::Error> for PublishError {
+    fn from(e: nostr_sdk::nostr::event::builder::Error) -> Self {
+        PublishError::Sign(e)
+    }
+}
+
+impl From<Nip46Error> for PublishError {
+    fn from(e: Nip46Error) -> Self {
+        PublishError::Remote(e)
+    }
+}
+
+impl From<nostr_sdk::client::Error> for PublishError {
+    fn from(e: nostr_sdk::client::Error) -> Self {
+        PublishError::Client(e)
+    }
+}
 
 /// Gnostique session. In order to use Gnostique, an instance of this
 /// has to exist.
@@ -31,9 +94,35 @@ impl Debug for Gnostique {
 }
 
 struct GnostiqueInner {
-    pool: SqlitePool,
+    config: Config,
+    store: Arc<dyn EventStore>,
     dirs: ProjectDirs,
+    /// All identities of the user.
+    keyring: RwLock<Keyring>,
+    /// File the keyring is saved to.
+    keyring_file: RwLock<KeyringFile>,
+    /// Index of the current identity in keyring.
+    current: RwLock<usize>,
+    /// Keys of the current identity.
+    keys: RwLock<Keys>,
+    /// Remote signer of the current identity, if it has one (NIP-46).
+    signer: RwLock<Option<Arc<RemoteSigner>>>,
+    /// Remote signer being connected by [`Gnostique::spawn_signer`], if any.
+    signer_connecting: RwLock<Option<XOnlyPublicKey>>,
     client: Client,
+    /// Relays that are not managed by user but were connected in order
+    /// to reach other authors on their write relays (NIP-65).
+    outbox: Mutex<HashSet<Url>>,
+    /// Number of requests to subscribe relays so far, only the latest is carried out.
+    subscriptions: AtomicU64,
+    /// Held while relays are being subscribed.
+    subscribing: Mutex<()>,
+    /// Contact list of the current identity.
+    follow: RwLock<Follow>,
+    /// Identity whose contact list relays have been asked for and have answered.
+    follow_looked_up: RwLock<Option<XOnlyPublicKey>>,
+    /// Lane presets, initially from configuration, changed by user.
+    presets: RwLock<Vec<LanePreset>>,
     download: Download,
     demand: Demand,
     // TODO: Should this be Incoming or a new type?
@@ -41,16 +130,45 @@ struct GnostiqueInner {
 }
 
 impl Gnostique {
-    fn new(pool: SqlitePool, dirs: ProjectDirs, client: Client) -> Gnostique {
+    fn new(
+        config: Config,
+        store: Arc<dyn EventStore>,
+        dirs: ProjectDirs,
+        keyring: Keyring,
+        keyring_file: KeyringFile,
+    ) -> Result<Gnostique, KeyringError> {
         let (external_tx, _) = broadcast::channel(10);
-        Gnostique(Arc::new(GnostiqueInner {
+        let current = keyring.default_index();
+        let default = keyring.default_identity();
+        let keys = default
+            .nostr_key()
+            .ok_or_else(|| KeyringError::NoKey(default.name().to_string()))?;
+        let client = Client::new(&keys);
+        Ok(Gnostique(Arc::new(GnostiqueInner {
+            presets: RwLock::new(config.lanes.clone()),
+            config,
             demand: Demand::new(client.clone(), external_tx.clone()),
             download: Download::new(dirs.clone()),
             dirs,
+            keyring: RwLock::new(keyring),
+            keyring_file: RwLock::new(keyring_file),
+            current: RwLock::new(current),
+            keys: RwLock::new(keys),
+            signer: Default::default(),
+            signer_connecting: Default::default(),
             client,
-            pool,
+            outbox: Default::default(),
+            subscriptions: Default::default(),
+            subscribing: Default::default(),
+            follow: Default::default(),
+            follow_looked_up: Default::default(),
+            store,
             external: external_tx,
-        }))
+        })))
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.0.config
     }
 
     pub fn demand(&self) -> &Demand {
@@ -67,151 +185,711 @@ impl Gnostique {
         self.0.external.subscribe()
     }
 
-    pub fn pool(&self) -> &SqlitePool {
-        &self.0.pool
+    pub fn store(&self) -> &dyn EventStore {
+        self.0.store.as_ref()
     }
 
     pub fn client(&self) -> &Client {
         &self.0.client
     }
 
-    // pub fn dirs(&self) -> &ProjectDirs {
-    //     &self.0.dirs
-    // }
+    /// Public key of the current identity.
+    pub fn pubkey(&self) -> XOnlyPublicKey {
+        self.0.keys.read().unwrap().public_key()
+    }
+
+    /// Current identity has no secret key, so it can read but not publish.
+    pub fn read_only(&self) -> bool {
+        let keyring = self.0.keyring.read().unwrap();
+        keyring
+            .get(self.current_identity())
+            .map_or(true, Identity::is_read_only)
+    }
+
+    /// Remote signer of the current identity and keys to sign requests to it with.
+    fn current_bunker(&self) -> Option<(BunkerUri, Keys)> {
+        let keyring = self.0.keyring.read().unwrap();
+        keyring
+            .get(self.current_identity())
+            .and_then(Identity::bunker)
+    }
+
+    /// Connects to remote signer of the current identity, if it has one (NIP-46).
+    /// What the signer does is reported as [`Incoming::Signer`].
+    pub async fn connect_signer(&self) {
+        let Some((uri, keys)) = self.current_bunker() else {
+            *self.0.signer.write().unwrap() = None;
+            return;
+        };
+
+        info!("Connecting to remote signer {}.", uri.signer);
+
+        let external = self.0.external.clone();
+        let signer = RemoteSigner::over_relays(uri, keys, move |status| {
+            external.send(Incoming::Signer(status)).unwrap_or_default();
+        })
+        .await;
+
+        // Identity might have been switched in the meantime.
+        if self.current_bunker().map(|(u, _)| u.signer) != Some(signer.signer()) {
+            return;
+        }
+        *self.0.signer.write().unwrap() = Some(signer.clone());
 
-    /// Stores event and relay from which it arrives into database,
-    /// does nothing when already exist.
-    pub async fn store_event(&self, event: &ReceivedEvent) {
-        let ReceivedEvent { event, relay } = event;
-        let id = event.id.as_bytes().to_vec();
-        let json = serde_json::to_string(event).unwrap();
+        if let Err(e) = signer.connect().await {
+            warn!("Could not connect to remote signer: {e}");
+            return;
+        }
+
+        // Events signed as someone else would not be accepted.
+        match signer.get_public_key().await {
+            Ok(pubkey) if pubkey != self.pubkey() => {
+                warn!("Remote signer signs as {pubkey}, not as {}.", self.pubkey())
+            }
+            Ok(_) => info!("Connected to remote signer {}.", signer.signer()),
+            Err(e) => warn!("Could not get public key of remote signer: {e}"),
+        }
+    }
+
+    /// Connects to remote signer of the current identity in the background,
+    /// unless it is connected or being connected already.
+    fn spawn_signer(&self) {
+        let Some((uri, _)) = self.current_bunker() else {
+            *self.0.signer.write().unwrap() = None;
+            return;
+        };
+
+        let connected = self.0.signer.read().unwrap().as_ref().map(|s| s.signer());
+        let mut connecting = self.0.signer_connecting.write().unwrap();
+        if connected != Some(uri.signer) && *connecting != Some(uri.signer) {
+            *connecting = Some(uri.signer);
+
+            let gnostique = self.clone();
+            tokio::spawn(async move {
+                gnostique.connect_signer().await;
+                let mut connecting = gnostique.0.signer_connecting.write().unwrap();
+                if *connecting == Some(uri.signer) {
+                    *connecting = None;
+                }
+            });
+        }
+    }
+
+    /// All identities of the user.
+    pub fn identities(&self) -> Vec<Identity> {
+        self.0.keyring.read().unwrap().identities().to_vec()
+    }
+
+    /// Index of the current identity among [`Gnostique::identities`].
+    pub fn current_identity(&self) -> usize {
+        *self.0.current.read().unwrap()
+    }
+
+    /// Makes identity at `index` of the keyring the current one. The client signs
+    /// with its keys from now on and its stored contact list replaces the current
+    /// one, which is returned. Nothing happens if there is no such identity.
+    /// The identity also becomes the default one for the next start.
+    pub async fn switch_identity(&self, index: usize) -> Option<Follow> {
+        let keys = {
+            let mut keyring = self.0.keyring.write().unwrap();
+            let keys = keyring.get(index)?.nostr_key()?;
+            keyring.set_default(index);
+            keys
+        };
+
+        info!("Switching to identity {}.", keys.public_key());
+
+        self.client().set_keys(&keys).await;
+        *self.0.keys.write().unwrap() = keys;
+        *self.0.current.write().unwrap() = index;
+        *self.0.follow.write().unwrap() = Follow::default();
+        *self.0.signer.write().unwrap() = None;
+        self.spawn_signer();
+
+        self.load_follow().await;
+
+        if let Err(e) = self.save_keyring().await {
+            warn!("Could not save default identity: {e}");
+        }
+
+        Some(self.follow())
+    }
+
+    /// Replaces all identities by `identities` and saves them. The current
+    /// identity cannot be removed, but it may have gained or lost its secret key.
+    pub async fn set_identities(&self, identities: Vec<Identity>) -> Result<(), KeyringError> {
+        let keys = {
+            let mut keyring = self.0.keyring.write().unwrap();
+            let current = keyring.replace(identities, self.pubkey())?;
+            *self.0.current.write().unwrap() = current;
+            keyring.get(current).and_then(Identity::nostr_key)
+        };
+
+        if let Some(keys) = keys {
+            self.client().set_keys(&keys).await;
+            *self.0.keys.write().unwrap() = keys;
+        }
+        self.spawn_signer();
+
+        self.0
+            .external
+            .send(Incoming::IdentitiesChanged)
+            .unwrap_or_default();
+
+        self.save_keyring().await
+    }
+
+    /// Encrypts identities by `new` password from now on, if `old` is the current one.
+    pub async fn change_password(
+        &self,
+        old: SecretString,
+        new: SecretString,
+    ) -> Result<(), KeyringError> {
+        let file = {
+            let file = self.0.keyring_file.read().unwrap();
+            if !file.has_password(&old) {
+                return Err(KeyringError::WrongPassword);
+            }
+            file.with_password(new)
+        };
+
+        let keyring = self.0.keyring.read().unwrap().clone();
+        file.save(&keyring).await?;
+        *self.0.keyring_file.write().unwrap() = file;
+        Ok(())
+    }
 
-        query!("INSERT INTO textnotes (id, event) VALUES (?, ?)", id, json)
-            .execute(self.pool())
+    /// Writes encrypted keyring into identity file.
+    async fn save_keyring(&self) -> Result<(), KeyringError> {
+        let keyring = self.0.keyring.read().unwrap().clone();
+        let file = self.0.keyring_file.read().unwrap().clone();
+        file.save(&keyring).await
+    }
+
+    /// Signs event with the current identity, or has it signed by its remote
+    /// signer, and sends it to relays.
+    pub async fn publish(&self, builder: EventBuilder) -> Result<Event, PublishError> {
+        let signer = self.0.signer.read().unwrap().clone();
+
+        let event = if let Some(signer) = signer {
+            signer
+                .sign(builder.to_unsigned_event(self.pubkey()))
+                .await?
+        } else if self.current_bunker().is_some() {
+            return Err(PublishError::NotConnected);
+        } else {
+            let keys = self.0.keys.read().unwrap().clone();
+            builder.to_event(&keys)?
+        };
+
+        self.client().send_event(event.clone()).await?;
+        self.keep_published(&event).await;
+        Ok(event)
+    }
+
+    /// Stores own published `event` as if write relays sent it back, so it
+    /// does not have to make the round trip to show up. Text notes are passed
+    /// on as incoming, thus replies appear in their threads and reactions by
+    /// their notes straight away.
+    async fn keep_published(&self, event: &Event) {
+        for (relay, r) in self.client().relays().await {
+            if r.opts().write() {
+                let received = ReceivedEvent {
+                    relay,
+                    event: event.clone(),
+                };
+                if let Err(e) = self.store().store_event(&received).await {
+                    warn!("Could not store published event {}: {e}", event.id);
+                }
+            }
+        }
+
+        for incoming in published_event(self, event.clone()).await {
+            self.0.external.send(incoming).unwrap_or_default();
+        }
+    }
+
+    /// Publishes relay list metadata (NIP-65) made of enabled relays of `policies`.
+    pub async fn publish_relay_list(
+        &self,
+        policies: &[RelayPolicy],
+    ) -> Result<Event, PublishError> {
+        let tags = relay_list_tags(policies);
+        self.publish(EventBuilder::new(Kind::RelayList, "", &tags))
             .await
-            .unwrap();
+    }
 
-        let id = event.id.as_bytes().to_vec();
-        let relay_str = relay.to_string();
+    /// Lanes saved under a name.
+    pub fn lane_presets(&self) -> Vec<LanePreset> {
+        self.0.presets.read().unwrap().clone()
+    }
 
-        query!(
-            "INSERT INTO textnotes_relays (textnote, relay) VALUES (?, ?)",
-            id,
-            relay_str
-        )
-        .execute(self.pool())
-        .await
-        .unwrap();
+    /// Saves lane `preset` into configuration, replacing preset of the same name.
+    pub async fn save_lane_preset(&self, preset: LanePreset) -> Result<(), SaveError> {
+        let presets = {
+            let mut presets = self.0.presets.write().unwrap();
+            match presets.iter_mut().find(|p| p.name == preset.name) {
+                Some(p) => *p = preset,
+                None => presets.push(preset),
+            }
+            presets.clone()
+        };
+
+        save_lane_presets(&self.0.dirs, &presets).await
     }
 
-    pub async fn textnote_relays(&self, event_id: EventId) -> Vec<Url> {
-        let id: &[u8] = event_id.as_bytes();
+    /// Lanes saved on last exit, in their order, following current contact list.
+    pub async fn saved_lanes(&self) -> Vec<SavedLane> {
+        match self.store().lanes().await {
+            Ok(lanes) => lanes
+                .into_iter()
+                .map(|l| SavedLane {
+                    subscription: l.subscription.with_follow(&self.follow()),
+                    ..l
+                })
+                .collect(),
+            Err(e) => {
+                warn!("Could not load saved lanes: {e}");
+                vec![]
+            }
+        }
+    }
 
-        query!(
-            r#"
-SELECT url FROM relays
-WHERE url IN (SELECT relay FROM textnotes_relays WHERE textnote = ?)"#,
-            id
-        )
-        .map(|r| Url::parse(&r.url).unwrap())
-        .fetch_all(self.pool())
-        .await
-        .unwrap_or_default()
+    /// Contact list of the current identity.
+    pub fn follow(&self) -> Follow {
+        self.0.follow.read().unwrap().clone()
+    }
+
+    /// Loads contact list of the current identity from database.
+    pub async fn load_follow(&self) {
+        match self
+            .store()
+            .get_replaceable(self.pubkey(), Kind::ContactList)
+            .await
+        {
+            Ok(Some(event)) => *self.0.follow.write().unwrap() = Follow::from_event(event),
+            Ok(None) => {}
+            Err(e) => warn!("Could not load contact list: {e}"),
+        }
+    }
+
+    /// Stores received contact list `event`. If it is a newer contact list
+    /// of the current identity, it replaces the current one, which is returned.
+    pub async fn contact_list_received(&self, event: &Event) -> Option<Follow> {
+        if let Err(e) = self.store().store_replaceable(event).await {
+            warn!("Could not store contact list: {e}");
+        }
+
+        if event.pubkey != self.pubkey() {
+            return None;
+        }
+
+        let mut follow = self.0.follow.write().unwrap();
+        if follow.created_at().map_or(true, |c| c < event.created_at) {
+            *follow = Follow::from_event(event.clone());
+            Some(follow.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Looks up contact list of the current identity at relays in the background.
+    /// Once they answer, a contact list may be published even if they have none.
+    fn spawn_follow_lookup(&self) {
+        let gnostique = self.clone();
+        tokio::spawn(async move {
+            let pubkey = gnostique.pubkey();
+            match gnostique.demand().contact_list(pubkey).await {
+                Ok(events) => {
+                    for event in events {
+                        if let Some(follow) = gnostique.contact_list_received(&event).await {
+                            gnostique
+                                .0
+                                .external
+                                .send(Incoming::Follow(follow))
+                                .unwrap_or_default();
+                        }
+                    }
+                    *gnostique.0.follow_looked_up.write().unwrap() = Some(pubkey);
+                }
+                Err(e) => warn!("Could not look up contact list: {e}"),
+            }
+        });
     }
 
-    /// Attempts to obtain text note from database with a given `id`,
-    /// runs in relm4 executor.
+    /// Follows or unfollows `pubkey` by publishing a new contact list.
+    /// Contact list of the identity has to be looked up first, otherwise
+    /// it is looked up again and [`PublishError::NoContactList`] is returned.
+    pub async fn set_following(
+        &self,
+        pubkey: XOnlyPublicKey,
+        following: bool,
+    ) -> Result<Follow, PublishError> {
+        let follow = self.follow();
+        let looked_up = *self.0.follow_looked_up.read().unwrap() == Some(self.pubkey());
+        if follow.created_at().is_none() && !looked_up {
+            self.spawn_follow_lookup();
+            return Err(PublishError::NoContactList);
+        }
+
+        let follow = if following {
+            follow.follow(pubkey)
+        } else {
+            follow.unfollow(&pubkey)
+        };
+
+        let event = self.publish(follow.to_event_builder()).await?;
+
+        if let Some(follow) = self.contact_list_received(&event).await {
+            self.0
+                .external
+                .send(Incoming::Follow(follow))
+                .unwrap_or_default();
+        }
+
+        Ok(self.follow())
+    }
+
+    /// Attempts to obtain stored text note with a given `id`.
     pub async fn get_note(&self, id: EventId) -> Option<Event> {
-        let id_bytes = id.as_bytes().to_vec();
+        match self.store().get_event(id).await {
+            Ok(event) => event.filter(|e| e.kind == Kind::TextNote),
+            Err(e) => {
+                warn!("Could not load note {id}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Attempts to obtain stored preview of link to `url`.
+    pub async fn get_link_preview(&self, url: &reqwest::Url) -> Option<Preview> {
+        self.store().get_preview(url).await.unwrap_or_else(|e| {
+            warn!("Could not load preview of {url}: {e}");
+            None
+        })
+    }
 
-        query!("SELECT event FROM textnotes WHERE id = ?", id_bytes)
-            .fetch_optional(self.pool())
+    /// Deletes stored events according to retention policy. Events of the current
+    /// identity, of followed authors and bookmarked ones are kept.
+    pub async fn prune(&self) -> Result<u64, StoreError> {
+        let mut keep = self.follow().pubkeys().copied().collect::<Vec<_>>();
+        keep.push(self.pubkey());
+
+        self.store().prune(&self.config().retention, &keep).await
+    }
+
+    /// Relays that can be asked to search (NIP-50), according to their
+    /// stored information documents.
+    pub async fn search_relays(&self) -> HashSet<Url> {
+        self.store()
+            .search_relays()
             .await
-            .ok()
-            .flatten()
-            .and_then(|record| serde_json::from_str::<Event>(&record.event).ok())
+            .unwrap_or_else(|e| {
+                warn!("Could not load search relays: {e}");
+                vec![]
+            })
+            .into_iter()
+            .collect()
     }
 
-    // TODO: Consider whether caching previews makes sense.
-    pub async fn get_link_preview(&self, url: &reqwest::Url) -> Option<Preview> {
-        None
-        //         use crate::nostr::preview::PreviewKind;
-
-        //         let url = url.to_string();
-        //         query!(
-        //             r#"
-        // SELECT url, kind AS "kind: PreviewKind", title, description, thumbnail, error, time
-        // FROM previews
-        // WHERE url = ?
-        // "#,
-        //             url
-        //         )
-        //         .fetch_optional(self.pool())
-        //         .await
-        //         .ok()
-        //         .flatten()
-        //         .and_then(|record| {
-        //             Some(Preview::new(
-        //                 Url::parse(&record.url).ok()?,
-        //                 record.kind,
-        //                 record.title,
-        //                 record.description,
-        //                 record
-        //                     .thumbnail
-        //                     .and_then(|bs| gdk::Texture::from_bytes(&glib::Bytes::from(&bs)).ok()),
-        //                 record.error,
-        //             ))
-        //         })
-    }
-
-    /// Attempts to obtain [`Person`] from database for a given `pubkey`, runs
-    /// in relm4 executor.
-    pub async fn get_persona(&self, pubkey: XOnlyPublicKey) -> Option<Persona> {
-        let pubkey_bytes: &[u8] = &pubkey.serialize();
-
-        query!(
-            r#"
-SELECT event, (unixepoch('now') - unixepoch(nip05_verified)) / 3600 AS "nip05_hours: u16"
-FROM metadata
-WHERE author = ?
-"#,
-            pubkey_bytes
+    /// If no relay is stored yet, relays from configuration are stored as enabled.
+    /// Relays that user disabled, even all of them, are left as they are.
+    pub async fn seed_relays(&self) {
+        let empty = match self.store().relay_policies().await {
+            Ok(policies) => policies.is_empty(),
+            Err(e) => {
+                warn!("Could not load relays: {e}");
+                return;
+            }
+        };
+
+        if empty {
+            for relay in &self.config().relays {
+                if let Some(url) = RelayPolicy::parse_url(&relay.url) {
+                    let policy = RelayPolicy {
+                        url,
+                        enabled: true,
+                        read: relay.read,
+                        write: relay.write,
+                    };
+
+                    if let Err(e) = self.store().set_relay_policy(&policy).await {
+                        warn!("Could not store relay {}: {e}", policy.url);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Brings relays of Nostr client in line with stored relay policies, relays
+    /// that are no longer enabled are removed, newly enabled are connected.
+    pub async fn apply_relays(&self) {
+        let policies = match self.store().relay_policies().await {
+            Ok(policies) => policies
+                .into_iter()
+                .filter(|p| p.enabled)
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("Could not load relays, keeping the current ones: {e}");
+                return;
+            }
+        };
+
+        let current = self.client().relays().await;
+        let mut outbox = self.0.outbox.lock().await;
+
+        // Relays enabled by user are no longer outbox relays.
+        outbox.retain(|url| !policies.iter().any(|p| &p.url == url));
+
+        for (url, relay) in &current {
+            let opts = relay.opts();
+            let keep = policies
+                .iter()
+                .any(|p| &p.url == url && p.read == opts.read() && p.write == opts.write())
+                || outbox.contains(url);
+
+            if !keep {
+                info!("Removing relay {url}.");
+                let _ = self.client().remove_relay(url.as_str()).await;
+            }
+        }
+
+        let current = self.client().relays().await;
+
+        drop(outbox);
+
+        for p in policies.iter().filter(|p| !current.contains_key(&p.url)) {
+            info!("Adding relay {}.", p.url);
+            let _ = self
+                .client()
+                .add_relay_with_opts(p.url.as_str(), None, RelayOptions::new(p.read, p.write))
+                .await;
+        }
+
+        self.client().connect().await;
+
+        self.0
+            .external
+            .send(Incoming::RelaysChanged)
+            .unwrap_or_default();
+    }
+
+    /// Connects to relays and asks them for what the session needs to start.
+    /// Everything before this works with local data only.
+    ///
+    /// Relays are subscribed to what lanes show once they report
+    /// [`Incoming::RelaysChanged`], not here.
+    pub async fn connect(&self) {
+        self.spawn_signer();
+        self.apply_relays().await;
+        self.request_identity().await;
+    }
+
+    /// Asks relays for contact list of the current identity and for relay lists
+    /// of the identity and of followed authors, so they can be reached (NIP-65).
+    pub async fn request_identity(&self) {
+        self.spawn_follow_lookup();
+
+        let mut authors = self.follow().pubkeys().copied().collect::<Vec<_>>();
+        authors.push(self.pubkey());
+        self.demand().relay_lists(authors).await;
+    }
+
+    /// Finds relays to which `author` publishes according to their relay list (NIP-65).
+    pub async fn write_relays(&self, author: XOnlyPublicKey) -> Vec<Url> {
+        self.store()
+            .get_replaceable(author, Kind::RelayList)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Could not load relay list: {e}");
+                None
+            })
+            .map(|e| parse_relay_list(&e))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| p.write)
+            .map(|p| p.url)
+            .collect()
+    }
+
+    /// Makes sure that client is connected to a few of `author`'s write relays,
+    /// so that author's events can be obtained from there. Returns the relays,
+    /// none if too many relays are connected already.
+    pub async fn connect_outbox(&self, author: XOnlyPublicKey) -> Vec<Url> {
+        self.connect_outboxes([author], false)
+            .await
+            .remove(&author)
+            .unwrap_or_default()
+    }
+
+    /// Like [`Gnostique::connect_outbox`] for each of `authors`, whose relay lists
+    /// are looked up concurrently. Returns write relays of each author. Relays
+    /// that are connected already are preferred, others are chosen by how many
+    /// of the authors they cover. If `replace`, relays connected for other
+    /// authors before are disconnected unless these need them too.
+    async fn connect_outboxes(
+        &self,
+        authors: impl IntoIterator<Item = XOnlyPublicKey>,
+        replace: bool,
+    ) -> HashMap<XOnlyPublicKey, Vec<Url>> {
+        let write_relays = future::join_all(
+            authors
+                .into_iter()
+                .map(|author| async move { (author, self.write_relays(author).await) }),
         )
-        .fetch_optional(self.pool())
         .await
-        .ok()
-        .flatten()
-        .and_then(|record| {
-            let mut persona = serde_json::from_str::<Event>(&record.event)
-                .and_then(|e| {
-                    serde_json::from_str::<Metadata>(&e.content)
-                        .map(|m| Persona::from_metadata(pubkey, m))
-                })
-                .ok();
+        .into_iter()
+        .collect::<HashMap<_, _>>();
 
-            if matches!(record.nip05_hours, Some(h) if h < 5) {
-                if let Some(ref mut p) = persona {
-                    // p.nip05_verified = true
-                }
-            };
+        let current = self.client().relays().await;
+        let mut outbox = self.0.outbox.lock().await;
 
-            persona
-        })
+        let (connected, max): (HashSet<Url>, usize) = if replace {
+            let own = current.keys().filter(|u| !outbox.contains(*u));
+            (own.cloned().collect(), MAX_OUTBOX_RELAYS)
+        } else {
+            let connected = current.keys().cloned().collect::<HashSet<_>>();
+            (connected, MAX_OUTBOX_RELAYS.saturating_sub(outbox.len()))
+        };
+        let outboxes = select_outboxes(&write_relays, &connected, OUTBOX_RELAYS, max);
+        let wanted = outboxes
+            .values()
+            .flatten()
+            .filter(|u| !connected.contains(*u))
+            .collect::<HashSet<_>>();
+
+        if replace {
+            for url in outbox.iter().filter(|u| !wanted.contains(u)) {
+                info!("Removing outbox relay {url}.");
+                let _ = self.client().remove_relay(url.as_str()).await;
+            }
+            outbox.retain(|u| wanted.contains(u));
+        }
+
+        for url in wanted.into_iter().filter(|u| !current.contains_key(*u)) {
+            info!("Adding outbox relay {url}.");
+            if self
+                .client()
+                .add_relay_with_opts(url.as_str(), None, RelayOptions::new(true, false))
+                .await
+                .is_ok()
+            {
+                outbox.insert(url.clone());
+            }
+        }
+
+        drop(outbox);
+        self.client().connect().await;
+
+        outboxes
+    }
+
+    /// Subscribes relays to what `subscription` selects. Authors are asked for
+    /// only at their write relays (NIP-65), or at user's relays if they have
+    /// none. Everything else is asked for at user's relays, searches only at
+    /// those that support it. If it is called again before relays are subscribed,
+    /// only the latest subscription is carried out.
+    pub async fn subscribe(&self, subscription: Subscription) {
+        let generation = self.0.subscriptions.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let mut outboxes = self
+            .connect_outboxes(
+                subscription
+                    .authors()
+                    .union(&subscription.pubkeys())
+                    .copied(),
+                true,
+            )
+            .await;
+
+        // Relays that could not be added do not count.
+        let relays = self.client().relays().await;
+        for urls in outboxes.values_mut() {
+            urls.retain(|u| relays.contains_key(u));
+        }
+
+        // Relays that do not support search would ignore it
+        // and send everything else the filter selects. Relays are asked
+        // only for as many recent notes as a lane can show.
+        let (search_filters, filters): (Vec<_>, Vec<_>) = subscription
+            .to_filters(self.config().cache.lane_notes)
+            .into_iter()
+            .partition(|f| f.search.is_some());
+        let search_relays = self.search_relays().await;
+
+        let _subscribing = self.0.subscribing.lock().await;
+        if self.0.subscriptions.load(Ordering::SeqCst) != generation {
+            debug!("Subscription has been superseded.");
+            return;
+        }
+
+        let outbox = self.0.outbox.lock().await.clone();
+
+        for (url, relay) in relays {
+            let mut relay_filters =
+                route_filters(&filters, &outboxes, &url, !outbox.contains(&url));
+            if search_relays.contains(&url) {
+                relay_filters.extend(search_filters.iter().cloned());
+            }
+
+            if relay_filters.is_empty() {
+                let _ = relay.unsubscribe(None).await;
+            } else {
+                let _ = relay.subscribe(relay_filters, None).await;
+            }
+
+            let active_subs = relay.subscriptions().await;
+            debug!("On {url} subscribed to {active_subs:#?}");
+        }
+    }
+
+    /// Attempts to obtain [`Persona`] from storage for a given `pubkey`.
+    pub async fn get_persona(&self, pubkey: XOnlyPublicKey) -> Option<Persona> {
+        let event = match self.store().get_metadata(pubkey).await {
+            Ok(event) => event?,
+            Err(e) => {
+                warn!("Could not load metadata of {pubkey}: {e}");
+                return None;
+            }
+        };
+
+        let mut persona = event
+            .as_metadata()
+            .map(|m| Persona::from_metadata(pubkey, m))?;
+        persona.relays = self.write_relays(pubkey).await;
+        Some(persona)
     }
 }
 
+#[derive(Debug)]
 pub enum LoadError {
     PaswordRequired,
     Config(config::ConfigError),
     ConfigFile(tokio::io::Error),
     IdentityFile(std::io::Error),
     Age(age::DecryptError),
+    /// Configuration was loaded but it does not make sense.
+    Invalid(String),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::PaswordRequired => write!(f, "Password is required."),
+            LoadError::Config(e) => write!(f, "Could not load configuration: {e}"),
+            LoadError::ConfigFile(e) => write!(f, "Could not access configuration file: {e}"),
+            LoadError::IdentityFile(e) => write!(f, "Could not access identity file: {e}"),
+            LoadError::Age(e) => write!(f, "Could not decrypt identity: {e}"),
+            LoadError::Invalid(e) => write!(f, "Invalid configuration: {e}"),
+        }
+    }
 }
 
 /// Creates a gnostique session. If `identity_file` does not exist, a new random
 /// identity will be created and saved to file encrypted using `password`.
 // TODO: this function should not be creating new identities, a ready identity should be passed.
 pub async fn make_gnostique(
+    config: Config,
     dirs: ProjectDirs,
     pool: SqlitePool,
     identity_file: PathBuf,
@@ -231,75 +909,277 @@ pub async fn make_gnostique(
 
     tracing::subscriber::set_global_default(subscriber).unwrap();
 
-    let identity = if tokio::fs::try_exists(&identity_file).await.unwrap() {
-        let ciph = tokio::fs::File::open(identity_file).await;
-        let mut buf = Vec::new();
-        ciph.unwrap()
-            .read_to_end(&mut buf)
-            .await
-            .map_err(|e| e.to_string())?;
+    let keyring_file = KeyringFile::new(identity_file, password);
 
-        if let Ok(Decryptor::Passphrase(d)) = Decryptor::new(buf.as_slice()) {
-            let rea = d.decrypt(&password, Some(18)).map_err(|e| e.to_string())?;
-            serde_json::from_reader(rea).map_err(|e| e.to_string())?
-        } else {
-            Err("Can't".to_string())?
+    let keyring = match keyring_file.load().await.map_err(|e| e.to_string())? {
+        Some(keyring) => keyring,
+        None => {
+            let keyring = Keyring::new(Identity::new_random("Default identity"));
+            keyring_file
+                .save(&keyring)
+                .await
+                .map_err(|e| e.to_string())?;
+            keyring
         }
-    } else {
-        let new_identity = Identity::new_random("Default identity");
+    };
 
-        // TODO: Save
+    let store = Arc::new(SqliteStore::new(pool));
+    let gnostique =
+        Gnostique::new(config, store, dirs, keyring, keyring_file).map_err(|e| e.to_string())?;
 
-        new_identity
-    };
+    gnostique.seed_relays().await;
+    gnostique.load_follow().await;
 
-    // Create Nostr client
-    let client = Client::new(&identity.nostr_key());
-
-    let gnostique = Gnostique::new(pool, dirs, client);
-
-    gnostique
-        .client()
-        .add_relays(vec![
-            // ("ws://localhost:8080", None),
-            // ("wss://eden.nostr.land", None),
-            // ("wss://nostr.fmt.wiz.biz", None),
-            // ("wss://relay.damus.io", None),
-            // ("wss://nostr-pub.wellorder.net", None),
-            ("wss://nos.lol", None),
-            // ("wss://relay.snort.social", None),
-            // ("wss://relay.current.fyi", None),
-        ])
-        .await
-        .unwrap();
+    Ok(gnostique)
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::*;
 
-    gnostique.client().connect().await;
+    use super::*;
+    use crate::nostr::ReceivedEvent;
+    use crate::store::memory::MemoryStore;
+
+    fn gnostique(keyring: Keyring) -> Gnostique {
+        configured(keyring, "db_file = 'test.db'")
+    }
+
+    fn configured(keyring: Keyring, config: &str) -> Gnostique {
+        let config = toml::from_str(config).unwrap();
+        let dirs = ProjectDirs::from("com.jirijakes", "", "Gnostique").unwrap();
+        // Identity file of each session is separate, so that tests do not touch
+        // the real one and do not share it.
+        let path = std::env::temp_dir().join(format!(
+            "gnostique-identity-{}",
+            Keys::generate().public_key()
+        ));
+        let file = KeyringFile::new(path, SecretString::new("test".to_string()));
+        Gnostique::new(
+            config,
+            Arc::new(MemoryStore::default()),
+            dirs,
+            keyring,
+            file,
+        )
+        .unwrap()
+    }
+
+    /// Keyring with a single random identity and keys of the identity.
+    fn keyring() -> (Keyring, Keys) {
+        let identity = Identity::new_random("Test identity");
+        let keys = identity.nostr_key().unwrap();
+        (Keyring::new(identity), keys)
+    }
 
-    for (_, r) in gnostique.client().relays().await {
-        r.subscribe(vec![Filter::new().since(Timestamp::now())], None)
+    #[tokio::test]
+    async fn disabled_relays_not_seeded_again() {
+        let gnostique = configured(
+            keyring().0,
+            "db_file = 'test.db'\n[[relays]]\nurl = 'wss://relay.example.com'",
+        );
+
+        gnostique.seed_relays().await;
+        let mut policy = gnostique.store().relay_policies().await.unwrap()[0].clone();
+        assert!(policy.enabled);
+
+        policy.enabled = false;
+        gnostique.store().set_relay_policy(&policy).await.unwrap();
+        gnostique.seed_relays().await;
+        assert_eq!(
+            gnostique.store().relay_policies().await.unwrap(),
+            vec![policy]
+        );
+    }
+
+    #[tokio::test]
+    async fn newer_contact_list_replaces_current() {
+        let (keyring, keys) = keyring();
+        let gnostique = gnostique(keyring);
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+
+        let newer = Follow::new()
+            .follow(alice)
+            .to_event_builder()
+            .to_event(&keys)
+            .unwrap();
+        let mut older = Follow::new()
+            .follow(bob)
+            .to_event_builder()
+            .to_event(&keys)
+            .unwrap();
+        older.created_at = Timestamp::from(newer.created_at.as_u64() - 60);
+
+        assert!(gnostique.contact_list_received(&newer).await.is_some());
+        assert!(gnostique.contact_list_received(&older).await.is_none());
+        assert!(gnostique.follow().follows(&alice));
+        assert!(!gnostique.follow().follows(&bob));
+
+        // Contact lists of others do not change whom we follow.
+        let other = Follow::new()
+            .follow(bob)
+            .to_event_builder()
+            .to_event(&Keys::generate())
+            .unwrap();
+        assert!(gnostique.contact_list_received(&other).await.is_none());
+        assert!(!gnostique.follow().follows(&bob));
+    }
+
+    #[tokio::test]
+    async fn unloaded_contact_list_not_published() {
+        let (keyring, keys) = keyring();
+        let gnostique = gnostique(keyring);
+        let alice = Keys::generate().public_key();
+
+        assert!(matches!(
+            gnostique.set_following(alice, true).await,
+            Err(PublishError::NoContactList)
+        ));
+        assert!(!gnostique.follow().follows(&alice));
+        assert!(gnostique
+            .store()
+            .get_replaceable(keys.public_key(), Kind::ContactList)
             .await
-            .expect("Did not subscribe successfully.");
+            .unwrap()
+            .is_none());
+
+        // Relays have answered that there is no contact list, so the first one
+        // can be published.
+        *gnostique.0.follow_looked_up.write().unwrap() = Some(keys.public_key());
+        assert!(!matches!(
+            gnostique.set_following(alice, true).await,
+            Err(PublishError::NoContactList)
+        ));
     }
 
-    Ok(gnostique)
-}
+    #[tokio::test]
+    async fn write_relays_from_stored_relay_list() {
+        let gnostique = gnostique(keyring().0);
+        let author = Keys::generate();
+
+        let read = RelayPolicy {
+            write: false,
+            ..RelayPolicy::new(Url::parse("wss://read.example.com").unwrap())
+        };
+        let write = RelayPolicy::new(Url::parse("wss://write.example.com").unwrap());
+
+        let event = EventBuilder::new(Kind::RelayList, "", &relay_list_tags(&[read, write]))
+            .to_event(&author)
+            .unwrap();
+        gnostique.store().store_replaceable(&event).await.unwrap();
+
+        assert_eq!(
+            gnostique.write_relays(author.public_key()).await,
+            vec![Url::parse("wss://write.example.com").unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_keeps_own_followed_and_bookmarked() {
+        let (keyring, keys) = keyring();
+        let gnostique = configured(
+            keyring,
+            "db_file = 'test.db'\n[retention]\nevents_per_author = 1",
+        );
+        let friend = Keys::generate();
+        let stranger = Keys::generate();
+
+        let contacts = Follow::new()
+            .follow(friend.public_key())
+            .to_event_builder()
+            .to_event(&keys)
+            .unwrap();
+        gnostique.contact_list_received(&contacts).await;
 
-// async fn load_config(dirs: &ProjectDirs, filename: &str) -> Result<Config, LoadError> {
-//     let file = dirs.config_dir().join(filename);
+        let mut notes = vec![];
+        for author in [&keys, &friend, &stranger] {
+            for age in 0..3 {
+                let mut event = EventBuilder::new_text_note(format!("{age}"), &[])
+                    .to_event(author)
+                    .unwrap();
+                event.created_at = Timestamp::from(event.created_at.as_u64() - age * 60);
+                let received = ReceivedEvent {
+                    event: event.clone(),
+                    relay: Url::parse("wss://relay.example.com").unwrap(),
+                };
+                gnostique.store().store_event(&received).await.unwrap();
+                notes.push(event.id);
+            }
+        }
 
-//     let exists = tokio::fs::try_exists(&file)
-//         .await
-//         .map_err(LoadError::ConfigFile)?;
+        // The oldest note of stranger.
+        gnostique
+            .store()
+            .set_bookmark(notes[8], true)
+            .await
+            .unwrap();
 
-//     let x = toml::to_string_pretty(&Config {
-//         default_identity: None,
-//         db_file: dirs.data_dir().join("gnostique.db"),
-//     });
+        assert_eq!(gnostique.prune().await.unwrap(), 1);
+        for (i, id) in notes.into_iter().enumerate() {
+            let stored = gnostique.store().get_event(id).await.unwrap();
+            assert_eq!(stored.is_some(), i != 7);
+        }
+    }
 
-//     let xxx = config::Config::builder()
-//         .add_source(config::File::from(file.as_ref()))
-//         .build()
-//         .map_err(LoadError::Config)?;
+    #[tokio::test]
+    async fn switching_identity_loads_its_contact_list() {
+        let first = Identity::new_random("First");
+        let second = Identity::new_random("Second");
+        let second_keys = second.nostr_key().unwrap();
+        let keyring: Keyring = serde_json::from_value(
+            serde_json::json!({ "identities": [first, second], "default": 0 }),
+        )
+        .unwrap();
+        let gnostique = gnostique(keyring);
+        let friend = Keys::generate().public_key();
 
-//     xxx.try_deserialize().map_err(LoadError::Config)
-// }
+        let contacts = Follow::new()
+            .follow(friend)
+            .to_event_builder()
+            .to_event(&second_keys)
+            .unwrap();
+        gnostique
+            .store()
+            .store_replaceable(&contacts)
+            .await
+            .unwrap();
+        assert!(!gnostique.follow().follows(&friend));
+
+        let follow = gnostique.switch_identity(1).await.unwrap();
+        assert!(follow.follows(&friend));
+        assert_eq!(gnostique.current_identity(), 1);
+        assert_eq!(gnostique.pubkey(), second_keys.public_key());
+
+        assert!(gnostique.switch_identity(2).await.is_none());
+        assert_eq!(gnostique.current_identity(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_only_identity_follows_but_cannot_publish() {
+        let owner = Keys::generate();
+        let keyring = Keyring::new(Identity::read_only("Watched", &owner.public_key()));
+        let gnostique = gnostique(keyring);
+        let friend = Keys::generate().public_key();
+
+        let contacts = Follow::new()
+            .follow(friend)
+            .to_event_builder()
+            .to_event(&owner)
+            .unwrap();
+        gnostique
+            .store()
+            .store_replaceable(&contacts)
+            .await
+            .unwrap();
+        gnostique.load_follow().await;
+
+        assert!(gnostique.read_only());
+        assert_eq!(gnostique.pubkey(), owner.public_key());
+        assert!(gnostique.follow().follows(&friend));
+        assert!(gnostique
+            .publish(EventBuilder::new_text_note("Hello", &[]))
+            .await
+            .is_err());
+    }
+}
@@ -1,12 +1,30 @@
-use nostr_sdk::prelude::{FromMnemonic, Keys};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use age::{Decryptor, Encryptor};
+use nostr_sdk::nostr::nips::nip19;
+use nostr_sdk::prelude::{FromBech32, FromMnemonic, Keys, SecretKey, ToBech32, XOnlyPublicKey};
 use nostr_sdk::secp256k1::rand::rngs::OsRng;
 use nostr_sdk::secp256k1::rand::Rng;
 use secrecy::*;
 use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::nostr::nip46::{BunkerUri, Nip46Error};
+use crate::nostr::nip49::{self, Nip49Error};
+use crate::nostr::query::parse_pubkey;
 
+/// Secret key in hex.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Key(String);
 
+impl Key {
+    pub fn reveal(&self) -> &str {
+        &self.0
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Mnemonic(String);
 
@@ -20,44 +38,482 @@ impl Mnemonic {
 /// all the secrets and identity-specific settings.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Identity {
-    /// Mnemonic used to create secret key.
-    mnemonic: Secret<Mnemonic>,
+    /// Mnemonic used to create secret key, if the identity was created from one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mnemonic: Option<Secret<Mnemonic>>,
+
+    /// Secret key, if the identity was imported without mnemonic.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<Secret<Key>>,
+
+    /// Public key in hex of an identity that keeps no secret key,
+    /// because it is read-only or signs by a remote signer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pubkey: Option<String>,
+
+    /// Remote signer that keeps secret key of the identity (NIP-46).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bunker: Option<Bunker>,
 
     /// Name of the identity.
     name: String,
 }
 
+/// Remote signer of an identity (NIP-46).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bunker {
+    /// URI of the signer, `bunker://…`.
+    uri: String,
+
+    /// Secret key of this client, which signs requests to the signer.
+    client_key: Secret<Key>,
+}
+
+/// Format of exported secret key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// Bech32 with `nsec` prefix (NIP-19).
+    Nsec,
+    Hex,
+    /// Encrypted by password, with `ncryptsec` prefix (NIP-49).
+    Ncryptsec,
+}
+
+/// Work factor of scrypt when encrypting exported keys (NIP-49),
+/// recommended by the NIP.
+const NCRYPTSEC_LOG_N: u8 = 16;
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// Text is neither nsec, hex nor ncryptsec secret key.
+    Unknown,
+    Nip19(nip19::Error),
+    Key(nostr_sdk::secp256k1::Error),
+    Nip49(Nip49Error),
+    /// Public key of read-only identity is not valid.
+    PublicKey(String),
+    Nip46(Nip46Error),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Unknown => write!(f, "Expected secret key as nsec, hex or ncryptsec."),
+            ImportError::Nip19(e) => write!(f, "Invalid secret key: {e}"),
+            ImportError::Key(e) => write!(f, "Invalid secret key: {e}"),
+            ImportError::Nip49(e) => write!(f, "{e}"),
+            ImportError::PublicKey(e) => write!(f, "{e}"),
+            ImportError::Nip46(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    /// Identity is read-only or its secret key is kept by remote signer.
+    NoSecret,
+    Nip49(Nip49Error),
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::NoSecret => write!(f, "Identity does not keep its secret key."),
+            ExportError::Nip49(e) => write!(f, "{e}"),
+        }
+    }
+}
+
 impl Identity {
     pub fn new_random(name: &str) -> Identity {
         Identity {
             name: name.to_string(),
-            mnemonic: Secret::new(Mnemonic(
+            mnemonic: Some(Secret::new(Mnemonic(
                 bip39::Mnemonic::from_entropy(&OsRng.gen::<[_; 32]>())
                     .unwrap()
                     .to_string(),
-            )),
+            ))),
+            key: None,
+            pubkey: None,
+            bunker: None,
         }
     }
 
     pub fn from_bip39_mnemonic(name: &str, mnemonic: &bip39::Mnemonic) -> Identity {
         Identity {
-            mnemonic: Secret::new(Mnemonic(mnemonic.to_string())),
+            mnemonic: Some(Secret::new(Mnemonic(mnemonic.to_string()))),
+            key: None,
+            pubkey: None,
+            bunker: None,
+            name: name.to_string(),
+        }
+    }
+
+    pub fn from_secret_key(name: &str, secret: &SecretKey) -> Identity {
+        Identity {
+            mnemonic: None,
+            key: Some(Secret::new(Key(secret.display_secret().to_string()))),
+            pubkey: None,
+            bunker: None,
             name: name.to_string(),
         }
     }
 
+    /// Creates identity that can only read, not publish, as `pubkey`.
+    pub fn read_only(name: &str, pubkey: &XOnlyPublicKey) -> Identity {
+        Identity {
+            mnemonic: None,
+            key: None,
+            pubkey: Some(pubkey.to_string()),
+            bunker: None,
+            name: name.to_string(),
+        }
+    }
+
+    /// Imports identity whose events are signed by remote signer at `uri`,
+    /// `bunker://…` (NIP-46). The identity has public key of the signer.
+    pub fn import_bunker(name: &str, uri: &str) -> Result<Identity, ImportError> {
+        let parsed = BunkerUri::from_str(uri).map_err(ImportError::Nip46)?;
+        let client_key = Keys::generate().secret_key().unwrap();
+
+        Ok(Identity {
+            mnemonic: None,
+            key: None,
+            pubkey: Some(parsed.signer.to_string()),
+            bunker: Some(Bunker {
+                uri: parsed.to_string(),
+                client_key: Secret::new(Key(client_key.display_secret().to_string())),
+            }),
+            name: name.to_string(),
+        })
+    }
+
+    /// Imports read-only identity from public key given as npub, nprofile or hex.
+    pub fn import_read_only(name: &str, pubkey: &str) -> Result<Identity, ImportError> {
+        let (pubkey, _) = parse_pubkey(pubkey.trim()).map_err(ImportError::PublicKey)?;
+        Ok(Identity::read_only(name, &pubkey))
+    }
+
+    /// Imports identity from secret key given as nsec, hex or ncryptsec. Only
+    /// ncryptsec needs `password`.
+    pub fn import(name: &str, secret: &str, password: &str) -> Result<Identity, ImportError> {
+        let secret = secret.trim();
+
+        let key = if secret.starts_with("ncryptsec1") {
+            nip49::decrypt(secret, password).map_err(ImportError::Nip49)?
+        } else if secret.starts_with("nsec1") {
+            SecretKey::from_bech32(secret).map_err(ImportError::Nip19)?
+        } else if secret.len() == 64 && secret.chars().all(|c| c.is_ascii_hexdigit()) {
+            SecretKey::from_str(secret).map_err(ImportError::Key)?
+        } else {
+            return Err(ImportError::Unknown);
+        };
+
+        Ok(Identity::from_secret_key(name, &key))
+    }
+
+    /// Secret key in `format`. Format ncryptsec is encrypted by `password`.
+    pub fn export(&self, format: KeyFormat, password: &str) -> Result<String, ExportError> {
+        let secret = self.secret_key().ok_or(ExportError::NoSecret)?;
+
+        Ok(match format {
+            KeyFormat::Nsec => secret.to_bech32().unwrap(),
+            KeyFormat::Hex => secret.display_secret().to_string(),
+            KeyFormat::Ncryptsec => {
+                nip49::encrypt(&secret, password, NCRYPTSEC_LOG_N).map_err(ExportError::Nip49)?
+            }
+        })
+    }
+
+    /// The same identity with another name.
+    pub fn renamed(&self, name: &str) -> Identity {
+        Identity {
+            name: name.to_string(),
+            ..self.clone()
+        }
+    }
+
     /// Name of the identity.
     pub fn name(&self) -> &str {
         &self.name
     }
 
-    /// Mnemonic of the identity.
-    pub fn mnemonic(&self) -> &Secret<Mnemonic> {
-        &self.mnemonic
+    /// Mnemonic of the identity, if it was created from one.
+    pub fn mnemonic(&self) -> Option<&Secret<Mnemonic>> {
+        self.mnemonic.as_ref()
+    }
+
+    /// Identity has only public key and cannot sign events.
+    pub fn is_read_only(&self) -> bool {
+        self.mnemonic.is_none() && self.key.is_none() && self.bunker.is_none()
+    }
+
+    /// Identity keeps its secret key, so it can be exported.
+    pub fn keeps_secret(&self) -> bool {
+        self.mnemonic.is_some() || self.key.is_some()
+    }
+
+    /// URI of remote signer and keys this client signs requests to it with,
+    /// if the identity signs by one.
+    pub fn bunker(&self) -> Option<(BunkerUri, Keys)> {
+        let bunker = self.bunker.as_ref()?;
+        let uri = BunkerUri::from_str(&bunker.uri).ok()?;
+        let keys = Keys::from_sk_str(bunker.client_key.expose_secret().reveal()).ok()?;
+        Some((uri, keys))
+    }
+
+    /// Keys of the identity. Keys of a read-only identity and of one
+    /// with remote signer have no secret key. Returns `None` if the identity
+    /// has no valid key, which can happen only to one read from a damaged file.
+    pub fn nostr_key(&self) -> Option<Keys> {
+        match (&self.mnemonic, &self.key, &self.pubkey) {
+            (Some(mnemonic), _, _) => {
+                Keys::from_mnemonic(mnemonic.expose_secret().reveal(), None).ok()
+            }
+            (None, Some(key), _) => Keys::from_sk_str(key.expose_secret().reveal()).ok(),
+            (None, None, Some(pubkey)) => XOnlyPublicKey::from_str(pubkey)
+                .ok()
+                .map(Keys::from_public_key),
+            (None, None, None) => None,
+        }
+    }
+
+    /// Secret key of the identity, unless it is read-only.
+    pub fn secret_key(&self) -> Option<SecretKey> {
+        self.nostr_key()?.secret_key().ok()
+    }
+}
+
+/// All identities of the user, one of which is used by default.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Keyring {
+    identities: Vec<Identity>,
+
+    /// Index of the identity used when Gnostique starts.
+    default: usize,
+}
+
+#[derive(Debug)]
+pub enum KeyringError {
+    Io(std::io::Error),
+    Encrypt(age::EncryptError),
+    Decrypt(age::DecryptError),
+    /// Identity file is not encrypted by password.
+    NotPassphrase,
+    /// Decrypted identity file could not be read.
+    Invalid(serde_json::Error),
+    /// Keyring has to contain at least one identity.
+    Empty,
+    /// Identity in use cannot be removed from keyring.
+    CurrentRemoved,
+    /// Given password is not the one keyring is encrypted with.
+    WrongPassword,
+    /// Identity of the name has no valid key.
+    NoKey(String),
+}
+
+impl std::fmt::Display for KeyringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyringError::Io(e) => write!(f, "Could not access identity file: {e}"),
+            KeyringError::Encrypt(e) => write!(f, "Could not encrypt identities: {e}"),
+            KeyringError::Decrypt(e) => write!(f, "Could not decrypt identities: {e}"),
+            KeyringError::NotPassphrase => write!(f, "Identity file is not protected by password."),
+            KeyringError::Invalid(e) => write!(f, "Invalid identity file: {e}"),
+            KeyringError::Empty => write!(f, "At least one identity is required."),
+            KeyringError::CurrentRemoved => write!(f, "Identity in use cannot be removed."),
+            KeyringError::WrongPassword => write!(f, "Password is not correct."),
+            KeyringError::NoKey(name) => write!(f, "Identity '{name}' has no valid key."),
+        }
+    }
+}
+
+impl From<std::io::Error> for KeyringError {
+    fn from(e: std::io::Error) -> Self {
+        KeyringError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for KeyringError {
+    fn from(e: serde_json::Error) -> Self {
+        KeyringError::Invalid(e)
+    }
+}
+
+/// Content of decrypted identity file, either a keyring or a single
+/// identity as it was stored before there were keyrings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredIdentities {
+    Keyring(Keyring),
+    Identity(Identity),
+}
+
+impl Keyring {
+    /// Creates keyring with single identity, which is the default one.
+    pub fn new(identity: Identity) -> Keyring {
+        Keyring {
+            identities: vec![identity],
+            default: 0,
+        }
+    }
+
+    /// Reads keyring from decrypted content of identity file. Identities
+    /// without valid key are refused.
+    pub fn from_reader(reader: impl Read) -> Result<Keyring, serde_json::Error> {
+        let keyring = match serde_json::from_reader(reader)? {
+            StoredIdentities::Keyring(keyring) if keyring.identities.is_empty() => Err(
+                serde::de::Error::custom("keyring does not contain any identity"),
+            ),
+            StoredIdentities::Keyring(keyring) => Ok(keyring),
+            StoredIdentities::Identity(identity) => Ok(Keyring::new(identity)),
+        }?;
+
+        match keyring.identities.iter().find(|i| i.nostr_key().is_none()) {
+            Some(invalid) => Err(serde::de::Error::custom(format!(
+                "identity '{}' has no valid key",
+                invalid.name
+            ))),
+            None => Ok(keyring),
+        }
+    }
+
+    /// Decrypts keyring from content of identity file, which was encrypted
+    /// by [`Keyring::encrypt`] or which holds a single identity.
+    pub fn decrypt(data: &[u8], password: &SecretString) -> Result<Keyring, KeyringError> {
+        match Decryptor::new(data).map_err(KeyringError::Decrypt)? {
+            Decryptor::Passphrase(d) => {
+                let reader = d.decrypt(password, None).map_err(KeyringError::Decrypt)?;
+                Ok(Keyring::from_reader(reader)?)
+            }
+            _ => Err(KeyringError::NotPassphrase),
+        }
     }
 
-    pub fn nostr_key(&self) -> Keys {
-        Keys::from_mnemonic(self.mnemonic.expose_secret().reveal(), None).unwrap()
+    /// Encrypts keyring by `password` (age with scrypt) to be written to identity file.
+    pub fn encrypt(&self, password: &SecretString) -> Result<Vec<u8>, KeyringError> {
+        let json = serde_json::to_vec(self)?;
+        let mut data = vec![];
+
+        let mut writer = Encryptor::with_user_passphrase(password.clone())
+            .wrap_output(&mut data)
+            .map_err(KeyringError::Encrypt)?;
+        writer.write_all(&json)?;
+        writer.finish()?;
+
+        Ok(data)
+    }
+
+    /// Replaces all identities by `identities`, of which identity with public key
+    /// `current` has to be one. The default identity stays the same unless it was
+    /// removed, then the current one becomes default. Returns new index of `current`.
+    pub fn replace(
+        &mut self,
+        identities: Vec<Identity>,
+        current: XOnlyPublicKey,
+    ) -> Result<usize, KeyringError> {
+        if identities.is_empty() {
+            return Err(KeyringError::Empty);
+        }
+
+        let pubkeys = identities
+            .iter()
+            .map(|i| {
+                i.nostr_key()
+                    .map(|k| k.public_key())
+                    .ok_or_else(|| KeyringError::NoKey(i.name.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let current = pubkeys
+            .iter()
+            .position(|pk| *pk == current)
+            .ok_or(KeyringError::CurrentRemoved)?;
+        let default = self.default_identity().nostr_key().map(|k| k.public_key());
+
+        self.default = pubkeys
+            .iter()
+            .position(|pk| Some(*pk) == default)
+            .unwrap_or(current);
+        self.identities = identities;
+
+        Ok(current)
+    }
+
+    /// All identities in the keyring.
+    pub fn identities(&self) -> &[Identity] {
+        &self.identities
+    }
+
+    /// Identity at `index`, if there is one.
+    pub fn get(&self, index: usize) -> Option<&Identity> {
+        self.identities.get(index)
+    }
+
+    /// Index of the default identity.
+    pub fn default_index(&self) -> usize {
+        self.default.min(self.identities.len().saturating_sub(1))
+    }
+
+    /// The identity used when Gnostique starts.
+    pub fn default_identity(&self) -> &Identity {
+        &self.identities[self.default_index()]
+    }
+
+    /// Makes identity at `index` the default one, unless there is none.
+    pub fn set_default(&mut self, index: usize) -> bool {
+        let exists = index < self.identities.len();
+        if exists {
+            self.default = index;
+        }
+        exists
+    }
+}
+
+/// Identity file, which holds keyring encrypted by password.
+#[derive(Clone, Debug)]
+pub struct KeyringFile {
+    path: PathBuf,
+    password: SecretString,
+}
+
+impl KeyringFile {
+    pub fn new(path: PathBuf, password: SecretString) -> KeyringFile {
+        KeyringFile { path, password }
+    }
+
+    /// Decrypts keyring from the file, unless the file does not exist.
+    pub async fn load(&self) -> Result<Option<Keyring>, KeyringError> {
+        if !tokio::fs::try_exists(&self.path).await? {
+            return Ok(None);
+        }
+
+        let data = tokio::fs::read(&self.path).await?;
+        Keyring::decrypt(&data, &self.password).map(Some)
+    }
+
+    /// Encrypts `keyring` into the file. The previous content is replaced
+    /// only once the new one is written completely.
+    pub async fn save(&self, keyring: &Keyring) -> Result<(), KeyringError> {
+        let data = keyring.encrypt(&self.password)?;
+        let tmp = self.path.with_extension("part");
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+
+    /// Whether the file is encrypted by `password`. Compared in constant time,
+    /// so that how long it takes does not tell how much of it is correct.
+    pub fn has_password(&self, password: &SecretString) -> bool {
+        let given = password.expose_secret().as_bytes();
+        given.ct_eq(self.password.expose_secret().as_bytes()).into()
+    }
+
+    /// The same file encrypted by another `password`.
+    pub fn with_password(&self, password: SecretString) -> KeyringFile {
+        KeyringFile {
+            path: self.path.clone(),
+            password,
+        }
     }
 }
 
@@ -70,3 +526,197 @@ impl Zeroize for Mnemonic {
 impl DebugSecret for Mnemonic {}
 impl CloneableSecret for Mnemonic {}
 impl SerializableSecret for Mnemonic {}
+
+impl Zeroize for Key {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl DebugSecret for Key {}
+impl CloneableSecret for Key {}
+impl SerializableSecret for Key {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyring_from_single_identity() {
+        let identity = Identity::new_random("Old identity");
+        let json = serde_json::to_string(&identity).unwrap();
+
+        let keyring = Keyring::from_reader(json.as_bytes()).unwrap();
+        assert_eq!(keyring.identities().len(), 1);
+        assert_eq!(keyring.default_identity().name(), "Old identity");
+        assert_eq!(
+            keyring.default_identity().nostr_key().unwrap().public_key(),
+            identity.nostr_key().unwrap().public_key()
+        );
+    }
+
+    #[test]
+    fn keyring_round_trip() {
+        let mut keyring = Keyring::new(Identity::new_random("First"));
+        keyring.identities.push(Identity::new_random("Second"));
+        assert!(keyring.set_default(1));
+        assert!(!keyring.set_default(2));
+
+        let json = serde_json::to_string(&keyring).unwrap();
+        let keyring = Keyring::from_reader(json.as_bytes()).unwrap();
+        assert_eq!(keyring.default_index(), 1);
+        assert_eq!(keyring.default_identity().name(), "Second");
+
+        let empty = r#"{"identities":[],"default":0}"#;
+        assert!(Keyring::from_reader(empty.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn identities_without_key_refused() {
+        let keyless = r#"{"identities":[{"name":"Damaged"}],"default":0}"#;
+        assert!(Keyring::from_reader(keyless.as_bytes()).is_err());
+
+        let invalid = r#"{"name":"Damaged","pubkey":"xyz"}"#;
+        assert!(Keyring::from_reader(invalid.as_bytes()).is_err());
+
+        let damaged: Identity = serde_json::from_str(invalid).unwrap();
+        assert!(damaged.nostr_key().is_none());
+        assert!(damaged.secret_key().is_none());
+
+        let mut keyring = Keyring::new(Identity::new_random("First"));
+        let current = keyring.default_identity().nostr_key().unwrap().public_key();
+        assert!(matches!(
+            keyring.replace(vec![damaged], current),
+            Err(KeyringError::NoKey(_))
+        ));
+    }
+
+    #[test]
+    fn import_exported_keys() {
+        let identity = Identity::new_random("Original");
+        let pubkey = identity.nostr_key().unwrap().public_key();
+
+        for format in [KeyFormat::Nsec, KeyFormat::Hex] {
+            let secret = identity.export(format, "").unwrap();
+            let imported = Identity::import("Imported", &format!(" {secret}\n"), "").unwrap();
+            assert_eq!(imported.nostr_key().unwrap().public_key(), pubkey);
+            assert!(imported.mnemonic().is_none());
+
+            // Identity with key only survives saving.
+            let json = serde_json::to_string(&imported).unwrap();
+            let restored: Identity = serde_json::from_str(&json).unwrap();
+            assert_eq!(restored.nostr_key().unwrap().public_key(), pubkey);
+        }
+
+        assert!(matches!(
+            Identity::import("Wrong", "npub1xyz", ""),
+            Err(ImportError::Unknown)
+        ));
+        assert!(matches!(
+            Identity::import("Wrong", "nsec1xyz", ""),
+            Err(ImportError::Nip19(_))
+        ));
+    }
+
+    #[test]
+    fn read_only_identity() {
+        let pubkey = Identity::new_random("Watched")
+            .nostr_key()
+            .unwrap()
+            .public_key();
+        let npub = pubkey.to_bech32().unwrap();
+
+        let identity = Identity::import_read_only("Watched", &npub).unwrap();
+        assert!(identity.is_read_only());
+        assert_eq!(identity.nostr_key().unwrap().public_key(), pubkey);
+        assert!(identity.secret_key().is_none());
+        assert!(matches!(
+            identity.export(KeyFormat::Nsec, ""),
+            Err(ExportError::NoSecret)
+        ));
+
+        let json = serde_json::to_string(&identity).unwrap();
+        let restored: Identity = serde_json::from_str(&json).unwrap();
+        assert!(restored.is_read_only());
+        assert_eq!(restored.nostr_key().unwrap().public_key(), pubkey);
+
+        let hex = Identity::import_read_only("Hex", &pubkey.to_string()).unwrap();
+        assert_eq!(hex.nostr_key().unwrap().public_key(), pubkey);
+        assert!(Identity::import_read_only("Wrong", "npub1xyz").is_err());
+    }
+
+    #[test]
+    fn bunker_identity() {
+        let signer = Keys::generate().public_key();
+        let uri = format!("bunker://{signer}?relay=wss://relay.example.com&secret=abc");
+
+        let identity = Identity::import_bunker("Remote", &uri).unwrap();
+        assert!(!identity.is_read_only());
+        assert!(identity.secret_key().is_none());
+        assert_eq!(identity.nostr_key().unwrap().public_key(), signer);
+
+        let json = serde_json::to_string(&identity).unwrap();
+        let restored: Identity = serde_json::from_str(&json).unwrap();
+        let (restored_uri, client_keys) = restored.bunker().unwrap();
+        let (original_uri, original_keys) = identity.bunker().unwrap();
+        assert_eq!(restored_uri, original_uri);
+        assert_eq!(restored_uri.secret.as_deref(), Some("abc"));
+        assert_eq!(client_keys.public_key(), original_keys.public_key());
+        assert_ne!(client_keys.public_key(), signer);
+
+        assert!(matches!(
+            Identity::import_bunker("Wrong", "https://example.com"),
+            Err(ImportError::Nip46(_))
+        ));
+    }
+
+    #[test]
+    fn keyring_encrypted_by_password() {
+        let password = SecretString::new("correct horse".to_string());
+        let keyring = Keyring::new(Identity::new_random("Encrypted"));
+
+        let data = keyring.encrypt(&password).unwrap();
+        let decrypted = Keyring::decrypt(&data, &password).unwrap();
+        assert_eq!(decrypted.default_identity().name(), "Encrypted");
+
+        let wrong = SecretString::new("battery staple".to_string());
+        assert!(matches!(
+            Keyring::decrypt(&data, &wrong),
+            Err(KeyringError::Decrypt(_))
+        ));
+
+        let file = KeyringFile::new(PathBuf::from("identity"), password.clone());
+        assert!(file.has_password(&password));
+        assert!(!file.has_password(&wrong));
+        assert!(!file.has_password(&SecretString::new("correct".to_string())));
+    }
+
+    #[test]
+    fn replacing_keeps_current_and_default() {
+        let first = Identity::new_random("First");
+        let second = Identity::new_random("Second");
+        let third = Identity::new_random("Third");
+        let current = second.nostr_key().unwrap().public_key();
+
+        let mut keyring = Keyring::new(first.clone());
+        assert!(matches!(
+            keyring.replace(vec![], current),
+            Err(KeyringError::Empty)
+        ));
+        assert!(matches!(
+            keyring.replace(vec![first.clone()], current),
+            Err(KeyringError::CurrentRemoved)
+        ));
+
+        let index = keyring
+            .replace(vec![third.clone(), first, second.clone()], current)
+            .unwrap();
+        assert_eq!(index, 2);
+        assert_eq!(keyring.default_identity().name(), "First");
+
+        // Default identity was removed, current one takes its place.
+        let index = keyring.replace(vec![second, third], current).unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(keyring.default_identity().name(), "Second");
+    }
+}
@@ -30,13 +30,21 @@ pub async fn deal_with_feedback(gnostique: Gnostique, rx: mpsc::Receiver<Feedbac
         .for_each(|f| async {
             match f {
                 Feedback::NeedMetadata { relay, pubkey } => {
-                    gnostique.demand().metadata(pubkey, vec![relay]).await;
+                    let mut relays = gnostique.connect_outbox(pubkey).await;
+                    relays.push(relay);
+                    gnostique.demand().metadata(pubkey, relays).await;
                 }
                 Feedback::NeedNote { event_id, relay } => {
                     gnostique.demand().text_note(event_id, relay).await;
                 }
                 Feedback::MakePreview { url } => {
-                    gnostique.demand().link_preview(&url).await;
+                    let preview = gnostique.demand().link_preview(&url).await;
+                    // Failed previews are not kept so that they can be tried again later.
+                    if preview.error().is_none() {
+                        if let Err(e) = gnostique.store().store_preview(&preview).await {
+                            tracing::warn!("Could not store preview of {url}: {e}");
+                        }
+                    }
                 }
             }
         })
@@ -2,22 +2,26 @@ mod feedback;
 
 use std::collections::HashSet;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use futures_util::*;
 use nostr_sdk::nostr::nips::nip05;
 use nostr_sdk::prelude::*;
 use nostr_sdk::RelayPoolNotification;
-use sqlx::query;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::BroadcastStream;
-use tracing::info;
+use tracing::{info, warn};
 
 use self::feedback::{deal_with_feedback, Feedback};
+use crate::follow::Follow;
 use crate::gnostique::Gnostique;
 use crate::nostr::content::{DynamicContent, Reference};
 use crate::nostr::gnevent::GnEvent;
+use crate::nostr::nip46::SignerStatus;
 use crate::nostr::preview::Preview;
+use crate::nostr::reaction::{deleted_ids, Reaction};
 use crate::nostr::{EventExt, Persona, ReceivedEvent, Repost, TextNote};
+use crate::relays::is_relay;
 
 // Note: Clone is required by broadcast::channel.
 #[derive(Clone, Debug)]
@@ -33,13 +37,28 @@ pub enum Incoming {
     },
     Reaction {
         event_id: EventId,
-        content: String,
+        reaction: Reaction,
+        /// Downloaded image of custom emoji.
+        emoji: Option<PathBuf>,
+    },
+    /// Author asked to delete their events.
+    Deleted {
+        author: XOnlyPublicKey,
+        ids: Vec<EventId>,
     },
     Metadata {
         persona: Persona,
         avatar: Option<PathBuf>,
     },
     Preview(Preview),
+    /// Set of relays used by client has changed.
+    RelaysChanged,
+    /// Contact list of the current identity has changed.
+    Follow(Follow),
+    /// Identities were created, edited or removed.
+    IdentitiesChanged,
+    /// Something happened with request to remote signer (NIP-46).
+    Signer(SignerStatus),
 }
 
 /// Stream of incoming messages. These are not only Nostr messages but any that can
@@ -88,27 +107,43 @@ async fn received_event(
     feedback: mpsc::Sender<Feedback>,
     event: ReceivedEvent,
 ) -> Option<Incoming> {
+    // Everything is kept so that reactions, reposts and threads can be
+    // put together again from the database.
+    if let Err(e) = gnostique.store().store_event(&event).await {
+        warn!("Could not store event {}: {e}", event.event.id);
+    }
+
     match event.event.kind {
         Kind::TextNote => Some(received_text_note(gnostique, feedback, event, None).await),
         Kind::Metadata => Some(received_metadata(gnostique, event.event).await),
-        Kind::Reaction => event.event.reacts_to().map(|to| Incoming::Reaction {
-            event_id: to,
-            content: event.event.content,
-        }),
+        Kind::RelayList => {
+            if let Err(e) = gnostique.store().store_replaceable(&event.event).await {
+                warn!("Could not store relay list: {e}");
+            }
+            None
+        }
+        Kind::ContactList => gnostique
+            .contact_list_received(&event.event)
+            .await
+            .map(Incoming::Follow),
+        Kind::Reaction => received_reaction(gnostique, &event.event).await,
+        Kind::EventDeletion => received_deletion(&event.event),
         Kind::Repost => {
-            if let Ok(inner) = Event::from_json(&event.event.content) {
-                Some(
-                    received_text_note(
-                        gnostique,
-                        feedback,
-                        ReceivedEvent {
-                            relay: event.relay,
-                            event: inner,
-                        },
-                        Some(event.event),
-                    )
-                    .await,
-                )
+            // Reposted event is stored too, so it has to be genuine.
+            let inner = Event::from_json(&event.event.content)
+                .ok()
+                .filter(|e| e.verify().is_ok());
+
+            if let Some(inner) = inner {
+                let inner = ReceivedEvent {
+                    relay: event.relay,
+                    event: inner,
+                };
+                if let Err(e) = gnostique.store().store_event(&inner).await {
+                    warn!("Could not store reposted event {}: {e}", inner.event.id);
+                }
+
+                Some(received_text_note(gnostique, feedback, inner, Some(event.event)).await)
             } else {
                 None
             }
@@ -117,21 +152,49 @@ async fn received_event(
     }
 }
 
-async fn received_metadata(gnostique: &Gnostique, event: Event) -> Incoming {
-    let pubkey_vec = event.pubkey.serialize().to_vec();
-    let json = event.as_json();
+async fn received_reaction(gnostique: &Gnostique, event: &Event) -> Option<Incoming> {
+    let (event_id, reaction) = Reaction::from_event(event)?;
 
-    let _ = query!(
-        r#"
-INSERT INTO metadata (author, event) VALUES (?, ?)
-ON CONFLICT (author) DO UPDATE SET event = EXCLUDED.event
-"#,
-        pubkey_vec,
-        json
-    )
-    .execute(gnostique.pool())
-    .await;
+    let emoji = match &reaction.emoji {
+        Some(emoji) => gnostique.download().to_cached_file(&emoji.url).await.file(),
+        None => None,
+    };
+
+    Some(Incoming::Reaction {
+        event_id,
+        reaction,
+        emoji,
+    })
+}
+
+fn received_deletion(event: &Event) -> Option<Incoming> {
+    let ids = deleted_ids(event);
+    (!ids.is_empty()).then_some(Incoming::Deleted {
+        author: event.pubkey,
+        ids,
+    })
+}
+
+/// Turns own `event`, which has just been published and stored, into incoming
+/// messages, so that it shows up without waiting for relays to send it back.
+pub async fn published_event(gnostique: &Gnostique, event: Event) -> Vec<Incoming> {
+    match event.kind {
+        Kind::TextNote => stored_text_notes(gnostique, vec![event]).await,
+        Kind::Reaction => received_reaction(gnostique, &event)
+            .await
+            .into_iter()
+            .collect(),
+        Kind::EventDeletion => received_deletion(&event).into_iter().collect(),
+        _ => vec![],
+    }
+}
+
+async fn received_metadata(gnostique: &Gnostique, event: Event) -> Incoming {
+    if let Err(e) = gnostique.store().store_metadata(&event).await {
+        warn!("Could not store metadata of {}: {e}", event.pubkey);
+    }
 
+    let json = event.as_json();
     let metadata = event.as_metadata().unwrap();
 
     let avatar_url = metadata
@@ -156,8 +219,11 @@ ON CONFLICT (author) DO UPDATE SET event = EXCLUDED.event
         false
     };
 
+    let relays = gnostique.write_relays(event.pubkey).await;
+
     let p = Persona {
         pubkey: event.pubkey,
+        relays,
         name: metadata.name,
         display_name: metadata.display_name,
         avatar: avatar_url,
@@ -233,14 +299,37 @@ async fn get_note_or_demand(
     note
 }
 
+/// Prepares text notes loaded from the database the same way as if
+/// they have just been received from relays.
+pub async fn stored_text_notes(gnostique: &Gnostique, events: Vec<Event>) -> Vec<Incoming> {
+    let (feedback, rx) = mpsc::channel(10);
+    tokio::spawn(deal_with_feedback(gnostique.clone(), rx));
+
+    let mut notes = Vec::with_capacity(events.len());
+    for event in events {
+        // Relay is needed to know where to ask for missing profiles.
+        let relay = gnostique
+            .store()
+            .event_relays(event.id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .next();
+        if let Some(relay) = relay {
+            let event = ReceivedEvent { relay, event };
+            notes.push(received_text_note(gnostique, feedback.clone(), event, None).await);
+        }
+    }
+
+    notes
+}
+
 async fn received_text_note(
     gnostique: &Gnostique,
     feedback: mpsc::Sender<Feedback>,
     event: ReceivedEvent,
     repost: Option<Event>,
 ) -> Incoming {
-    gnostique.store_event(&event).await;
-
     // if let Some((root, root_relay)) = event.thread_root() {
     //     feedback
     //         .send(Feedback::NeedNote {
@@ -307,7 +396,15 @@ async fn received_text_note(
             .and_then(|url| gnostique.download().cached(url))
     });
 
-    let relays = gnostique.textnote_relays(event.id).await;
+    // Relays are shown and given as hints where to find the note.
+    let relays = gnostique
+        .store()
+        .event_relays(event.id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(is_relay)
+        .collect();
 
     let note = TextNote::new(GnEvent::new(event, author));
 
@@ -332,56 +429,37 @@ async fn offer_relays(gnostique: &Gnostique, event: &ReceivedEvent) {
 
 async fn offer_relay_url(gnostique: &Gnostique, relay: &UncheckedUrl) {
     let url_s = relay.to_string();
-    let _ = query!(
-        "INSERT INTO relays(url) VALUES (?) ON CONFLICT(url) DO NOTHING",
-        url_s
-    )
-    .execute(gnostique.pool())
-    .await;
+    if let Err(e) = gnostique.store().offer_relay(&url_s).await {
+        warn!("Could not offer relay {url_s}: {e}");
+    }
 }
 
 async fn verify_nip05(gnostique: &Gnostique, pubkey: XOnlyPublicKey, nip05: &str) -> bool {
-    let pubkey_bytes = pubkey.serialize().to_vec();
     // If the nip05 is already verified and not for too long, just confirm.
-    let x = query!(
-        r#"
-SELECT (unixepoch('now') - unixepoch(nip05_verified)) / 60 / 60 AS "hours?: u32"
-FROM metadata WHERE author = ?"#,
-        pubkey_bytes
-    )
-    .fetch_optional(gnostique.pool())
-    .await;
-
-    if let Ok(result) = x {
-        let x = result.and_then(|r| r.hours);
-
-        match x {
-            Some(hours) if hours < 12 => {
-                info!("NIP05: {} verified {} hours ago", nip05, hours);
-                true
-            }
-            _ => {
-                info!("NIP05: Verifying {}.", nip05);
-                // If it's not yet verified or been verified for very long, update.
-                if nip05::verify(pubkey, nip05, None).await.is_ok() {
-                    let _ = query!(
-                        r#"
-UPDATE metadata SET nip05_verified = datetime('now')
-WHERE author = ?"#,
-                        pubkey_bytes
-                    )
-                    .execute(gnostique.pool())
-                    .await;
-
-                    info!("NIP05: {} verified.", nip05);
-                    true
-                } else {
-                    info!("NIP05: {} verification failed.", nip05);
-                    false
+    match gnostique.store().nip05_verified(pubkey).await {
+        Ok(Some(ago)) if ago < Duration::from_secs(12 * 60 * 60) => {
+            info!(
+                "NIP05: {} verified {} hours ago",
+                nip05,
+                ago.as_secs() / 60 / 60
+            );
+            true
+        }
+        Ok(_) => {
+            info!("NIP05: Verifying {}.", nip05);
+            // If it's not yet verified or been verified for very long, update.
+            if nip05::verify(pubkey, nip05, None).await.is_ok() {
+                if let Err(e) = gnostique.store().set_nip05_verified(pubkey).await {
+                    warn!("Could not store NIP05 verification of {nip05}: {e}");
                 }
+
+                info!("NIP05: {} verified.", nip05);
+                true
+            } else {
+                info!("NIP05: {} verification failed.", nip05);
+                false
             }
         }
-    } else {
-        false
+        Err(_) => false,
     }
 }
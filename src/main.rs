@@ -7,6 +7,8 @@ mod gnostique;
 mod identity;
 mod incoming;
 mod nostr;
+mod relays;
+mod store;
 mod ui;
 
 use relm4::*;
@@ -0,0 +1,127 @@
+//! Helpers of writing notes: mentions, tags derived from content and preview.
+
+use std::sync::Arc;
+
+use nostr_sdk::prelude::*;
+
+use super::parse::{parse_text, NIP21, TAG};
+use super::Persona;
+
+/// Tags that a note of `content` should have: `p` tags of mentioned profiles
+/// and `t` tags of hashtags.
+pub fn content_tags(content: &str) -> Vec<Tag> {
+    let mut tags = vec![];
+
+    for c in NIP21.captures_iter(content) {
+        let nip19 = c.name("nip19").map(|m| m.as_str()).unwrap_or_default();
+        let pubkey = match c.name("type").map(|m| m.as_str()) {
+            Some("npub") => XOnlyPublicKey::from_bech32(nip19).ok(),
+            Some("nprofile") => Profile::from_bech32(nip19).ok().map(|p| p.public_key),
+            _ => None,
+        };
+        if let Some(tag) = pubkey.map(|p| Tag::PubKey(p, None)) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+
+    for c in TAG.captures_iter(content) {
+        if let Some(m) = c.name("tag") {
+            let tag = Tag::Hashtag(m.as_str().trim_start_matches('#').to_lowercase());
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+
+    tags
+}
+
+/// If `before_cursor`, text written so far, ends by a started mention (`@…`),
+/// returns what has been written after `@`.
+pub fn mention_at(before_cursor: &str) -> Option<&str> {
+    let word = before_cursor
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or_default();
+
+    word.strip_prefix('@').filter(|w| !w.is_empty())
+}
+
+/// Reference to `pubkey` to be put into content of a note.
+pub fn mention(pubkey: XOnlyPublicKey) -> String {
+    format!(
+        "nostr:{}",
+        pubkey.to_bech32().expect("Public key is always encodable")
+    )
+}
+
+/// Markup of `content` as it would be shown in a note, with mentions
+/// of known `personas` shown by their names.
+pub fn preview(content: &str, personas: &[Arc<Persona>]) -> String {
+    let mut dcontent = parse_text(content, &[], None);
+    for persona in personas {
+        dcontent.provide(persona);
+    }
+
+    dcontent
+        .augment(&html_escape::encode_text(content))
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_of_content() {
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+        let nprofile = Profile::new(bob, Vec::<String>::new()).to_bech32().unwrap();
+
+        let content = format!(
+            "Hi {} and nostr:{nprofile}, see #Nostr and #rust. Again {} #nostr",
+            mention(alice),
+            mention(alice)
+        );
+
+        assert_eq!(
+            content_tags(&content),
+            vec![
+                Tag::PubKey(alice, None),
+                Tag::PubKey(bob, None),
+                Tag::Hashtag("nostr".to_string()),
+                Tag::Hashtag("rust".to_string()),
+            ]
+        );
+
+        // Anchors of links are not hashtags.
+        assert_eq!(content_tags("https://example.com/#top"), vec![]);
+    }
+
+    #[test]
+    fn started_mention() {
+        assert_eq!(mention_at("Hello @ali"), Some("ali"));
+        assert_eq!(mention_at("@a"), Some("a"));
+        assert_eq!(mention_at("Hello @"), None);
+        assert_eq!(mention_at("Hello @alice "), None);
+        assert_eq!(mention_at("mail@example"), None);
+        assert_eq!(mention_at(""), None);
+    }
+
+    #[test]
+    fn preview_with_names() {
+        let alice = Keys::generate().public_key();
+        let mut persona = Persona::new(alice);
+        persona.name = Some("alice".to_string());
+
+        let content = format!("Hi {} <3 #nostr", mention(alice));
+        let markup = preview(&content, &[Arc::new(persona)]);
+
+        assert!(markup.contains(">@alice</a>"));
+        assert!(markup.contains("&lt;3"));
+        assert!(markup.contains(r#"<a href="gnostique:search?tag=nostr">#nostr</a>"#));
+    }
+}
@@ -1,7 +1,12 @@
+pub mod compose;
 pub mod content;
 pub mod gnevent;
+pub mod nip46;
+pub mod nip49;
 mod parse;
 pub mod preview;
+pub mod query;
+pub mod reaction;
 pub mod subscriptions;
 
 pub use std::sync::Arc;
@@ -103,7 +108,6 @@ impl TextNote {
 
 #[derive(Clone, Debug)]
 pub struct Persona {
-    // TODO: Add relays?
     pub name: Option<String>,
     pub display_name: Option<String>,
     pub pubkey: XOnlyPublicKey,
@@ -113,6 +117,8 @@ pub struct Persona {
     pub nip05: Option<String>,
     pub nip05_preverified: bool,
     pub metadata_json: String,
+    /// Relays to which the persona publishes (NIP-65).
+    pub relays: Vec<Url>,
 }
 
 impl std::hash::Hash for Persona {
@@ -141,6 +147,7 @@ impl Persona {
             about: None,
             metadata_json: String::new(),
             nip05_preverified: false,
+            relays: vec![],
         }
     }
 
@@ -156,6 +163,7 @@ impl Persona {
             nip05: metadata.nip05,
             metadata_json,
             nip05_preverified: false,
+            relays: vec![],
         }
     }
 
@@ -257,6 +265,13 @@ impl EventExt for Event {
                     Tag::Event(id, _, Some(Marker::Reply)) => Some(*id),
                     _ => None,
                 })
+                // Direct reply to the root has only the root tag.
+                .or_else(|| {
+                    self.tags.iter().find_map(|t| match t {
+                        Tag::Event(id, _, Some(Marker::Root)) => Some(*id),
+                        _ => None,
+                    })
+                })
                 .or_else(|| {
                     // Positional tags
                     let only_events = self
@@ -295,8 +310,9 @@ impl EventExt for Event {
                         .filter(|t| matches!(t, Tag::Event(_, _, None)))
                         .collect::<Vec<_>>();
 
+                    // The first one is root, even if it is the only one.
                     match only_events.as_slice() {
-                        [_, .., Tag::Event(id, relay, _)] => {
+                        [Tag::Event(id, relay, _), ..] => {
                             Some((*id, relay.as_ref().and_then(|s| s.clone().try_into().ok())))
                         }
                         _ => None,
@@ -340,6 +356,96 @@ impl EventExt for Event {
     }
 }
 
+/// Tags of a reply to `parent` (NIP-10) written by `author`. Parent and root of
+/// the thread are referred to by marked `e` tags, with `relay` as the hint where
+/// the parent can be found. Everyone taking part in the thread is mentioned
+/// by a `p` tag, except the author.
+pub fn reply_tags(parent: &Event, relay: Option<&Url>, author: XOnlyPublicKey) -> Vec<Tag> {
+    let hint = |url: Option<&Url>| url.map(|u| UncheckedUrl::from(u.to_string()));
+    let mut tags = vec![];
+
+    match parent.thread_root() {
+        Some((root, root_relay)) if root != parent.id => {
+            tags.push(Tag::Event(
+                root,
+                hint(root_relay.as_ref()),
+                Some(Marker::Root),
+            ));
+            tags.push(Tag::Event(parent.id, hint(relay), Some(Marker::Reply)));
+        }
+        // Parent starts the thread.
+        _ => tags.push(Tag::Event(parent.id, hint(relay), Some(Marker::Root))),
+    }
+
+    let mut participants = vec![parent.pubkey];
+    for tag in &parent.tags {
+        if let Tag::PubKey(pubkey, _) = tag {
+            if !participants.contains(pubkey) {
+                participants.push(*pubkey);
+            }
+        }
+    }
+
+    tags.extend(
+        participants
+            .into_iter()
+            .filter(|p| *p != author)
+            .map(|p| Tag::PubKey(p, None)),
+    );
+
+    tags
+}
+
+/// Kind of repost of events other than text notes (NIP-18).
+const GENERIC_REPOST: u64 = 16;
+
+/// Builder of repost (NIP-18) of `event`, which can be found at `relay`. Text
+/// notes are reposted by kind 6, everything else by generic repost of kind 16.
+pub fn repost(event: &Event, relay: Option<&Url>) -> EventBuilder {
+    let mut tags = vec![
+        Tag::Event(
+            event.id,
+            relay.map(|u| UncheckedUrl::from(u.to_string())),
+            None,
+        ),
+        Tag::PubKey(event.pubkey, None),
+    ];
+
+    let kind = if event.kind == Kind::TextNote {
+        Kind::Repost
+    } else {
+        tags.push(Tag::Generic(
+            TagKind::Custom("k".to_string()),
+            vec![event.kind.as_u64().to_string()],
+        ));
+        Kind::from(GENERIC_REPOST)
+    };
+
+    EventBuilder::new(kind, event.as_json(), &tags)
+}
+
+/// Reference of `event` to be put into content of a note quoting it (`nostr:nevent1…`)
+/// and the `q` tag the note should have (NIP-18). The event can be found at `relays`.
+pub fn quote(event: &Event, relays: &[Url]) -> (String, Tag) {
+    let nevent = Nip19Event::new(
+        event.id,
+        relays.iter().map(Url::to_string).collect::<Vec<_>>(),
+    )
+    .to_bech32()
+    .expect("Event ID is always encodable");
+
+    let tag = Tag::Generic(
+        TagKind::Custom("q".to_string()),
+        vec![
+            event.id.to_hex(),
+            relays.first().map(Url::to_string).unwrap_or_default(),
+            event.pubkey.to_string(),
+        ],
+    );
+
+    (format!("nostr:{nevent}"), tag)
+}
+
 pub fn mnemonic() {
     let m = Mnemonic::from_entropy(&OsRng.gen::<[u8; 32]>()).unwrap();
     m.word_iter().for_each(|w| print!("{w} "));
@@ -348,3 +454,149 @@ pub fn mnemonic() {
     m.word_iter().for_each(|w| print!("{w} "));
     println!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relay(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn reply_to_root_and_to_reply() {
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+        let carol = Keys::generate();
+
+        let root = EventBuilder::new_text_note("Root", &[])
+            .to_event(&alice)
+            .unwrap();
+
+        // Bob replies to the root.
+        let tags = reply_tags(
+            &root,
+            Some(&relay("wss://alice.example.com")),
+            bob.public_key(),
+        );
+        assert_eq!(
+            tags,
+            vec![
+                Tag::Event(
+                    root.id,
+                    Some(UncheckedUrl::from("wss://alice.example.com/")),
+                    Some(Marker::Root)
+                ),
+                Tag::PubKey(alice.public_key(), None),
+            ]
+        );
+        let reply = EventBuilder::new_text_note("Reply", &tags)
+            .to_event(&bob)
+            .unwrap();
+        assert_eq!(reply.replies_to(), Some(root.id));
+        assert_eq!(reply.thread_root().map(|(id, _)| id), Some(root.id));
+
+        // Carol replies to Bob's reply.
+        let tags = reply_tags(
+            &reply,
+            Some(&relay("wss://bob.example.com")),
+            carol.public_key(),
+        );
+        assert_eq!(
+            tags,
+            vec![
+                Tag::Event(
+                    root.id,
+                    Some(UncheckedUrl::from("wss://alice.example.com/")),
+                    Some(Marker::Root)
+                ),
+                Tag::Event(
+                    reply.id,
+                    Some(UncheckedUrl::from("wss://bob.example.com/")),
+                    Some(Marker::Reply)
+                ),
+                Tag::PubKey(bob.public_key(), None),
+                Tag::PubKey(alice.public_key(), None),
+            ]
+        );
+        let nested = EventBuilder::new_text_note("Nested", &tags)
+            .to_event(&carol)
+            .unwrap();
+        assert_eq!(nested.replies_to(), Some(reply.id));
+        assert_eq!(nested.thread_root().map(|(id, _)| id), Some(root.id));
+
+        // Alice replying in her own thread does not mention herself.
+        let tags = reply_tags(&nested, None, alice.public_key());
+        assert!(!tags.contains(&Tag::PubKey(alice.public_key(), None)));
+        assert!(tags.contains(&Tag::PubKey(carol.public_key(), None)));
+        assert!(tags.contains(&Tag::PubKey(bob.public_key(), None)));
+    }
+
+    #[test]
+    fn reposts() {
+        let keys = Keys::generate();
+        let note = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        let relay = relay("wss://relay.example.com");
+
+        let repost = repost(&note, Some(&relay)).to_event(&keys).unwrap();
+        assert_eq!(repost.kind, Kind::Repost);
+        assert_eq!(Event::from_json(&repost.content).unwrap(), note);
+        assert!(repost.tags.contains(&Tag::PubKey(note.pubkey, None)));
+
+        let metadata = EventBuilder::set_metadata(Metadata::new().name("Alice"))
+            .to_event(&keys)
+            .unwrap();
+        let generic = super::repost(&metadata, None).to_event(&keys).unwrap();
+        assert_eq!(generic.kind, Kind::from(16));
+        assert_eq!(Event::from_json(&generic.content).unwrap(), metadata);
+        assert!(generic
+            .tags
+            .iter()
+            .any(|t| t.as_vec() == vec!["k".to_string(), "0".to_string()]));
+    }
+
+    #[test]
+    fn quotes() {
+        let keys = Keys::generate();
+        let note = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+
+        let (reference, tag) = quote(&note, &[relay("wss://relay.example.com")]);
+        let nevent = Nip19Event::from_bech32(reference.strip_prefix("nostr:").unwrap()).unwrap();
+        assert_eq!(nevent.event_id, note.id);
+        assert_eq!(nevent.relays, vec!["wss://relay.example.com/".to_string()]);
+
+        assert_eq!(
+            tag.as_vec(),
+            vec![
+                "q".to_string(),
+                note.id.to_hex(),
+                "wss://relay.example.com/".to_string(),
+                note.pubkey.to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn positional_thread_root() {
+        let keys = Keys::generate();
+        let root = EventId::all_zeros();
+        let parent = EventId::from_slice(&[1; 32]).unwrap();
+
+        let single = EventBuilder::new_text_note("", &[Tag::Event(root, None, None)])
+            .to_event(&keys)
+            .unwrap();
+        assert_eq!(single.thread_root().map(|(id, _)| id), Some(root));
+        assert_eq!(single.replies_to(), Some(root));
+
+        let tags = [Tag::Event(root, None, None), Tag::Event(parent, None, None)];
+        let nested = EventBuilder::new_text_note("", &tags)
+            .to_event(&keys)
+            .unwrap();
+        assert_eq!(nested.thread_root().map(|(id, _)| id), Some(root));
+        assert_eq!(nested.replies_to(), Some(parent));
+    }
+}
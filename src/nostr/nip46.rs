@@ -0,0 +1,641 @@
+//! Signing by a remote signer, also known as bunker (NIP-46). Requests and responses
+//! are encrypted messages (NIP-04) exchanged over relays, signed by local keys of
+//! the client, which are not keys of the identity.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use nostr_sdk::nostr::nips::nip04;
+use nostr_sdk::prelude::rand::rngs::OsRng;
+use nostr_sdk::prelude::rand::Rng;
+use nostr_sdk::prelude::*;
+use nostr_sdk::RelayPoolNotification;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// Kind of events carrying requests and responses.
+const KIND: u64 = 24133;
+
+/// How long to wait for a response. Signer may wait for its user
+/// to approve the request, which takes a while.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Result of a response asking user to approve the request at URL
+/// given in its error.
+const AUTH_URL: &str = "auth_url";
+
+#[derive(Debug)]
+pub enum Nip46Error {
+    /// URI of the signer is not valid.
+    Uri(String),
+    Key(nostr_sdk::nostr::key::Error),
+    Builder(nostr_sdk::nostr::event::builder::Error),
+    Nip04(nip04::Error),
+    Json(serde_json::Error),
+    Client(nostr_sdk::client::Error),
+    /// Signer refused the request.
+    Refused(String),
+    /// Signer did not respond in time.
+    TimedOut,
+    /// Signer responded with something else than it was asked for.
+    Invalid(String),
+}
+
+impl Display for Nip46Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Nip46Error::Uri(e) => write!(f, "Invalid remote signer: {e}"),
+            Nip46Error::Key(e) => write!(f, "Could not sign request: {e}"),
+            Nip46Error::Builder(e) => write!(f, "Could not sign request: {e}"),
+            Nip46Error::Nip04(e) => write!(f, "Could not encrypt message of remote signer: {e}"),
+            Nip46Error::Json(e) => write!(f, "Invalid message of remote signer: {e}"),
+            Nip46Error::Client(e) => write!(f, "Could not reach remote signer: {e}"),
+            Nip46Error::Refused(e) => write!(f, "Remote signer refused: {e}"),
+            Nip46Error::TimedOut => write!(f, "Remote signer did not respond."),
+            Nip46Error::Invalid(e) => write!(f, "Invalid response of remote signer: {e}"),
+        }
+    }
+}
+
+impl From<nostr_sdk::nostr::key::Error> for Nip46Error {
+    fn from(e: nostr_sdk::nostr::key::Error) -> Self {
+        Nip46Error::Key(e)
+    }
+}
+
+impl From<nostr_sdk::nostr::event::builder::Error> for Nip46Error {
+    fn from(e: nostr_sdk::nostr::event::builder::Error) -> Self {
+        Nip46Error::Builder(e)
+    }
+}
+
+impl From<nip04::Error> for Nip46Error {
+    fn from(e: nip04::Error) -> Self {
+        Nip46Error::Nip04(e)
+    }
+}
+
+impl From<serde_json::Error> for Nip46Error {
+    fn from(e: serde_json::Error) -> Self {
+        Nip46Error::Json(e)
+    }
+}
+
+impl From<nostr_sdk::client::Error> for Nip46Error {
+    fn from(e: nostr_sdk::client::Error) -> Self {
+        Nip46Error::Client(e)
+    }
+}
+
+/// Where to reach a remote signer, given as `bunker://<pubkey>?relay=…&secret=…`.
+/// URIs starting with `nostrconnect://` are refused, they are made by clients
+/// for signers and carry public key of the client, not of the signer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BunkerUri {
+    /// Public key of the signer.
+    pub signer: XOnlyPublicKey,
+    /// Relays the signer listens on.
+    pub relays: Vec<Url>,
+    /// Secret the signer may require when connecting.
+    pub secret: Option<String>,
+}
+
+impl FromStr for BunkerUri {
+    type Err = Nip46Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url = Url::parse(s.trim()).map_err(|e| Nip46Error::Uri(e.to_string()))?;
+
+        match url.scheme() {
+            "bunker" => {}
+            "nostrconnect" => {
+                return Err(Nip46Error::Uri(
+                    "nostrconnect:// is for signers, use bunker:// of the signer".to_string(),
+                ))
+            }
+            scheme => {
+                return Err(Nip46Error::Uri(format!(
+                    "expected bunker://, not {scheme}://"
+                )))
+            }
+        }
+
+        let signer = url
+            .host_str()
+            .and_then(|h| XOnlyPublicKey::from_str(h).ok())
+            .ok_or_else(|| Nip46Error::Uri("missing public key of signer".to_string()))?;
+
+        let mut relays = vec![];
+        let mut secret = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "relay" => relays.push(
+                    Url::parse(&value).map_err(|e| Nip46Error::Uri(format!("{value}: {e}")))?,
+                ),
+                "secret" => secret = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        if relays.is_empty() {
+            return Err(Nip46Error::Uri("no relay of signer".to_string()));
+        }
+
+        Ok(BunkerUri {
+            signer,
+            relays,
+            secret,
+        })
+    }
+}
+
+impl Display for BunkerUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut url =
+            Url::parse(&format!("bunker://{}", self.signer)).map_err(|_| std::fmt::Error)?;
+        {
+            let mut query = url.query_pairs_mut();
+            for relay in &self.relays {
+                query.append_pair("relay", relay.as_str());
+            }
+            if let Some(secret) = &self.secret {
+                query.append_pair("secret", secret);
+            }
+        }
+        write!(f, "{url}")
+    }
+}
+
+/// What is going on with requests sent to the signer, to be shown to user.
+#[derive(Clone, Debug)]
+pub enum SignerStatus {
+    /// Request of the method waits for response.
+    Waiting(String),
+    /// Signer asks user to approve the request at the URL.
+    AuthUrl(String),
+    /// Request of the method was approved.
+    Approved(String),
+    /// Request of the method was refused for the reason.
+    Refused(String, String),
+    /// Signer did not respond to request of the method.
+    TimedOut(String),
+}
+
+impl Display for SignerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerStatus::Waiting(m) => write!(f, "Waiting for remote signer to approve {m}…"),
+            SignerStatus::AuthUrl(url) => write!(f, "Approve request of remote signer at {url}"),
+            SignerStatus::Approved(m) => write!(f, "Remote signer approved {m}."),
+            SignerStatus::Refused(m, e) => write!(f, "Remote signer refused {m}: {e}"),
+            SignerStatus::TimedOut(m) => write!(f, "Remote signer did not respond to {m}."),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub id: String,
+    pub method: String,
+    pub params: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    /// Result as text. Some signers send results, such as signed events,
+    /// as JSON objects rather than strings.
+    fn result_text(&self) -> Option<String> {
+        match &self.result {
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(serde_json::Value::Null) | None => None,
+            Some(other) => Some(other.to_string()),
+        }
+    }
+}
+
+/// Encrypts `message` for `to` into an event signed by `keys`.
+pub fn wrap<T: Serialize>(
+    keys: &Keys,
+    to: XOnlyPublicKey,
+    message: &T,
+) -> Result<Event, Nip46Error> {
+    let content = nip04::encrypt(&keys.secret_key()?, &to, serde_json::to_string(message)?)?;
+    Ok(EventBuilder::new(Kind::from(KIND), content, &[Tag::PubKey(to, None)]).to_event(keys)?)
+}
+
+/// Decrypts message of `event` sent to `keys`.
+pub fn unwrap<T: DeserializeOwned>(keys: &Keys, event: &Event) -> Result<T, Nip46Error> {
+    let json = nip04::decrypt(&keys.secret_key()?, &event.pubkey, &event.content)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Way of delivering requests to the signer. Responses are handed over
+/// to [`RemoteSigner::receive`].
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, event: Event) -> Result<(), Nip46Error>;
+}
+
+/// Delivery of requests over relays of the signer.
+struct RelayTransport(Client);
+
+#[async_trait]
+impl Transport for RelayTransport {
+    async fn send(&self, event: Event) -> Result<(), Nip46Error> {
+        self.0.send_event(event).await?;
+        Ok(())
+    }
+}
+
+/// Remote signer that signs events on behalf of an identity.
+pub struct RemoteSigner {
+    uri: BunkerUri,
+    /// Keys of this client, which sign requests.
+    keys: Keys,
+    transport: Box<dyn Transport>,
+    /// Requests waiting for responses, by their IDs.
+    pending: Mutex<HashMap<String, mpsc::UnboundedSender<Response>>>,
+    on_status: Box<dyn Fn(SignerStatus) + Send + Sync>,
+}
+
+impl std::fmt::Debug for RemoteSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteSigner")
+            .field("uri", &self.uri)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RemoteSigner {
+    pub fn new(
+        uri: BunkerUri,
+        keys: Keys,
+        transport: Box<dyn Transport>,
+        on_status: impl Fn(SignerStatus) + Send + Sync + 'static,
+    ) -> RemoteSigner {
+        RemoteSigner {
+            uri,
+            keys,
+            transport,
+            pending: Default::default(),
+            on_status: Box::new(on_status),
+        }
+    }
+
+    /// Creates signer reached over its relays and starts listening to its responses.
+    pub async fn over_relays(
+        uri: BunkerUri,
+        keys: Keys,
+        on_status: impl Fn(SignerStatus) + Send + Sync + 'static,
+    ) -> Arc<RemoteSigner> {
+        let client = Client::new(&keys);
+        for relay in &uri.relays {
+            if let Err(e) = client.add_relay(relay.as_str(), None).await {
+                warn!("Could not add relay {relay} of remote signer: {e}");
+            }
+        }
+        client.connect().await;
+
+        let filter = Filter::new()
+            .kind(Kind::from(KIND))
+            .pubkey(keys.public_key())
+            .since(Timestamp::now());
+        for (_, relay) in client.relays().await {
+            let _ = relay.subscribe(vec![filter.clone()], None).await;
+        }
+
+        let mut notifications = client.notifications();
+        let signer = Arc::new(RemoteSigner::new(
+            uri,
+            keys,
+            Box::new(RelayTransport(client)),
+            on_status,
+        ));
+
+        let weak = Arc::downgrade(&signer);
+        tokio::spawn(async move {
+            while let Ok(notification) = notifications.recv().await {
+                let Some(signer) = weak.upgrade() else { break };
+                if let RelayPoolNotification::Event(_, event) = notification {
+                    signer.receive(&event);
+                }
+            }
+        });
+
+        signer
+    }
+
+    /// Public key of the signer.
+    pub fn signer(&self) -> XOnlyPublicKey {
+        self.uri.signer
+    }
+
+    /// Hands over event that may contain response to one of the requests.
+    pub fn receive(&self, event: &Event) {
+        if event.kind != Kind::from(KIND) || event.pubkey != self.uri.signer {
+            return;
+        }
+
+        match unwrap::<Response>(&self.keys, event) {
+            Ok(response) => {
+                if let Some(pending) = self.pending.lock().unwrap().get(&response.id) {
+                    pending.send(response).unwrap_or_default();
+                }
+            }
+            Err(e) => debug!("Ignoring message of remote signer: {e}"),
+        }
+    }
+
+    /// Sends request of `method` with `params` and waits for its result.
+    async fn request(&self, method: &str, params: Vec<String>) -> Result<String, Nip46Error> {
+        let id = format!("{:016x}", OsRng.gen::<u64>());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        self.pending.lock().unwrap().insert(id.clone(), tx);
+
+        let result = self.exchange(&id, method, params, &mut rx).await;
+        self.pending.lock().unwrap().remove(&id);
+
+        let status = match &result {
+            Ok(_) => SignerStatus::Approved(method.to_string()),
+            Err(Nip46Error::TimedOut) => SignerStatus::TimedOut(method.to_string()),
+            Err(e) => SignerStatus::Refused(method.to_string(), e.to_string()),
+        };
+        (self.on_status)(status);
+
+        result
+    }
+
+    async fn exchange(
+        &self,
+        id: &str,
+        method: &str,
+        params: Vec<String>,
+        responses: &mut mpsc::UnboundedReceiver<Response>,
+    ) -> Result<String, Nip46Error> {
+        let request = Request {
+            id: id.to_string(),
+            method: method.to_string(),
+            params,
+        };
+        let event = wrap(&self.keys, self.uri.signer, &request)?;
+        self.transport.send(event).await?;
+        (self.on_status)(SignerStatus::Waiting(method.to_string()));
+
+        loop {
+            let response = tokio::time::timeout(REQUEST_TIMEOUT, responses.recv())
+                .await
+                .ok()
+                .flatten()
+                .ok_or(Nip46Error::TimedOut)?;
+
+            match (response.result_text(), response.error) {
+                // Final response comes once user approves at the URL.
+                (Some(result), Some(url)) if result == AUTH_URL => {
+                    (self.on_status)(SignerStatus::AuthUrl(url))
+                }
+                (_, Some(error)) if !error.is_empty() => return Err(Nip46Error::Refused(error)),
+                (Some(result), _) => return Ok(result),
+                (None, _) => return Err(Nip46Error::Invalid("empty response".to_string())),
+            }
+        }
+    }
+
+    /// Connects to the signer, with secret of its URI if there is one.
+    pub async fn connect(&self) -> Result<(), Nip46Error> {
+        let mut params = vec![self.uri.signer.to_string()];
+        params.extend(self.uri.secret.clone());
+        self.request("connect", params).await.map(|_| ())
+    }
+
+    /// Public key of the identity the signer signs as.
+    pub async fn get_public_key(&self) -> Result<XOnlyPublicKey, Nip46Error> {
+        let pubkey = self.request("get_public_key", vec![]).await?;
+        XOnlyPublicKey::from_str(&pubkey).map_err(|e| Nip46Error::Invalid(e.to_string()))
+    }
+
+    /// Has `unsigned` event signed by the signer.
+    pub async fn sign(&self, unsigned: UnsignedEvent) -> Result<Event, Nip46Error> {
+        let json = serde_json::to_string(&unsigned)?;
+        let signed = self.request("sign_event", vec![json]).await?;
+        let event: Event = serde_json::from_str(&signed)?;
+
+        if event.id != unsigned.id || event.pubkey != unsigned.pubkey {
+            return Err(Nip46Error::Invalid("signed another event".to_string()));
+        }
+        event
+            .verify()
+            .map_err(|e| Nip46Error::Invalid(e.to_string()))?;
+
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signer standing in for a real bunker, keeping keys of `user`.
+    struct StandIn {
+        keys: Keys,
+        user: Keys,
+        /// Refuse to sign events.
+        refuse: bool,
+        /// Ask for approval at URL before each response.
+        auth_url: bool,
+    }
+
+    impl StandIn {
+        fn new(user: &Keys) -> StandIn {
+            StandIn {
+                keys: Keys::generate(),
+                user: user.clone(),
+                refuse: false,
+                auth_url: false,
+            }
+        }
+
+        /// Responses to request in `event`.
+        fn respond(&self, event: &Event) -> Vec<Event> {
+            let request: Request = unwrap(&self.keys, event).unwrap();
+            let response = |result: Option<String>, error: Option<String>| {
+                let response = Response {
+                    id: request.id.clone(),
+                    result: result.map(serde_json::Value::String),
+                    error,
+                };
+                wrap(&self.keys, event.pubkey, &response).unwrap()
+            };
+
+            let mut responses = vec![];
+            if self.auth_url {
+                responses.push(response(
+                    Some(AUTH_URL.to_string()),
+                    Some("https://bunker.example.com/approve".to_string()),
+                ));
+            }
+
+            responses.push(match request.method.as_str() {
+                "connect" => response(Some("ack".to_string()), None),
+                "get_public_key" => response(Some(self.user.public_key().to_string()), None),
+                "sign_event" if self.refuse => response(None, Some("not allowed".to_string())),
+                "sign_event" => {
+                    let unsigned: UnsignedEvent = serde_json::from_str(&request.params[0]).unwrap();
+                    let signed = unsigned.sign(&self.user).unwrap();
+                    response(Some(serde_json::to_string(&signed).unwrap()), None)
+                }
+                other => response(None, Some(format!("unknown method {other}"))),
+            });
+
+            responses
+        }
+    }
+
+    /// Passes requests to the stand-in over a channel.
+    struct Loopback(mpsc::UnboundedSender<Event>);
+
+    #[async_trait]
+    impl Transport for Loopback {
+        async fn send(&self, event: Event) -> Result<(), Nip46Error> {
+            self.0.send(event).unwrap();
+            Ok(())
+        }
+    }
+
+    /// Remote signer served by `stand_in` and statuses it reports.
+    fn remote(stand_in: StandIn) -> (Arc<RemoteSigner>, Arc<Mutex<Vec<SignerStatus>>>) {
+        let uri = BunkerUri {
+            signer: stand_in.keys.public_key(),
+            relays: vec![Url::parse("wss://bunker.example.com").unwrap()],
+            secret: Some("s3cret".to_string()),
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        let statuses = Arc::new(Mutex::new(vec![]));
+        let reported = statuses.clone();
+
+        let signer = Arc::new(RemoteSigner::new(
+            uri,
+            Keys::generate(),
+            Box::new(Loopback(tx)),
+            move |s| reported.lock().unwrap().push(s),
+        ));
+
+        let weak = Arc::downgrade(&signer);
+        tokio::spawn(async move {
+            while let Some(request) = rx.recv().await {
+                let Some(signer) = weak.upgrade() else { break };
+                for response in stand_in.respond(&request) {
+                    signer.receive(&response);
+                }
+            }
+        });
+
+        (signer, statuses)
+    }
+
+    #[test]
+    fn bunker_uri() {
+        let signer = Keys::generate().public_key();
+        let uri: BunkerUri = format!(
+            "bunker://{signer}?relay=wss%3A%2F%2Frelay.example.com&relay=wss://other.example.com&secret=abc"
+        )
+        .parse()
+        .unwrap();
+
+        assert_eq!(uri.signer, signer);
+        assert_eq!(
+            uri.relays,
+            vec![
+                Url::parse("wss://relay.example.com").unwrap(),
+                Url::parse("wss://other.example.com").unwrap()
+            ]
+        );
+        assert_eq!(uri.secret.as_deref(), Some("abc"));
+        assert_eq!(uri.to_string().parse::<BunkerUri>().unwrap(), uri);
+
+        // Public key of nostrconnect:// is the client's.
+        assert!(
+            format!("nostrconnect://{signer}?relay=wss://relay.example.com")
+                .parse::<BunkerUri>()
+                .is_err()
+        );
+
+        assert!(format!("bunker://{signer}").parse::<BunkerUri>().is_err());
+        assert!(format!("https://{signer}?relay=wss://relay.example.com")
+            .parse::<BunkerUri>()
+            .is_err());
+        assert!("bunker://abc?relay=wss://relay.example.com"
+            .parse::<BunkerUri>()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn signs_by_remote_signer() {
+        let user = Keys::generate();
+        let (signer, statuses) = remote(StandIn::new(&user));
+
+        signer.connect().await.unwrap();
+        assert_eq!(signer.get_public_key().await.unwrap(), user.public_key());
+
+        let unsigned = EventBuilder::new_text_note("Signed elsewhere", &[])
+            .to_unsigned_event(user.public_key());
+        let event = signer.sign(unsigned).await.unwrap();
+        assert_eq!(event.pubkey, user.public_key());
+        assert_eq!(event.content, "Signed elsewhere");
+        assert!(event.verify().is_ok());
+
+        let statuses = statuses.lock().unwrap();
+        assert!(matches!(&statuses[0], SignerStatus::Waiting(m) if m == "connect"));
+        assert!(matches!(&statuses[1], SignerStatus::Approved(m) if m == "connect"));
+        assert!(matches!(statuses.last(), Some(SignerStatus::Approved(m)) if m == "sign_event"));
+    }
+
+    #[tokio::test]
+    async fn refused_signing() {
+        let user = Keys::generate();
+        let (signer, statuses) = remote(StandIn {
+            refuse: true,
+            ..StandIn::new(&user)
+        });
+
+        let unsigned =
+            EventBuilder::new_text_note("Not allowed", &[]).to_unsigned_event(user.public_key());
+        assert!(matches!(
+            signer.sign(unsigned).await,
+            Err(Nip46Error::Refused(e)) if e == "not allowed"
+        ));
+        assert!(matches!(
+            statuses.lock().unwrap().last(),
+            Some(SignerStatus::Refused(m, _)) if m == "sign_event"
+        ));
+    }
+
+    #[tokio::test]
+    async fn approval_at_url() {
+        let user = Keys::generate();
+        let (signer, statuses) = remote(StandIn {
+            auth_url: true,
+            ..StandIn::new(&user)
+        });
+
+        assert_eq!(signer.get_public_key().await.unwrap(), user.public_key());
+        assert!(statuses
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|s| matches!(s, SignerStatus::AuthUrl(url) if url.ends_with("/approve"))));
+    }
+}
@@ -0,0 +1,186 @@
+//! Secret keys encrypted by password (NIP-49), known by their `ncryptsec` prefix.
+
+use bech32::{FromBase32, ToBase32, Variant};
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{Key, KeyInit, XChaCha20Poly1305, XNonce};
+use nostr_sdk::prelude::rand::rngs::OsRng;
+use nostr_sdk::prelude::rand::Rng;
+use nostr_sdk::secp256k1::SecretKey;
+use unicode_normalization::UnicodeNormalization;
+
+const PREFIX: &str = "ncryptsec";
+
+/// The only version of the format there is.
+const VERSION: u8 = 0x02;
+
+/// Key security byte saying that it is not known how the key was handled
+/// before it was encrypted.
+const UNKNOWN_SECURITY: u8 = 0x02;
+
+/// Largest accepted work factor. Deriving key takes 2^`log_n` KiB of memory,
+/// so 22 is already 4 GiB and more would only exhaust it.
+const MAX_LOG_N: u8 = 22;
+
+/// Length of version, work factor, salt, nonce, key security and encrypted key.
+const PAYLOAD_LEN: usize = 1 + 1 + 16 + 24 + 1 + 48;
+
+#[derive(Debug)]
+pub enum Nip49Error {
+    Bech32(bech32::Error),
+    /// The text is not an encrypted secret key.
+    Invalid(String),
+    /// Key could not be decrypted, most likely because of wrong password.
+    Decrypt,
+}
+
+impl std::fmt::Display for Nip49Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Nip49Error::Bech32(e) => write!(f, "Invalid encrypted key: {e}"),
+            Nip49Error::Invalid(e) => write!(f, "Invalid encrypted key: {e}"),
+            Nip49Error::Decrypt => write!(f, "Could not decrypt key, is the password correct?"),
+        }
+    }
+}
+
+impl From<bech32::Error> for Nip49Error {
+    fn from(e: bech32::Error) -> Self {
+        Nip49Error::Bech32(e)
+    }
+}
+
+/// Encrypts `secret` key by `password`. Deriving key from the password
+/// takes 2^`log_n` rounds of scrypt.
+pub fn encrypt(secret: &SecretKey, password: &str, log_n: u8) -> Result<String, Nip49Error> {
+    let salt = OsRng.gen::<[u8; 16]>();
+    let nonce = OsRng.gen::<[u8; 24]>();
+
+    let cipher = XChaCha20Poly1305::new(&derive_key(password, &salt, log_n)?);
+    let encrypted = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &secret.secret_bytes(),
+                aad: &[UNKNOWN_SECURITY],
+            },
+        )
+        .map_err(|_| Nip49Error::Decrypt)?;
+
+    let mut payload = Vec::with_capacity(PAYLOAD_LEN);
+    payload.push(VERSION);
+    payload.push(log_n);
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.push(UNKNOWN_SECURITY);
+    payload.extend_from_slice(&encrypted);
+
+    Ok(bech32::encode(
+        PREFIX,
+        payload.to_base32(),
+        Variant::Bech32,
+    )?)
+}
+
+/// Decrypts secret key from `ncryptsec` by `password`.
+pub fn decrypt(ncryptsec: &str, password: &str) -> Result<SecretKey, Nip49Error> {
+    let (prefix, data, _) = bech32::decode(ncryptsec.trim())?;
+    if prefix != PREFIX {
+        return Err(Nip49Error::Invalid(format!("unexpected prefix {prefix}")));
+    }
+
+    let payload = Vec::<u8>::from_base32(&data)?;
+    if payload.len() != PAYLOAD_LEN {
+        return Err(Nip49Error::Invalid(format!("length {}", payload.len())));
+    }
+    if payload[0] != VERSION {
+        return Err(Nip49Error::Invalid(format!("version {}", payload[0])));
+    }
+
+    let log_n = payload[1];
+    let salt = &payload[2..18];
+    let nonce = &payload[18..42];
+    let security = &payload[42..43];
+    let encrypted = &payload[43..];
+
+    let cipher = XChaCha20Poly1305::new(&derive_key(password, salt, log_n)?);
+    let secret = cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: encrypted,
+                aad: security,
+            },
+        )
+        .map_err(|_| Nip49Error::Decrypt)?;
+
+    SecretKey::from_slice(&secret).map_err(|e| Nip49Error::Invalid(e.to_string()))
+}
+
+/// Derives symmetric key from `password` normalized to NFKC.
+fn derive_key(password: &str, salt: &[u8], log_n: u8) -> Result<Key, Nip49Error> {
+    if log_n > MAX_LOG_N {
+        return Err(Nip49Error::Invalid(format!(
+            "work factor {log_n} is too large"
+        )));
+    }
+
+    let password = password.nfkc().collect::<String>();
+    let params = scrypt::Params::new(log_n, 8, 1)
+        .map_err(|e| Nip49Error::Invalid(format!("work factor {log_n}: {e}")))?;
+
+    let mut key = Key::default();
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| Nip49Error::Invalid(e.to_string()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn decrypt_test_vector() {
+        let ncryptsec = "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p";
+        let expected =
+            SecretKey::from_str("3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683")
+                .unwrap();
+
+        assert_eq!(decrypt(ncryptsec, "nostr").unwrap(), expected);
+        assert!(matches!(
+            decrypt(ncryptsec, "nostr!"),
+            Err(Nip49Error::Decrypt)
+        ));
+    }
+
+    #[test]
+    fn encrypt_round_trip() {
+        let secret =
+            SecretKey::from_str("3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683")
+                .unwrap();
+
+        // Password is normalized, so differently composed "Å" is the same password.
+        let ncryptsec = encrypt(&secret, "\u{212B}", 4).unwrap();
+        assert!(ncryptsec.starts_with("ncryptsec1"));
+        assert_eq!(decrypt(&ncryptsec, "\u{00C5}").unwrap(), secret);
+    }
+
+    #[test]
+    fn too_large_work_factor_refused() {
+        let secret =
+            SecretKey::from_str("3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683")
+                .unwrap();
+        let ncryptsec = encrypt(&secret, "nostr", 4).unwrap();
+
+        let (_, data, _) = bech32::decode(&ncryptsec).unwrap();
+        let mut payload = Vec::<u8>::from_base32(&data).unwrap();
+        payload[1] = 40;
+        let tampered = bech32::encode(PREFIX, payload.to_base32(), Variant::Bech32).unwrap();
+
+        assert!(matches!(
+            decrypt(&tampered, "nostr"),
+            Err(Nip49Error::Invalid(_))
+        ));
+    }
+}
@@ -18,6 +18,12 @@ lazy_static! {
 
 pub fn parse_content(event: &ReceivedEvent) -> DynamicContent {
     let ReceivedEvent { event, relay } = event;
+    parse_text(&event.content, &event.tags, Some(relay))
+}
+
+/// Parses `content` of a text note with `tags`, which can be found at `relay`.
+/// The text does not have to be a complete note, it may be still being written.
+pub fn parse_text(content: &str, tags: &[Tag], relay: Option<&Url>) -> DynamicContent {
     let mut dcontent = DynamicContent::new();
 
     // About trimming of content.
@@ -30,19 +36,23 @@ pub fn parse_content(event: &ReceivedEvent) -> DynamicContent {
     // be in the middle of Unicode character).
     //
     // So the trimming is done only at the presentation time.
-    let message = &html_escape::encode_text(&event.content);
+    let message = &html_escape::encode_text(content);
 
     NIP21.captures_iter(message).for_each(|c| {
         let nip19 = c.name("nip19").unwrap().as_str();
         let range = c.get(0).unwrap().range();
 
         match c.name("type").map(|m| m.as_str()) {
-            Some("nprofile") => {
-                let what = Profile::from_bech32(nip19).unwrap();
-                let key = what.public_key.to_bech32().unwrap();
-                let with = format!(r#"<a href="nostr:{}">@{}…</a>"#, nip19, &key[..16]);
-                dcontent.add(range, with, what);
-            }
+            Some("nprofile") => match Profile::from_bech32(nip19) {
+                Ok(what) => {
+                    let key = what.public_key.to_bech32().unwrap();
+                    let with = format!(r#"<a href="nostr:{}">@{}…</a>"#, nip19, &key[..16]);
+                    dcontent.add(range, with, what);
+                }
+                Err(err) => {
+                    tracing::error!("Failed parse {} because {:?}", nip19, err);
+                }
+            },
             Some("npub") => match XOnlyPublicKey::from_bech32(nip19) {
                 Ok(key) => {
                     let with = format!(
@@ -56,26 +66,34 @@ pub fn parse_content(event: &ReceivedEvent) -> DynamicContent {
                     tracing::error!("Failed parse {} because {:?}", nip19, err);
                 }
             },
-            Some("nevent") => {
-                let what = Nip19Event::from_bech32(nip19).unwrap();
-                // TODO: add relays to link
-                let with = format!(
-                    r#"<a href="gnostique:search?event={}&relay=&relay=">{}…</a>"#,
-                    nip19,
-                    &nip19[..24]
-                );
-                dcontent.add(range, with, what);
-            }
-            Some("note") => {
-                let what = EventId::from_bech32(nip19).unwrap();
-                let with = format!(
-                    r#"<a href="gnostique:search?event={}&relay={}">{}…</a>"#,
-                    nip19,
-                    relay,
-                    &nip19[..24]
-                );
-                dcontent.add(range, with, (Kind::TextNote, what));
-            }
+            Some("nevent") => match Nip19Event::from_bech32(nip19) {
+                Ok(what) => {
+                    // TODO: add relays to link
+                    let with = format!(
+                        r#"<a href="gnostique:search?event={}&relay=&relay=">{}…</a>"#,
+                        nip19,
+                        &nip19[..24]
+                    );
+                    dcontent.add(range, with, what);
+                }
+                Err(err) => {
+                    tracing::error!("Failed parse {} because {:?}", nip19, err);
+                }
+            },
+            Some("note") => match EventId::from_bech32(nip19) {
+                Ok(what) => {
+                    let with = format!(
+                        r#"<a href="gnostique:search?event={}&relay={}">{}…</a>"#,
+                        nip19,
+                        relay.map(Url::as_str).unwrap_or_default(),
+                        &nip19[..24]
+                    );
+                    dcontent.add(range, with, (Kind::TextNote, what));
+                }
+                Err(err) => {
+                    tracing::error!("Failed parse {} because {:?}", nip19, err);
+                }
+            },
             _ => (),
         }
     });
@@ -93,7 +111,7 @@ pub fn parse_content(event: &ReceivedEvent) -> DynamicContent {
     MENTION.captures_iter(message).for_each(|c| {
         let range = c.get(0).unwrap().range();
         let idx: usize = c.name("idx").unwrap().as_str().parse().unwrap();
-        match event.tags.get(idx) {
+        match tags.get(idx) {
             Some(Tag::Event(id, _, _)) => {
                 let nip19 = id.to_bech32().unwrap();
                 let with = format!(r#"<a href="nostr:{}">{}…</a>"#, nip19, &nip19[..24]);
@@ -53,6 +53,10 @@ impl Preview {
         &self.url
     }
 
+    pub fn kind(&self) -> &PreviewKind {
+        &self.kind
+    }
+
     pub fn title(&self) -> Option<&str> {
         self.title.as_deref()
     }
@@ -73,6 +77,10 @@ impl Preview {
     pub fn description(&self) -> Option<&String> {
         self.description.as_ref()
     }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -83,6 +91,10 @@ pub struct Thumbnail {
 }
 
 impl Thumbnail {
+    pub fn new(texture: gdk::Texture, url: reqwest::Url) -> Thumbnail {
+        Thumbnail { texture, url }
+    }
+
     pub fn texture(&self) -> &gdk::Texture {
         &self.texture
     }
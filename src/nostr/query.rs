@@ -0,0 +1,445 @@
+//! Textual lane queries.
+//!
+//! A query is parsed into [`Subscription`]. It is made of terms combined by
+//! `!` (not), `&` (and) and `+` (or), in the order of precedence; parentheses
+//! can be used for grouping. The terms are:
+//!
+//! - `#tag`: notes with hashtag,
+//! - `@npub1…`, `@nprofile1…` or `@<hex>`: profile,
+//! - `author:npub1…`: notes by the author,
+//! - `kind:1,6`: events of kinds,
+//! - `since:2d`, `until:1700000000`: time bounds, either relative (with unit
+//!   `s`, `m`, `h`, `d` or `w`) or absolute (Unix timestamp),
+//! - `note1…`, `nevent1…` or `<hex>`: thread of the event,
+//! - `id:<event>`: the event itself, `e:<event>`: events referencing it,
+//! - `search:word` or `"free text"`: notes containing all the words,
+//! - `following`: notes of followed authors,
+//! - `sink`: everything.
+//!
+//! For example `#nostr & @npub1… & kind:1 & since:2d`.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+use nostr_sdk::prelude::*;
+
+use super::subscriptions::{Subscription, Time};
+use crate::follow::Follow;
+
+/// Query could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    /// Position (in characters) in the query where the problem is.
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.position)
+    }
+}
+
+impl FromStr for Subscription {
+    type Err = QueryError;
+
+    /// Parses lane query. Follow list of `following` is empty, it is up to
+    /// the caller to supply one (see [`Subscription::with_follow`]).
+    fn from_str(query: &str) -> Result<Subscription, QueryError> {
+        let mut parser = Parser {
+            tokens: tokenize(query),
+            next: 0,
+            end: query.chars().count(),
+        };
+
+        let subscription = parser.or()?;
+
+        match parser.peek() {
+            Some(t) => Err(t.error(format!("Unexpected '{}'", t.text))),
+            // Relays would have to send everything for the rest to be excluded.
+            None if !subscription.has_positive_terms() => Err(QueryError {
+                position: 0,
+                message: "Query has to select something, not only exclude".to_string(),
+            }),
+            None => Ok(subscription),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    position: usize,
+    text: &'a str,
+}
+
+impl Token<'_> {
+    fn error<S: Into<String>>(&self, message: S) -> QueryError {
+        QueryError {
+            position: self.position,
+            message: message.into(),
+        }
+    }
+}
+
+/// Splits query into operators, parentheses and words. Whitespace and
+/// operators within double quotes are part of words.
+fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word: Option<(usize, usize)> = None;
+    let mut quoted = false;
+
+    for (position, (byte, c)) in query.char_indices().enumerate() {
+        if c == '"' {
+            quoted = !quoted;
+        }
+
+        let operator = !quoted && matches!(c, '(' | ')' | '&' | '+' | '!');
+
+        if (c.is_whitespace() && !quoted) || operator {
+            if let Some((position, start)) = word.take() {
+                tokens.push(Token {
+                    position,
+                    text: &query[start..byte],
+                });
+            }
+        } else if word.is_none() {
+            word = Some((position, byte));
+        }
+
+        if operator {
+            tokens.push(Token {
+                position,
+                text: &query[byte..byte + c.len_utf8()],
+            });
+        }
+    }
+
+    if let Some((position, start)) = word {
+        tokens.push(Token {
+            position,
+            text: &query[start..],
+        });
+    }
+
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+    /// Position of the end of the query.
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Result<&Token<'a>, QueryError> {
+        let end = self.end;
+        let token = self.tokens.get(self.next).ok_or_else(|| QueryError {
+            position: end,
+            message: "Unexpected end of query".to_string(),
+        })?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    /// Consumes next token if it is `operator`.
+    fn operator(&mut self, operator: &str) -> bool {
+        let found = self.peek().is_some_and(|t| t.text == operator);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Subscription, QueryError> {
+        let mut s = self.and()?;
+        while self.operator("+") {
+            s = s.add(self.and()?);
+        }
+        Ok(s)
+    }
+
+    fn and(&mut self) -> Result<Subscription, QueryError> {
+        let mut s = self.not()?;
+        while self.operator("&") {
+            s = s.and(self.not()?);
+        }
+        Ok(s)
+    }
+
+    fn not(&mut self) -> Result<Subscription, QueryError> {
+        if self.operator("!") {
+            Ok(!self.not()?)
+        } else {
+            self.term()
+        }
+    }
+
+    fn term(&mut self) -> Result<Subscription, QueryError> {
+        let (position, text) = {
+            let token = self.advance()?;
+            (token.position, token.text)
+        };
+
+        let error = |offset: usize, message: String| QueryError {
+            position: position + offset,
+            message,
+        };
+
+        match text {
+            "(" => {
+                let s = self.or()?;
+                if self.operator(")") {
+                    Ok(s)
+                } else {
+                    let end = self.end;
+                    Err(self
+                        .peek()
+                        .map(|t| t.error("Expected ')'"))
+                        .unwrap_or(QueryError {
+                            position: end,
+                            message: "Missing ')'".to_string(),
+                        }))
+                }
+            }
+            ")" | "&" | "+" | "!" => Err(error(0, format!("Unexpected '{text}'"))),
+            word => parse_term(word).map_err(|(offset, message)| error(offset, message)),
+        }
+    }
+}
+
+/// Parses a single term. Errors carry position within the term.
+fn parse_term(word: &str) -> Result<Subscription, (usize, String)> {
+    if let Some(tag) = word.strip_prefix('#') {
+        if !tag.is_empty()
+            && tag
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            Ok(Subscription::hashtag(tag))
+        } else {
+            Err((1, format!("Invalid hashtag '{word}'")))
+        }
+    } else if let Some(pubkey) = word.strip_prefix('@') {
+        let (pubkey, relays) = parse_pubkey(pubkey).map_err(|e| (1, e))?;
+        Ok(Subscription::profile(pubkey, relays))
+    } else if let Some((key, value)) = word.split_once(':') {
+        let offset = key.chars().count() + 1;
+        let at = |e| (offset, e);
+
+        match key.to_lowercase().as_str() {
+            "author" => Ok(Subscription::Author(parse_pubkey(value).map_err(at)?.0)),
+            "kind" => Ok(Subscription::Kinds(parse_kinds(value).map_err(at)?)),
+            "since" => Ok(Subscription::Since(parse_time(value).map_err(at)?)),
+            "until" => Ok(Subscription::Until(parse_time(value).map_err(at)?)),
+            "id" => parse_event(value)
+                .map(Subscription::Id)
+                .ok_or_else(|| at(format!("Invalid event '{value}'"))),
+            "e" => parse_event(value)
+                .map(Subscription::Event)
+                .ok_or_else(|| at(format!("Invalid event '{value}'"))),
+            "search" => parse_search(value).map_err(at),
+            _ => Err((0, format!("Unknown constraint '{key}'"))),
+        }
+    } else if word.starts_with('"') {
+        parse_search(word).map_err(|e| (0, e))
+    } else {
+        match word.to_lowercase().as_str() {
+            "following" => Ok(Subscription::Following(Follow::new())),
+            "sink" => Ok(Subscription::Sink),
+            _ => parse_event(word)
+                .map(Subscription::thread)
+                .ok_or_else(|| (0, format!("Unknown term '{word}'"))),
+        }
+    }
+}
+
+/// Parses public key with optional relays, as `npub`, `nprofile` or hex.
+pub fn parse_pubkey(s: &str) -> Result<(XOnlyPublicKey, Vec<Url>), String> {
+    if s.starts_with("nprofile1") {
+        Profile::from_bech32(s)
+            .map(|p| {
+                let relays = p.relays.iter().filter_map(|r| Url::parse(r).ok()).collect();
+                (p.public_key, relays)
+            })
+            .map_err(|e| format!("Invalid profile: {e}"))
+    } else if s.starts_with("npub1") {
+        XOnlyPublicKey::from_bech32(s)
+            .map(|pk| (pk, vec![]))
+            .map_err(|e| format!("Invalid public key: {e}"))
+    } else {
+        XOnlyPublicKey::from_str(s)
+            .map(|pk| (pk, vec![]))
+            .map_err(|_| format!("Invalid public key '{s}'"))
+    }
+}
+
+/// Parses event ID as `note`, `nevent` or hex.
+fn parse_event(s: &str) -> Option<EventId> {
+    if s.starts_with("nevent1") {
+        Nip19Event::from_bech32(s).ok().map(|e| e.event_id)
+    } else if s.starts_with("note1") {
+        EventId::from_bech32(s).ok()
+    } else if s.len() == 64 {
+        EventId::from_hex(s).ok()
+    } else {
+        None
+    }
+}
+
+/// Parses search text, either a single word or words in double quotes.
+fn parse_search(s: &str) -> Result<Subscription, String> {
+    let text = match s.strip_prefix('"') {
+        Some(quoted) => quoted
+            .strip_suffix('"')
+            .ok_or_else(|| "Missing closing '\"'".to_string())?,
+        None => s,
+    };
+
+    match Subscription::search(text) {
+        Subscription::Search(t) if t.is_empty() => Err("Nothing to search for".to_string()),
+        search => Ok(search),
+    }
+}
+
+/// Parses comma-separated list of kinds.
+fn parse_kinds(s: &str) -> Result<Vec<Kind>, String> {
+    s.split(',')
+        .map(|k| {
+            k.parse::<u64>()
+                .map(Kind::from)
+                .map_err(|_| format!("Invalid kind '{k}'"))
+        })
+        .collect()
+}
+
+/// Parses relative time (number with unit) or absolute time (Unix timestamp).
+fn parse_time(s: &str) -> Result<Time, String> {
+    let unit = match s.chars().last() {
+        Some('s') => Some(1),
+        Some('m') => Some(60),
+        Some('h') => Some(3600),
+        Some('d') => Some(86400),
+        Some('w') => Some(7 * 86400),
+        _ => None,
+    };
+
+    let number = match unit {
+        Some(_) => &s[..s.len() - 1],
+        None => s,
+    };
+
+    let n = number
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid time '{s}', expected e. g. '2d' or a timestamp"))?;
+
+    Ok(match unit {
+        Some(unit) => Time::Ago(Duration::from_secs(
+            n.checked_mul(unit)
+                .ok_or_else(|| format!("Time '{s}' is too far back"))?,
+        )),
+        None => Time::At(Timestamp::from(n)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use nostr_sdk::prelude::*;
+
+    use crate::nostr::subscriptions::{Subscription, Time};
+
+    #[test]
+    fn parse_combined() {
+        let pk = Keys::generate().public_key();
+        let query = format!("#nostr & @{} & kind:1 & since:2d", pk.to_bech32().unwrap());
+
+        let expected = Subscription::hashtag("nostr")
+            .and(Subscription::profile(pk, vec![]))
+            .and(Subscription::Kinds(vec![Kind::TextNote]))
+            .and(Subscription::Since(Time::Ago(Duration::from_secs(
+                2 * 86400,
+            ))));
+
+        assert_eq!(query.parse::<Subscription>(), Ok(expected));
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let s = "#a + #b & !#c".parse::<Subscription>().unwrap();
+        let expected = Subscription::hashtag("a")
+            .add(Subscription::hashtag("b").and(!Subscription::hashtag("c")));
+
+        assert_eq!(s, expected);
+        assert!(matches!(s, Subscription::Or(..)));
+    }
+
+    #[test]
+    fn parse_thread() {
+        let id =
+            EventId::from_hex("b10b0d5e5fae7e63b6e41e0a4a5e3e2d4e0b6d4fa4ff4b4b9e5d1a3dd1f6d4a2")
+                .unwrap();
+
+        assert_eq!(
+            id.to_bech32().unwrap().parse::<Subscription>(),
+            Ok(Subscription::thread(id))
+        );
+    }
+
+    #[test]
+    fn rendered_thread_parses() {
+        let id =
+            EventId::from_hex("b10b0d5e5fae7e63b6e41e0a4a5e3e2d4e0b6d4fa4ff4b4b9e5d1a3dd1f6d4a2")
+                .unwrap();
+        let s = Subscription::Event(id);
+
+        assert_eq!(s.to_string().parse::<Subscription>(), Ok(s));
+        assert_ne!(Subscription::Event(id), Subscription::thread(id));
+    }
+
+    #[test]
+    fn rendered_parses() {
+        let s = Subscription::hashtag("a")
+            .add(Subscription::hashtag("b"))
+            .and(!Subscription::Kinds(vec![Kind::Reaction]))
+            .and(Subscription::Until(Time::At(Timestamp::from(1700000000))))
+            .and(Subscription::Author(Keys::generate().public_key()));
+
+        assert_eq!(s.to_string().parse::<Subscription>(), Ok(s));
+    }
+
+    #[test]
+    fn parse_search() {
+        let s = "\"nostr & gtk\" + search:relm4".parse::<Subscription>();
+        let expected = Subscription::search("nostr & gtk").add(Subscription::search("relm4"));
+
+        assert_eq!(s, Ok(expected.clone()));
+        assert_eq!(expected.to_string().parse::<Subscription>(), Ok(expected));
+    }
+
+    #[test]
+    fn error_positions() {
+        let e = |q: &str| q.parse::<Subscription>().unwrap_err().position;
+
+        assert_eq!(e("#a & kind:x"), 10);
+        assert_eq!(e("#a & & #b"), 5);
+        assert_eq!(e("(#a + #b"), 8);
+        assert_eq!(e("#a bogus"), 3);
+        assert_eq!(e("#a + since:"), 11);
+        assert_eq!(e("#a & search:\"b"), 12);
+        assert_eq!(e("#a & since:99999999999999999w"), 11);
+    }
+
+    #[test]
+    fn only_exclusion_refused() {
+        assert!("!#nsfw".parse::<Subscription>().is_err());
+        assert!("#a + !#b".parse::<Subscription>().is_err());
+        assert!("#a & !#b".parse::<Subscription>().is_ok());
+    }
+}
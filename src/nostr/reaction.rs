@@ -0,0 +1,203 @@
+//! Reactions to events (NIP-25), possibly by custom emoji (NIP-30),
+//! and their retraction by deletion (NIP-09).
+
+use nostr_sdk::prelude::*;
+
+use super::EventExt;
+
+/// Content of a like.
+pub const LIKE: &str = "+";
+
+/// Content of a dislike.
+pub const DISLIKE: &str = "-";
+
+/// Custom emoji (NIP-30), written as `:shortcode:` and shown as image at `url`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CustomEmoji {
+    pub shortcode: String,
+    pub url: Url,
+}
+
+impl CustomEmoji {
+    fn tag(&self) -> Tag {
+        Tag::Generic(
+            TagKind::Custom("emoji".to_string()),
+            vec![self.shortcode.clone(), self.url.to_string()],
+        )
+    }
+}
+
+/// Reaction of `author` to an event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reaction {
+    /// ID of the reaction event itself.
+    pub id: EventId,
+    pub author: XOnlyPublicKey,
+    pub content: String,
+    /// Custom emoji, if the content is its shortcode.
+    pub emoji: Option<CustomEmoji>,
+}
+
+impl Reaction {
+    /// Reads reaction `event`. Returns ID of the event it reacts to
+    /// and the reaction, or `None` if it is not a reaction.
+    pub fn from_event(event: &Event) -> Option<(EventId, Reaction)> {
+        let to = event.reacts_to()?;
+
+        let emoji = event
+            .content
+            .strip_prefix(':')
+            .and_then(|c| c.strip_suffix(':'))
+            .and_then(|shortcode| {
+                event.tags.iter().find_map(|t| match t.as_vec().as_slice() {
+                    [name, code, url, ..] if name == "emoji" && code == shortcode => {
+                        Some(CustomEmoji {
+                            shortcode: code.clone(),
+                            url: Url::parse(url).ok()?,
+                        })
+                    }
+                    _ => None,
+                })
+            });
+
+        let reaction = Reaction {
+            id: event.id,
+            author: event.pubkey,
+            content: event.content.clone(),
+            emoji,
+        };
+
+        Some((to, reaction))
+    }
+
+    /// Empty content is a like too.
+    pub fn is_like(&self) -> bool {
+        matches!(self.content.as_str(), LIKE | "" | "🤙")
+    }
+
+    pub fn is_dislike(&self) -> bool {
+        self.content == DISLIKE
+    }
+
+    /// Reactions are the same if they show the same, whoever wrote them.
+    pub fn same_as(&self, other: &Reaction) -> bool {
+        self.content == other.content
+            && self.emoji.as_ref().map(|e| &e.url) == other.emoji.as_ref().map(|e| &e.url)
+    }
+}
+
+/// Builder of reaction to `event` by `content`, possibly a shortcode of custom `emoji`.
+/// The event can be found at `relay`.
+pub fn reaction(
+    event: &Event,
+    relay: Option<&Url>,
+    content: &str,
+    emoji: Option<&CustomEmoji>,
+) -> EventBuilder {
+    let mut tags = vec![
+        Tag::Event(
+            event.id,
+            relay.map(|u| UncheckedUrl::from(u.to_string())),
+            None,
+        ),
+        Tag::PubKey(event.pubkey, None),
+    ];
+    tags.extend(emoji.map(CustomEmoji::tag));
+
+    EventBuilder::new(Kind::Reaction, content, &tags)
+}
+
+/// Builder of request to delete own events of `ids` (NIP-09).
+pub fn deletion(ids: &[EventId]) -> EventBuilder {
+    let tags = ids
+        .iter()
+        .map(|id| Tag::Event(*id, None, None))
+        .collect::<Vec<_>>();
+
+    EventBuilder::new(Kind::EventDeletion, "", &tags)
+}
+
+/// IDs of events that deletion `event` asks to delete.
+pub fn deleted_ids(event: &Event) -> Vec<EventId> {
+    if event.kind != Kind::EventDeletion {
+        return vec![];
+    }
+
+    event
+        .tags
+        .iter()
+        .filter_map(|t| match t {
+            Tag::Event(id, _, _) => Some(*id),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn like_and_custom_emoji() {
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+        let note = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&alice)
+            .unwrap();
+        let relay = Url::parse("wss://relay.example.com").unwrap();
+
+        let like = reaction(&note, Some(&relay), LIKE, None)
+            .to_event(&bob)
+            .unwrap();
+        assert_eq!(like.kind, Kind::Reaction);
+        assert!(like.tags.contains(&Tag::PubKey(alice.public_key(), None)));
+
+        let (to, like) = Reaction::from_event(&like).unwrap();
+        assert_eq!(to, note.id);
+        assert_eq!(like.author, bob.public_key());
+        assert!(like.is_like());
+        assert!(!like.is_dislike());
+        assert_eq!(like.emoji, None);
+
+        let soapbox = CustomEmoji {
+            shortcode: "soapbox".to_string(),
+            url: Url::parse("https://example.com/soapbox.png").unwrap(),
+        };
+        let custom = reaction(&note, None, ":soapbox:", Some(&soapbox))
+            .to_event(&bob)
+            .unwrap();
+        let (to, custom) = Reaction::from_event(&custom).unwrap();
+        assert_eq!(to, note.id);
+        assert_eq!(custom.emoji, Some(soapbox));
+        assert!(!custom.is_like());
+        assert!(!custom.same_as(&like));
+
+        // Shortcode without its emoji tag is just text.
+        let plain = EventBuilder::new(
+            Kind::Reaction,
+            ":soapbox:",
+            &[Tag::Event(note.id, None, None)],
+        )
+        .to_event(&alice)
+        .unwrap();
+        let (_, plain) = Reaction::from_event(&plain).unwrap();
+        assert_eq!(plain.emoji, None);
+        assert!(!plain.same_as(&custom));
+
+        assert_eq!(Reaction::from_event(&note), None);
+    }
+
+    #[test]
+    fn retract_by_deletion() {
+        let keys = Keys::generate();
+        let note = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        let like = reaction(&note, None, LIKE, None).to_event(&keys).unwrap();
+
+        let deletion = deletion(&[like.id]).to_event(&keys).unwrap();
+        assert_eq!(deletion.kind, Kind::EventDeletion);
+        assert_eq!(deleted_ids(&deletion), vec![like.id]);
+        assert_eq!(deleted_ids(&like), vec![]);
+    }
+}
@@ -1,20 +1,152 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Display;
+use std::ops::Not;
+use std::str::FromStr;
+use std::time::Duration;
 
-use nostr_sdk::prelude::{Filter, Tag, Url, XOnlyPublicKey};
+use nostr_sdk::prelude::{Filter, Kind, Tag, Url, XOnlyPublicKey};
 use nostr_sdk::relay::ActiveSubscription;
 use nostr_sdk::{Event, EventId, Timestamp};
 
 use super::EventExt;
+use crate::follow::Follow;
 
+/// Maximum number of authors in a single filter. Relays tend to
+/// refuse filters that are too large.
+pub const AUTHORS_PER_FILTER: usize = 250;
+
+/// What a lane shows. Subscriptions are equal when they are composed
+/// the same way; relay hints of profiles and order of kinds do not matter.
 #[derive(Debug, Clone)]
 pub enum Subscription {
+    /// Everything relays send, meant for debugging.
     Sink,
+    /// Notes of authors followed by the current identity.
+    Following(Follow),
     Hashtag(String),
     Profile(XOnlyPublicKey, Vec<Url>),
     Id(EventId),
     Event(EventId),
-    // And(Box<Subscriptions>, Box<Subscriptions>),
+    /// Events of any of the kinds.
+    Kinds(Vec<Kind>),
+    /// Events created at or after the time.
+    Since(Time),
+    /// Events created at or before the time.
+    Until(Time),
+    /// Events created by the author.
+    Author(XOnlyPublicKey),
+    /// Notes containing all words of the text. Relays are asked
+    /// only if they support search (NIP-50).
+    Search(String),
+    And(Box<Subscription>, Box<Subscription>),
     Or(Box<Subscription>, Box<Subscription>),
+    /// Events not selected by the subscription. Relays cannot be asked for this,
+    /// so it is left out of filters and evaluated only by [`Subscription::accepts`].
+    Not(Box<Subscription>),
+}
+
+/// Point in time, either fixed or relative to the present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Time {
+    At(Timestamp),
+    Ago(Duration),
+}
+
+impl Time {
+    /// Determines the point in time as of now.
+    pub fn resolve(&self) -> Timestamp {
+        match self {
+            Time::At(t) => *t,
+            Time::Ago(d) => Timestamp::from(Timestamp::now().as_u64().saturating_sub(d.as_secs())),
+        }
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Time::At(t) => write!(f, "{}", t.as_u64()),
+            Time::Ago(d) => {
+                let secs = d.as_secs();
+                match [(86400, "d"), (3600, "h"), (60, "m")]
+                    .into_iter()
+                    .find(|(unit, _)| secs != 0 && secs % unit == 0)
+                {
+                    Some((unit, suffix)) => write!(f, "{}{suffix}", secs / unit),
+                    None => write!(f, "{secs}s"),
+                }
+            }
+        }
+    }
+}
+
+impl PartialEq for Subscription {
+    fn eq(&self, other: &Self) -> bool {
+        use Subscription::*;
+
+        match (self, other) {
+            (Sink, Sink) => true,
+            (Following(f1), Following(f2)) => {
+                f1.pubkeys().count() == f2.pubkeys().count()
+                    && f1.pubkeys().all(|pk| f2.follows(pk))
+            }
+            (Hashtag(t1), Hashtag(t2)) | (Search(t1), Search(t2)) => t1 == t2,
+            (Profile(pk1, _), Profile(pk2, _)) | (Author(pk1), Author(pk2)) => pk1 == pk2,
+            (Id(id1), Id(id2)) | (Event(id1), Event(id2)) => id1 == id2,
+            (Kinds(ks1), Kinds(ks2)) => {
+                let set = |ks: &[Kind]| ks.iter().map(|k| k.as_u64()).collect::<BTreeSet<_>>();
+                set(ks1) == set(ks2)
+            }
+            (Since(t1), Since(t2)) | (Until(t1), Until(t2)) => t1 == t2,
+            (And(a1, b1), And(a2, b2)) | (Or(a1, b1), Or(a2, b2)) => a1 == a2 && b1 == b2,
+            (Not(s1), Not(s2)) => s1 == s2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Subscription {}
+
+impl Not for Subscription {
+    type Output = Subscription;
+
+    fn not(self) -> Subscription {
+        Subscription::Not(Box::new(self))
+    }
+}
+
+/// Constraint of stored events that can be looked up by index of database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Indexed {
+    /// Event is by one of the authors.
+    Authors(Vec<XOnlyPublicKey>),
+    Id(EventId),
+    /// Event is the event or refers to it by `e` tag.
+    Thread(EventId),
+    /// Event has the hashtag (`t` tag), compared in lowercase.
+    Hashtag(String),
+    Since(Timestamp),
+    Until(Timestamp),
+}
+
+/// Literal of a subscription in disjunctive normal form: whether it is negated
+/// and key of the leaf.
+type Literal = (bool, u8, String);
+
+/// Constraints of a single filter, obtained from a conjunction of leaves.
+/// Sets are alternatives, `None` means unconstrained.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Conjunction {
+    hashtags: Option<BTreeSet<String>>,
+    ids: Option<BTreeSet<String>>,
+    events: Option<BTreeSet<String>>,
+    pubkeys: Option<BTreeSet<String>>,
+    authors: Option<BTreeSet<String>>,
+    kinds: Option<BTreeSet<u64>>,
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+    /// Words all of which have to be found (NIP-50).
+    search: Option<String>,
 }
 
 impl Subscription {
@@ -23,6 +155,17 @@ impl Subscription {
         Subscription::Hashtag(tag.into())
     }
 
+    /// Creates new search subscription for notes containing all words
+    /// of `text`. Quotes are left out, they cannot be searched for.
+    pub fn search<S: AsRef<str>>(text: S) -> Subscription {
+        let words = text
+            .as_ref()
+            .split(|c: char| c.is_whitespace() || c == '"')
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>();
+        Subscription::Search(words.join(" "))
+    }
+
     /// Creates new subscription for a pubkey.
     pub fn profile(pubkey: XOnlyPublicKey, relays: Vec<Url>) -> Subscription {
         Subscription::Profile(pubkey, relays)
@@ -37,179 +180,678 @@ impl Subscription {
         )
     }
 
-    /// Determines whether this subscription is exclusively subscribed to a single profile.
-    pub fn is_a_profile(&self) -> bool {
-        matches!(self, Subscription::Profile(..))
+    /// Replaces follow lists in this subscription with `follow`.
+    pub fn with_follow(self, follow: &Follow) -> Subscription {
+        match self {
+            Subscription::Following(_) => Subscription::Following(follow.clone()),
+            Subscription::Or(s1, s2) => s1.with_follow(follow).add(s2.with_follow(follow)),
+            Subscription::And(s1, s2) => s1.with_follow(follow).and(s2.with_follow(follow)),
+            Subscription::Not(s) => !s.with_follow(follow),
+            s => s,
+        }
     }
 
-    /// Collects all events from this subscription.
-    pub fn events(&self) -> HashSet<EventId> {
-        let mut ids: HashSet<EventId> = Default::default();
-
+    /// Determines whether this subscription contains notes of followed authors.
+    pub fn is_following(&self) -> bool {
         match self {
-            Subscription::Sink => {}
-            Subscription::Hashtag(..) => {}
-            Subscription::Or(s1, s2) => s1.events().union(&s2.events()).for_each(|t| {
-                ids.insert(*t);
-            }),
-            Subscription::Profile(..) => {}
-            Subscription::Id(..) => {}
-            Subscription::Event(id) => {
-                ids.insert(*id);
+            Subscription::Following(_) => true,
+            Subscription::Or(s1, s2) | Subscription::And(s1, s2) => {
+                s1.is_following() || s2.is_following()
             }
+            Subscription::Not(s) => s.is_following(),
+            _ => false,
         }
+    }
 
-        ids
+    /// Determines whether this subscription is exclusively subscribed to a single profile.
+    pub fn is_a_profile(&self) -> bool {
+        matches!(self, Subscription::Profile(..))
     }
 
     /// Collects all events from this subscription.
-    pub fn ids(&self) -> HashSet<EventId> {
-        let mut ids: HashSet<EventId> = Default::default();
-
-        match self {
-            Subscription::Sink => {}
-            Subscription::Hashtag(..) => {}
-            Subscription::Or(s1, s2) => s1.events().union(&s2.events()).for_each(|t| {
-                ids.insert(*t);
-            }),
-            Subscription::Profile(..) => {}
-            Subscription::Event(..) => {}
-            Subscription::Id(id) => {
-                ids.insert(*id);
-            }
-        }
+    pub fn events(&self) -> HashSet<EventId> {
+        self.collect(&|s| match s {
+            Subscription::Event(id) => Some(*id),
+            _ => None,
+        })
+    }
 
-        ids
+    /// Collects all event IDs from this subscription.
+    pub fn ids(&self) -> HashSet<EventId> {
+        self.collect(&|s| match s {
+            Subscription::Id(id) => Some(*id),
+            _ => None,
+        })
     }
 
     /// Collects all hashtags from this subscription.
     pub fn hashtags(&self) -> HashSet<&str> {
-        let mut tags: HashSet<&str> = Default::default();
+        self.collect(&|s| match s {
+            Subscription::Hashtag(t) => Some(t.as_str()),
+            _ => None,
+        })
+    }
 
+    /// Search text that every event accepted by this subscription has to
+    /// match, so that candidates can be looked up in the full-text index.
+    pub fn required_search(&self) -> Option<String> {
         match self {
-            Subscription::Sink => {}
-            Subscription::Hashtag(t) => {
-                tags.insert(t);
-            }
-            Subscription::Or(s1, s2) => s1.hashtags().union(&s2.hashtags()).for_each(|t| {
-                tags.insert(t);
-            }),
-            Subscription::Profile(..) => {}
-            Subscription::Event(..) => {}
-            Subscription::Id(..) => {}
+            Subscription::Search(text) => Some(text.clone()),
+            Subscription::And(s1, s2) => match (s1.required_search(), s2.required_search()) {
+                (Some(t1), Some(t2)) => Some(format!("{t1} {t2}")),
+                (t1, t2) => t1.or(t2),
+            },
+            _ => None,
         }
-
-        tags
     }
 
     /// Collects all pubkeys from this subscription.
     pub fn pubkeys(&self) -> HashSet<XOnlyPublicKey> {
-        let mut pubkeys: HashSet<XOnlyPublicKey> = Default::default();
+        self.collect(&|s| match s {
+            Subscription::Profile(p, _) => Some(*p),
+            _ => None,
+        })
+    }
 
-        match self {
-            Subscription::Sink => {}
-            Subscription::Profile(p, _) => {
-                pubkeys.insert(*p);
-            }
-            Subscription::Or(s1, s2) => s1.pubkeys().union(&s2.pubkeys()).for_each(|p| {
-                pubkeys.insert(*p);
-            }),
+    /// Collects all authors, including followed ones, from this subscription.
+    pub fn authors(&self) -> HashSet<XOnlyPublicKey> {
+        let mut authors = self.collect(&|s| match s {
+            Subscription::Author(p) => Some(*p),
+            _ => None,
+        });
 
-            Subscription::Hashtag(_) => {}
-            Subscription::Event(..) => {}
-            Subscription::Id(..) => {}
+        for f in self.collect(&|s| match s {
+            Subscription::Following(f) => Some(f.pubkeys().copied().collect::<Vec<_>>()),
+            _ => None,
+        }) {
+            authors.extend(f);
         }
 
-        pubkeys
+        authors
+    }
+
+    /// Collects values of leaves selected by `f`. Negated parts are skipped,
+    /// they do not add anything to what is shown.
+    fn collect<'a, T, F>(&'a self, f: &F) -> HashSet<T>
+    where
+        T: Eq + std::hash::Hash,
+        F: Fn(&'a Subscription) -> Option<T>,
+    {
+        match self {
+            Subscription::Or(s1, s2) | Subscription::And(s1, s2) => {
+                let mut values = s1.collect(f);
+                values.extend(s2.collect(f));
+                values
+            }
+            Subscription::Not(_) => Default::default(),
+            s => f(s).into_iter().collect(),
+        }
     }
 
     pub fn from_sdk(subscription: &ActiveSubscription) -> Option<Subscription> {
         Subscription::from_filters(&subscription.filters())
     }
 
+    /// Reconstructs subscription from `filters`, inverse of [`Subscription::to_filters`].
+    /// Relay hints of profiles are not part of filters, hence they are lost. Follow
+    /// lists come back as individual authors and relative times as absolute.
     pub fn from_filters(filters: &[Filter]) -> Option<Subscription> {
-        todo!()
+        filters
+            .iter()
+            .map(Subscription::from_filter)
+            .reduce(Subscription::add)
+    }
+
+    /// Reconstructs subscription from a single `filter`. Filter that does not
+    /// constrain anything known selects everything, i. e. it is a sink.
+    fn from_filter(filter: &Filter) -> Subscription {
+        let hashtags = filter
+            .hashtags
+            .iter()
+            .flatten()
+            .map(|t| Subscription::hashtag(t.to_string()))
+            .reduce(Subscription::add);
+
+        let ids = filter
+            .ids
+            .iter()
+            .flatten()
+            .filter_map(|id| EventId::from_hex(id.to_string()).ok())
+            .map(Subscription::Id)
+            .reduce(Subscription::add);
+
+        let events = filter
+            .events
+            .iter()
+            .flatten()
+            .filter_map(|id| EventId::from_hex(id.to_string()).ok())
+            .map(Subscription::Event)
+            .reduce(Subscription::add);
+
+        let pubkeys = filter
+            .pubkeys
+            .iter()
+            .flatten()
+            .filter_map(|pk| XOnlyPublicKey::from_str(&pk.to_string()).ok())
+            .map(|pk| Subscription::profile(pk, vec![]))
+            .reduce(Subscription::add);
+
+        let authors = filter
+            .authors
+            .iter()
+            .flatten()
+            .filter_map(|pk| XOnlyPublicKey::from_str(&pk.to_string()).ok())
+            .map(Subscription::Author)
+            .reduce(Subscription::add);
+
+        let kinds = filter
+            .kinds
+            .as_ref()
+            .map(|ks| Subscription::Kinds(ks.iter().cloned().collect()));
+
+        let since = filter.since.map(|t| Subscription::Since(Time::At(t)));
+        let until = filter.until.map(|t| Subscription::Until(Time::At(t)));
+        let search = filter.search.as_ref().map(Subscription::search);
+
+        [
+            hashtags, ids, events, pubkeys, authors, kinds, since, until, search,
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(Subscription::and)
+        .unwrap_or(Subscription::Sink)
     }
 
     pub fn add(self, other: Subscription) -> Subscription {
         Subscription::Or(Box::new(self), Box::new(other))
     }
 
-    // TODO: After introducing Subscription::Add, this has to be modified.
-    // TODO: At some point, since Timestamp Now has to be removed.
-    pub fn to_filters(&self) -> Vec<Filter> {
-        let mut filters = Vec::new();
+    pub fn and(self, other: Subscription) -> Subscription {
+        Subscription::And(Box::new(self), Box::new(other))
+    }
+
+    /// Compiles this subscription into filters for relays, each asking for at
+    /// most `limit` of the most recent events, except threads and individual
+    /// events, which are asked for in full. Filters select at least the events
+    /// this subscription accepts, possibly more, as not everything can be
+    /// expressed by filters (e. g. negation, or two different hashtags at once).
+    /// Alternatives that only exclude events are left out, relays would send
+    /// everything else (see [`Subscription::has_positive_terms`]).
+    pub fn to_filters(&self, limit: usize) -> Vec<Filter> {
+        let mut conjunctions: Vec<Conjunction> = Vec::new();
 
-        let hashtags = self.hashtags().into_iter().collect::<Vec<_>>();
-        if !hashtags.is_empty() {
-            filters.push(Filter::new().hashtags(hashtags).since(Timestamp::now()));
+        for conj in self.dnf() {
+            if conj.iter().all(|(negated, _)| *negated) {
+                continue;
+            }
+            let c = Conjunction::of(&conj);
+            if !c.is_empty() && !conjunctions.contains(&c) {
+                conjunctions.push(c);
+            }
         }
 
-        let events = self.events().into_iter().collect::<Vec<_>>();
-        if !events.is_empty() {
-            filters.push(Filter::new().events(events));
+        // Conjunctions that differ only in alternatives of one constraint
+        // can be asked for by one filter.
+        let mut merged: Vec<Conjunction> = Vec::new();
+        for c in conjunctions {
+            if let Some(m) = merged.iter_mut().find(|m| m.can_merge(&c)) {
+                m.merge(c);
+            } else {
+                merged.push(c);
+            }
         }
 
-        let ids = self.ids().into_iter().collect::<Vec<_>>();
-        if !ids.is_empty() {
-            filters.push(Filter::new().ids(ids));
+        merged.iter().flat_map(|c| c.to_filters(limit)).collect()
+    }
+
+    /// Filters asking for at most `limit` events per filter created
+    /// before `until`, to load history of the subscription.
+    pub fn to_filters_until(&self, until: Timestamp, limit: usize) -> Vec<Filter> {
+        self.clone()
+            .and(Subscription::Until(Time::At(until)))
+            .to_filters(limit)
+            .into_iter()
+            .map(|f| f.limit(limit))
+            .collect()
+    }
+
+    /// Determines whether each alternative of this subscription selects some
+    /// events rather than only excludes them, e. g. `!#nostr` does not. Relays
+    /// cannot be asked for subscriptions that do not.
+    pub fn has_positive_terms(&self) -> bool {
+        self.has_positive_terms_with(false)
+    }
+
+    fn has_positive_terms_with(&self, negated: bool) -> bool {
+        match self {
+            Subscription::Not(s) => s.has_positive_terms_with(!negated),
+            // Negated conjunction is a disjunction of negations and vice versa.
+            Subscription::And(s1, s2) if !negated => {
+                s1.has_positive_terms_with(negated) || s2.has_positive_terms_with(negated)
+            }
+            Subscription::Or(s1, s2) if negated => {
+                s1.has_positive_terms_with(negated) || s2.has_positive_terms_with(negated)
+            }
+            Subscription::And(s1, s2) | Subscription::Or(s1, s2) => {
+                s1.has_positive_terms_with(negated) && s2.has_positive_terms_with(negated)
+            }
+            _ => !negated,
         }
+    }
+
+    /// Constraints of stored events this subscription can accept, so that
+    /// candidates can be looked up by index. Event has to satisfy all constraints
+    /// of at least one of the alternatives. Constraints are necessary, not
+    /// sufficient, the candidates still need to be [accepted](Subscription::accepts).
+    /// Returns `None` if some events can be accepted regardless of indexes.
+    pub fn indexed(&self) -> Option<Vec<Vec<Indexed>>> {
+        self.dnf()
+            .into_iter()
+            .map(|conj| {
+                let constraints = conj
+                    .into_iter()
+                    .filter(|(negated, _)| !negated)
+                    .filter_map(|(_, leaf)| match leaf {
+                        Subscription::Following(f) => {
+                            Some(Indexed::Authors(f.pubkeys().copied().collect()))
+                        }
+                        Subscription::Profile(pk, _) | Subscription::Author(pk) => {
+                            Some(Indexed::Authors(vec![pk]))
+                        }
+                        Subscription::Id(id) => Some(Indexed::Id(id)),
+                        Subscription::Event(id) => Some(Indexed::Thread(id)),
+                        // Database lowercases only ASCII letters.
+                        Subscription::Hashtag(t) if t.is_ascii() => {
+                            Some(Indexed::Hashtag(t.to_lowercase()))
+                        }
+                        Subscription::Since(t) => Some(Indexed::Since(t.resolve())),
+                        Subscription::Until(t) => Some(Indexed::Until(t.resolve())),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                (!constraints.is_empty()).then_some(constraints)
+            })
+            .collect()
+    }
+
+    /// Converts this subscription into disjunctive normal form, a list of
+    /// conjunctions of leaves, each leaf possibly negated.
+    fn dnf(&self) -> Vec<Vec<(bool, Subscription)>> {
+        self.dnf_with(false)
+    }
+
+    fn dnf_with(&self, negated: bool) -> Vec<Vec<(bool, Subscription)>> {
+        let sum = |s1: &Subscription, s2: &Subscription| {
+            let mut d = s1.dnf_with(negated);
+            d.extend(s2.dnf_with(negated));
+            d
+        };
 
-        let pubkeys = self.pubkeys().into_iter().collect::<Vec<_>>();
-        if !pubkeys.is_empty() {
-            filters.push(Filter::new().pubkeys(pubkeys).since(Timestamp::now()));
+        let product = |s1: &Subscription, s2: &Subscription| {
+            let d2 = s2.dnf_with(negated);
+            s1.dnf_with(negated)
+                .into_iter()
+                .flat_map(|c1| {
+                    d2.iter().map(move |c2| {
+                        let mut c = c1.clone();
+                        c.extend(c2.iter().cloned());
+                        c
+                    })
+                })
+                .collect()
+        };
+
+        match (self, negated) {
+            (Subscription::Or(s1, s2), false) | (Subscription::And(s1, s2), true) => sum(s1, s2),
+            (Subscription::And(s1, s2), false) | (Subscription::Or(s1, s2), true) => {
+                product(s1, s2)
+            }
+            (Subscription::Not(s), _) => s.dnf_with(!negated),
+            (s, _) => vec![vec![(negated, s.clone())]],
         }
+    }
+
+    /// Canonical form of this subscription, a set of satisfiable
+    /// conjunctions of literals. Leaves with several alternatives are split
+    /// into single ones.
+    fn canonical(&self) -> BTreeSet<BTreeSet<Literal>> {
+        self.expand()
+            .dnf()
+            .into_iter()
+            .map(|c| {
+                c.into_iter()
+                    .filter(|(n, l)| *n || !matches!(l, Subscription::Sink))
+                    .map(|(n, l)| {
+                        let (rank, key) = l.leaf_key();
+                        (n, rank, key)
+                    })
+                    .collect::<BTreeSet<_>>()
+            })
+            .filter(satisfiable)
+            .collect()
+    }
 
-        // TODO: When Sink lane is removed, this can be removed, too.
-        filters.push(Filter::new().since(Timestamp::now()));
+    /// Replaces leaves with several alternatives by disjunction of single ones.
+    /// Leaves with no alternatives select nothing.
+    fn expand(&self) -> Subscription {
+        let any = |leaves: Vec<Subscription>| {
+            leaves
+                .into_iter()
+                .reduce(Subscription::add)
+                .unwrap_or(!Subscription::Sink)
+        };
 
-        filters
+        match self {
+            Subscription::Following(f) => {
+                any(f.pubkeys().map(|pk| Subscription::Author(*pk)).collect())
+            }
+            Subscription::Kinds(ks) => {
+                any(ks.iter().map(|k| Subscription::Kinds(vec![*k])).collect())
+            }
+            Subscription::Or(s1, s2) => s1.expand().add(s2.expand()),
+            Subscription::And(s1, s2) => s1.expand().and(s2.expand()),
+            Subscription::Not(s) => !s.expand(),
+            s => s.clone(),
+        }
+    }
+
+    /// Key distinguishing leaves of subscriptions.
+    fn leaf_key(&self) -> (u8, String) {
+        match self {
+            Subscription::Sink => (0, String::new()),
+            Subscription::Following(_) => (1, self.to_string()),
+            Subscription::Hashtag(t) => (2, t.clone()),
+            Subscription::Profile(pk, _) => (3, pk.to_string()),
+            Subscription::Id(id) => (4, id.to_hex()),
+            Subscription::Event(id) => (5, id.to_hex()),
+            Subscription::Kinds(ks) => {
+                let ks = ks.iter().map(|k| k.as_u64()).collect::<BTreeSet<_>>();
+                (6, format!("{ks:?}"))
+            }
+            Subscription::Since(t) => (7, t.to_string()),
+            Subscription::Until(t) => (8, t.to_string()),
+            Subscription::Author(pk) => (9, pk.to_string()),
+            Subscription::Search(t) => (10, t.to_lowercase()),
+            Subscription::And(..) | Subscription::Or(..) | Subscription::Not(..) => {
+                (11, self.to_string())
+            }
+        }
     }
 
     pub fn to_string(&self) -> String {
+        self.to_string_prec(0)
+    }
+
+    /// Renders the subscription, parenthesized if the surrounding operator
+    /// binds tighter than this one (0: `+`, 1: `&`, 2: `!`).
+    fn to_string_prec(&self, prec: u8) -> String {
+        let paren = |p: u8, s: String| if prec > p { format!("({s})") } else { s };
+
         match self {
-            Subscription::Sink => "Sink".to_string(),
+            Subscription::Sink => "sink".to_string(),
+            Subscription::Following(_) => "following".to_string(),
             Subscription::Hashtag(t) => format!("#{t}"),
-            // Subscriptions::And(s1, s2) => format!("{} & {}", s1.to_string(), s2.to_string()),
-            Subscription::Or(s1, s2) => format!("{} + {}", s1.to_string(), s2.to_string()),
+            Subscription::Or(s1, s2) => paren(
+                0,
+                format!("{} + {}", s1.to_string_prec(0), s2.to_string_prec(0)),
+            ),
+            Subscription::And(s1, s2) => paren(
+                1,
+                format!("{} & {}", s1.to_string_prec(1), s2.to_string_prec(1)),
+            ),
+            Subscription::Not(s) => format!("!{}", s.to_string_prec(2)),
             Subscription::Profile(p, _) => format!("@{p}"),
-            Subscription::Event(event) => event.to_string(),
-            Subscription::Id(event) => event.to_string(),
+            Subscription::Event(event) => format!("e:{}", event.to_hex()),
+            Subscription::Id(event) => format!("id:{}", event.to_hex()),
+            Subscription::Kinds(ks) => format!(
+                "kind:{}",
+                ks.iter()
+                    .map(|k| k.as_u64().to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            Subscription::Since(t) => format!("since:{t}"),
+            Subscription::Until(t) => format!("until:{t}"),
+            Subscription::Author(p) => format!("author:{p}"),
+            Subscription::Search(t) => format!("search:\"{t}\""),
         }
     }
 
     /// Determines whether the incoming `event` is going to be placed in this lane.
-    /// Gradually, it will cover all cases and at the end will replace lane kind.
     pub fn accepts(&self, event: &Event) -> bool {
-        let tags = self
-            .hashtags()
-            .iter()
-            .map(|t| t.to_lowercase())
-            .collect::<HashSet<_>>();
+        match self {
+            Subscription::Sink => true,
+            Subscription::Following(f) => f.follows(&event.pubkey),
+            // TODO: could also consider content of the text note, not only event.tags.
+            Subscription::Hashtag(tag) => event
+                .tags
+                .iter()
+                .any(|t| matches!(t, Tag::Hashtag(h) if h.to_lowercase() == tag.to_lowercase())),
+            Subscription::Profile(pk, _) | Subscription::Author(pk) => event.pubkey == *pk,
+            Subscription::Id(id) => event.id == *id,
+            Subscription::Event(id) => {
+                event.id == *id
+                    || event.replies_to() == Some(*id)
+                    || matches!(event.thread_root(), Some((i, _)) if i == *id)
+            }
+            Subscription::Kinds(ks) => ks.contains(&event.kind),
+            Subscription::Search(text) => {
+                let content = event.content.to_lowercase();
+                text.split_whitespace()
+                    .all(|w| content.contains(&w.to_lowercase()))
+            }
+            Subscription::Since(t) => event.created_at >= t.resolve(),
+            Subscription::Until(t) => event.created_at <= t.resolve(),
+            Subscription::And(s1, s2) => s1.accepts(event) && s2.accepts(event),
+            Subscription::Or(s1, s2) => s1.accepts(event) || s2.accepts(event),
+            Subscription::Not(s) => !s.accepts(event),
+        }
+    }
+}
 
-        // TODO: could also consider content of the text note, not only event.tags.
-        let accepts_tags = event
-            .tags
-            .iter()
-            .any(|t| matches!(t, Tag::Hashtag(h) if tags.contains(h.to_lowercase().as_str())));
+/// Determines whether some event can satisfy conjunction of literals. An event
+/// cannot be selected by nothing, nor can it have two IDs, authors or kinds.
+fn satisfiable(conj: &BTreeSet<Literal>) -> bool {
+    let positive = |rank: u8| conj.iter().filter(|(n, r, _)| !n && *r == rank).count();
 
-        let accept_pubkeys = self.pubkeys().contains(&event.pubkey);
+    !conj.contains(&(true, 0, String::new())) && [4, 6, 9].into_iter().all(|r| positive(r) <= 1)
+}
 
-        let accept_event_ids = self.events().iter().any(|id| {
-            event.id == *id
-                || event.replies_to() == Some(*id)
-                || matches!(event.thread_root(), Some((i, _)) if i == *id)
-        });
+impl Conjunction {
+    /// Collects constraints of conjunction of positive leaves.
+    fn of(leaves: &[(bool, Subscription)]) -> Conjunction {
+        let mut c = Conjunction::default();
+
+        for (_, leaf) in leaves {
+            match leaf {
+                // A filter can require only one hashtag, event or pubkey reference.
+                // Keeping the first one selects more than necessary, which is fine.
+                Subscription::Hashtag(t) => keep_first(&mut c.hashtags, t.clone()),
+                Subscription::Event(id) => keep_first(&mut c.events, id.to_hex()),
+                Subscription::Profile(pk, _) => keep_first(&mut c.pubkeys, pk.to_string()),
+                // Events have one ID, author and kind, so these intersect.
+                Subscription::Id(id) => intersect(&mut c.ids, [id.to_hex()]),
+                Subscription::Author(pk) => intersect(&mut c.authors, [pk.to_string()]),
+                Subscription::Following(f) => {
+                    intersect(&mut c.authors, f.pubkeys().map(|pk| pk.to_string()))
+                }
+                Subscription::Kinds(ks) => intersect(&mut c.kinds, ks.iter().map(|k| k.as_u64())),
+                Subscription::Since(t) => c.since = c.since.max(Some(t.resolve())),
+                Subscription::Until(t) => {
+                    let t = t.resolve();
+                    c.until = Some(c.until.map_or(t, |u| u.min(t)));
+                }
+                Subscription::Search(t) => {
+                    c.search = Some(match c.search.take() {
+                        Some(s) => format!("{s} {t}"),
+                        None => t.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
 
-        matches!(self, Subscription::Sink) || accepts_tags || accept_pubkeys || accept_event_ids
+        c
     }
+
+    /// Determines whether no event can satisfy the constraints.
+    fn is_empty(&self) -> bool {
+        [
+            self.ids.as_ref().map(BTreeSet::len),
+            self.authors.as_ref().map(BTreeSet::len),
+            self.kinds.as_ref().map(BTreeSet::len),
+        ]
+        .contains(&Some(0))
+            || matches!((self.since, self.until), (Some(s), Some(u)) if s > u)
+    }
+
+    /// Determines whether `other` differs from this only in alternatives
+    /// of one constraint, so that the two can be asked for by one filter.
+    fn can_merge(&self, other: &Conjunction) -> bool {
+        self.since == other.since
+            && self.until == other.until
+            && self.search == other.search
+            && self.shape() == other.shape()
+            && [
+                self.hashtags != other.hashtags,
+                self.ids != other.ids,
+                self.events != other.events,
+                self.pubkeys != other.pubkeys,
+                self.authors != other.authors,
+                self.kinds != other.kinds,
+            ]
+            .into_iter()
+            .filter(|d| *d)
+            .count()
+                <= 1
+    }
+
+    fn merge(&mut self, other: Conjunction) {
+        fn union<T: Ord>(a: &mut Option<BTreeSet<T>>, b: Option<BTreeSet<T>>) {
+            if let (Some(a), Some(b)) = (a.as_mut(), b) {
+                a.extend(b);
+            }
+        }
+
+        union(&mut self.hashtags, other.hashtags);
+        union(&mut self.ids, other.ids);
+        union(&mut self.events, other.events);
+        union(&mut self.pubkeys, other.pubkeys);
+        union(&mut self.authors, other.authors);
+        union(&mut self.kinds, other.kinds);
+    }
+
+    /// Which constraints are present.
+    fn shape(&self) -> [bool; 9] {
+        [
+            self.hashtags.is_some(),
+            self.ids.is_some(),
+            self.events.is_some(),
+            self.pubkeys.is_some(),
+            self.authors.is_some(),
+            self.kinds.is_some(),
+            self.since.is_some(),
+            self.until.is_some(),
+            self.search.is_some(),
+        ]
+    }
+
+    /// Makes filters of the constraints, asking for at most `limit` events
+    /// unless specific events are asked for. There may be more of them
+    /// if there are too many authors.
+    fn to_filters(&self, limit: usize) -> Vec<Filter> {
+        let mut filter = Filter::new();
+
+        if let Some(hashtags) = &self.hashtags {
+            filter = filter.hashtags(hashtags.iter().cloned().collect());
+        }
+
+        if let Some(ids) = &self.ids {
+            filter = filter.ids(
+                ids.iter()
+                    .filter_map(|id| EventId::from_hex(id).ok())
+                    .collect(),
+            );
+        }
+
+        if let Some(events) = &self.events {
+            filter = filter.events(
+                events
+                    .iter()
+                    .filter_map(|id| EventId::from_hex(id).ok())
+                    .collect(),
+            );
+        }
+
+        if let Some(pubkeys) = &self.pubkeys {
+            filter = filter.pubkeys(
+                pubkeys
+                    .iter()
+                    .filter_map(|pk| XOnlyPublicKey::from_str(pk).ok())
+                    .collect(),
+            );
+        }
+
+        if let Some(kinds) = &self.kinds {
+            filter = filter.kinds(kinds.iter().map(|k| Kind::from(*k)).collect());
+        }
+
+        if let Some(since) = self.since {
+            filter = filter.since(since);
+        }
+
+        if let Some(until) = self.until {
+            filter = filter.until(until);
+        }
+
+        if let Some(search) = &self.search {
+            filter = filter.search(search);
+        }
+
+        if self.ids.is_none() && self.events.is_none() {
+            filter = filter.limit(limit);
+        }
+
+        match &self.authors {
+            Some(authors) => authors
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .chunks(AUTHORS_PER_FILTER)
+                .map(|authors| filter.clone().authors(authors.to_vec()))
+                .collect(),
+            None => vec![filter],
+        }
+    }
+}
+
+/// Adds `value` as the only alternative unless there already are some.
+fn keep_first<T: Ord>(set: &mut Option<BTreeSet<T>>, value: T) {
+    set.get_or_insert_with(|| BTreeSet::from([value]));
+}
+
+/// Narrows alternatives to those in `values`.
+fn intersect<T: Ord>(set: &mut Option<BTreeSet<T>>, values: impl IntoIterator<Item = T>) {
+    let values = values.into_iter().collect::<BTreeSet<_>>();
+    *set = Some(match set.take() {
+        Some(s) => s.into_iter().filter(|v| values.contains(v)).collect(),
+        None => values,
+    });
 }
 
 #[cfg(test)]
 mod tests {
-    use nostr_sdk::Filter;
+    use std::collections::HashSet;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use nostr_sdk::prelude::{EventBuilder, Keys, Kind, SecretKey, Tag, XOnlyPublicKey};
+    use nostr_sdk::{EventId, Timestamp};
+    use proptest::prelude::*;
 
-    use super::Subscription;
+    use super::{Indexed, Subscription, Time};
+    use crate::follow::Follow;
 
     #[test]
     fn hashtags_to_filter() {
@@ -221,11 +863,285 @@ mod tests {
             Box::new(Subscription::Hashtag("one".to_string())),
         );
 
-        let f = s.to_filters();
+        let f = s.to_filters(50);
 
+        assert_eq!(f.len(), 1);
         assert_eq!(
-            f,
-            Filter::new().hashtags(vec!["one".to_string(), "two".to_string()])
+            f[0].hashtags
+                .iter()
+                .flatten()
+                .cloned()
+                .collect::<HashSet<_>>(),
+            HashSet::from(["one".to_string(), "two".to_string()])
         );
     }
+
+    #[test]
+    fn following_accepts_followed_authors() {
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+        let s = Subscription::Following(Follow::new().follow(alice.public_key()));
+
+        let note = |keys: &Keys| {
+            EventBuilder::new_text_note("Hello", &[])
+                .to_event(keys)
+                .unwrap()
+        };
+
+        assert!(s.accepts(&note(&alice)));
+        assert!(!s.accepts(&note(&bob)));
+        assert!(!s.to_filters(50).is_empty());
+    }
+
+    #[test]
+    fn following_batched() {
+        let follow = (0..600).map(|_| Keys::generate().public_key()).collect();
+
+        assert_eq!(Subscription::Following(follow).to_filters(50).len(), 3);
+    }
+
+    #[test]
+    fn filters_to_thread() {
+        let id =
+            EventId::from_hex("b10b0d5e5fae7e63b6e41e0a4a5e3e2d4e0b6d4fa4ff4b4b9e5d1a3dd1f6d4a2")
+                .unwrap();
+        let s = Subscription::thread(id);
+
+        assert_eq!(Subscription::from_filters(&s.to_filters(50)), Some(s));
+    }
+
+    #[test]
+    fn lossy_filters_select_more() {
+        let s = Subscription::hashtag("a").and(Subscription::hashtag("b"));
+        let r = Subscription::from_filters(&s.to_filters(50)).unwrap();
+
+        assert_eq!(r, Subscription::hashtag("a"));
+        assert!(selects_at_least(Some(&r), &s));
+        assert!(!selects_at_least(Some(&s), &r));
+    }
+
+    #[test]
+    fn search_accepts_all_words() {
+        let s = Subscription::search("Nostr \"client\"").and(Subscription::hashtag("gtk"));
+
+        let note = |content: &str| {
+            EventBuilder::new_text_note(content, &[Tag::Hashtag("gtk".to_string())])
+                .to_event(&Keys::generate())
+                .unwrap()
+        };
+
+        assert!(s.accepts(&note("A native nostr CLIENT")));
+        assert!(!s.accepts(&note("A native nostr relay")));
+        assert_eq!(s.required_search().as_deref(), Some("Nostr client"));
+        assert_eq!(s.to_filters(50)[0].search.as_deref(), Some("Nostr client"));
+        assert_eq!(Subscription::search("a").add(s).required_search(), None);
+    }
+
+    #[test]
+    fn combined_accepts() {
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+
+        // #bitcoin from people I follow, text notes only, last 24h, but no #nsfw.
+        let s = Subscription::hashtag("bitcoin")
+            .and(Subscription::Following(
+                Follow::new().follow(alice.public_key()),
+            ))
+            .and(Subscription::Kinds(vec![Kind::TextNote]))
+            .and(Subscription::Since(Time::Ago(Duration::from_secs(86400))))
+            .and(!Subscription::hashtag("nsfw"));
+
+        let note = |keys: &Keys, tags: &[&str]| {
+            let tags = tags
+                .iter()
+                .map(|t| Tag::Hashtag(t.to_string()))
+                .collect::<Vec<_>>();
+            EventBuilder::new_text_note("Hello", &tags)
+                .to_event(keys)
+                .unwrap()
+        };
+
+        assert!(s.accepts(&note(&alice, &["bitcoin"])));
+        assert!(!s.accepts(&note(&alice, &["bitcoin", "nsfw"])));
+        assert!(!s.accepts(&note(&alice, &["nostr"])));
+        assert!(!s.accepts(&note(&bob, &["bitcoin"])));
+
+        let f = s.to_filters(50);
+        assert_eq!(f.len(), 1);
+        assert!(f[0].since.is_some());
+    }
+
+    #[test]
+    fn historic_hashtag() {
+        let f = Subscription::hashtag("nostr").to_filters(50);
+
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].since, None);
+    }
+
+    #[test]
+    fn history_pages() {
+        let older = Timestamp::from(1_000);
+        let newer = Timestamp::from(2_000);
+        let s = Subscription::hashtag("nostr").and(Subscription::Until(Time::At(older)));
+
+        let f = s.to_filters_until(newer, 5);
+
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].until, Some(older));
+        assert_eq!(f[0].limit, Some(5));
+    }
+
+    #[test]
+    fn indexed_constraints() {
+        let alice = Keys::generate().public_key();
+        let s = Subscription::hashtag("Nostr")
+            .and(!Subscription::Author(alice))
+            .add(Subscription::Author(alice).and(Subscription::Kinds(vec![Kind::TextNote])));
+
+        assert_eq!(
+            s.indexed(),
+            Some(vec![
+                vec![Indexed::Hashtag("nostr".to_string())],
+                vec![Indexed::Authors(vec![alice])],
+            ])
+        );
+
+        // Notes without the hashtag can be accepted too.
+        assert_eq!(s.add(!Subscription::hashtag("nostr")).indexed(), None);
+        assert_eq!(Subscription::hashtag("čeština").indexed(), None);
+    }
+
+    #[test]
+    fn negation_alone_not_asked_for() {
+        let nsfw = Subscription::hashtag("nsfw");
+        let alice = Subscription::Author(Keys::generate().public_key());
+
+        assert!(!(!nsfw.clone()).has_positive_terms());
+        assert!(!alice.clone().add(!nsfw.clone()).has_positive_terms());
+        assert!(!(!alice.clone().and(nsfw.clone())).has_positive_terms());
+        assert!(alice.clone().and(!nsfw.clone()).has_positive_terms());
+        assert!((!(!nsfw.clone())).has_positive_terms());
+
+        assert!((!nsfw.clone()).to_filters(50).is_empty());
+        let f = alice.add(!nsfw).to_filters(50);
+        assert_eq!(f.len(), 1);
+        assert!(f[0].authors.is_some());
+        assert_eq!(f[0].limit, Some(50));
+    }
+
+    #[test]
+    fn render_precedence() {
+        let s = Subscription::hashtag("a")
+            .add(Subscription::hashtag("b"))
+            .and(!Subscription::hashtag("c"));
+
+        assert_eq!(s.to_string(), "(#a + #b) & !#c");
+    }
+
+    fn pubkey() -> impl Strategy<Value = XOnlyPublicKey> {
+        "[0-9a-f]{64}"
+            .prop_filter_map("invalid secret key", |h| SecretKey::from_str(&h).ok())
+            .prop_map(|sk| Keys::new(sk).public_key())
+    }
+
+    fn event_id() -> impl Strategy<Value = EventId> {
+        "[0-9a-f]{64}".prop_map(|h| EventId::from_hex(h).unwrap())
+    }
+
+    fn leaf() -> impl Strategy<Value = Subscription> {
+        prop_oneof![
+            Just(Subscription::Sink),
+            "[a-z0-9]{1,12}".prop_map(Subscription::hashtag),
+            pubkey().prop_map(|pk| Subscription::profile(pk, vec![])),
+            event_id().prop_map(Subscription::thread),
+            event_id().prop_map(Subscription::Id),
+            event_id().prop_map(Subscription::Event),
+            prop::collection::vec(pubkey(), 1..5)
+                .prop_map(|pks| Subscription::Following(pks.into_iter().collect())),
+        ]
+    }
+
+    /// Leaf with additional constraints, such that a single filter can express it.
+    fn constrained() -> impl Strategy<Value = Subscription> {
+        let author = pubkey().prop_map(Subscription::Author);
+        let kinds = prop::collection::vec(0..10000u64, 1..3)
+            .prop_map(|ks| Subscription::Kinds(ks.into_iter().map(Kind::from).collect()));
+        let since =
+            (0..1_000_000_000u64).prop_map(|t| Subscription::Since(Time::At(Timestamp::from(t))));
+        let until = (1_000_000_000..2_000_000_000u64)
+            .prop_map(|t| Subscription::Until(Time::At(Timestamp::from(t))));
+        let search = "[a-z]{1,8}( [a-z]{1,8})?".prop_map(Subscription::search);
+
+        (
+            leaf(),
+            proptest::option::of(author),
+            proptest::option::of(kinds),
+            proptest::option::of(since),
+            proptest::option::of(until),
+            proptest::option::of(search),
+        )
+            .prop_map(|(leaf, author, kinds, since, until, search)| {
+                [author, kinds, since, until, search]
+                    .into_iter()
+                    .flatten()
+                    .fold(leaf, Subscription::and)
+            })
+    }
+
+    /// Leaf with more constraints than a single filter can express, e. g. two
+    /// hashtags, and with a negated leaf, which filters leave out.
+    fn lossy() -> impl Strategy<Value = Subscription> {
+        (constrained(), leaf(), proptest::option::of(leaf())).prop_map(|(s, extra, excluded)| {
+            let s = s.and(extra);
+            match excluded {
+                Some(excluded) => s.and(!excluded),
+                None => s,
+            }
+        })
+    }
+
+    fn subscription() -> impl Strategy<Value = Subscription> {
+        constrained().prop_recursive(4, 16, 2, |inner| {
+            (inner.clone(), inner).prop_map(|(s1, s2)| s1.add(s2))
+        })
+    }
+
+    fn lossy_subscription() -> impl Strategy<Value = Subscription> {
+        prop_oneof![constrained(), lossy()].prop_recursive(4, 16, 2, |inner| {
+            (inner.clone(), inner).prop_map(|(s1, s2)| s1.add(s2))
+        })
+    }
+
+    /// Whether `a` and `b` select the same events, however they are composed.
+    fn same_selection(a: &Subscription, b: &Subscription) -> bool {
+        a.canonical() == b.canonical()
+    }
+
+    /// Whether `wider` (none selects nothing) selects at least what `narrower`
+    /// does, i. e. each conjunction of `narrower` implies one of `wider`.
+    fn selects_at_least(wider: Option<&Subscription>, narrower: &Subscription) -> bool {
+        let wider = wider.map(Subscription::canonical).unwrap_or_default();
+        narrower
+            .canonical()
+            .iter()
+            .all(|c| wider.iter().any(|w| w.is_subset(c)))
+    }
+
+    proptest! {
+        #[test]
+        fn filters_roundtrip(s in subscription()) {
+            match Subscription::from_filters(&s.to_filters(50)) {
+                Some(r) => prop_assert!(same_selection(&r, &s), "{} != {}", r.to_string(), s.to_string()),
+                // Nothing to ask relays for, e. g. an author who is not followed.
+                None => prop_assert!(s.canonical().is_empty()),
+            }
+        }
+
+        #[test]
+        fn lossy_filters_miss_nothing(s in lossy_subscription()) {
+            let r = Subscription::from_filters(&s.to_filters(50));
+            prop_assert!(selects_at_least(r.as_ref(), &s));
+        }
+    }
 }
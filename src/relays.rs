@@ -0,0 +1,284 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use nostr_sdk::prelude::{Event, Filter, Kind, Tag, XOnlyPublicKey};
+use nostr_sdk::Url;
+
+/// Relay as managed by user: whether it is used at all and
+/// whether events are read from it and published to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelayPolicy {
+    pub url: Url,
+    pub enabled: bool,
+    pub read: bool,
+    pub write: bool,
+}
+
+impl RelayPolicy {
+    /// Creates new enabled relay that is used for both reading and writing.
+    pub fn new(url: Url) -> RelayPolicy {
+        RelayPolicy {
+            url,
+            enabled: true,
+            read: true,
+            write: true,
+        }
+    }
+
+    /// Parses relay URL entered by user, accepts only websocket URLs.
+    pub fn parse_url(s: &str) -> Option<Url> {
+        Url::parse(s.trim())
+            .ok()
+            .filter(|u| u.scheme() == "ws" || u.scheme() == "wss")
+    }
+}
+
+/// Parses relay list metadata (NIP-65) from `event`. Relays without marker
+/// are used for both reading and writing. All the relays are enabled.
+pub fn parse_relay_list(event: &Event) -> Vec<RelayPolicy> {
+    if event.kind != Kind::RelayList {
+        return vec![];
+    }
+
+    event
+        .tags
+        .iter()
+        .filter_map(|t| match t.as_vec().as_slice() {
+            [r, url] if r == "r" => Some(RelayPolicy::new(RelayPolicy::parse_url(url)?)),
+            [r, url, marker] if r == "r" => Some(RelayPolicy {
+                read: marker != "write",
+                write: marker != "read",
+                ..RelayPolicy::new(RelayPolicy::parse_url(url)?)
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Creates tags of relay list metadata (NIP-65) from enabled relays.
+pub fn relay_list_tags(policies: &[RelayPolicy]) -> Vec<Tag> {
+    policies
+        .iter()
+        .filter(|p| p.enabled && (p.read || p.write))
+        .filter_map(|p| {
+            let url = p.url.to_string();
+            let tag = match (p.read, p.write) {
+                (true, true) => vec!["r".to_string(), url],
+                (true, false) => vec!["r".to_string(), url, "read".to_string()],
+                _ => vec!["r".to_string(), url, "write".to_string()],
+            };
+            Tag::parse(tag).ok()
+        })
+        .collect()
+}
+
+/// Whether events from `url` can be asked for there, i. e. it is a relay,
+/// not for example an archive the events were imported from.
+pub fn is_relay(url: &Url) -> bool {
+    matches!(url.scheme(), "ws" | "wss")
+}
+
+/// Chooses at most `per_author` of `write_relays` of each author to reach them at
+/// (NIP-65). Relays that are `connected` already are chosen first. Of the others,
+/// at most `max` are chosen, those that the most authors write to first, so that
+/// a few relays cover as many authors as possible.
+pub fn select_outboxes(
+    write_relays: &HashMap<XOnlyPublicKey, Vec<Url>>,
+    connected: &HashSet<Url>,
+    per_author: usize,
+    max: usize,
+) -> HashMap<XOnlyPublicKey, Vec<Url>> {
+    let mut selected = write_relays
+        .iter()
+        .map(|(author, urls)| {
+            let urls = urls
+                .iter()
+                .filter(|u| connected.contains(u))
+                .take(per_author)
+                .cloned()
+                .collect::<Vec<_>>();
+            (*author, urls)
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Authors writing to each of the relays that are not connected.
+    let mut writers: HashMap<&Url, Vec<XOnlyPublicKey>> = HashMap::new();
+    for (author, urls) in write_relays {
+        for url in urls.iter().filter(|u| !connected.contains(u)) {
+            writers.entry(url).or_default().push(*author);
+        }
+    }
+
+    for _ in 0..max {
+        let missing = |authors: &Vec<XOnlyPublicKey>| {
+            authors
+                .iter()
+                .filter(|a| selected[*a].len() < per_author)
+                .count()
+        };
+
+        // Ties are broken by URL, so that the choice does not change needlessly.
+        let best = writers
+            .iter()
+            .map(|(url, authors)| (missing(authors), *url))
+            .filter(|(n, _)| *n > 0)
+            .max_by(|(n1, u1), (n2, u2)| n1.cmp(n2).then(u2.cmp(u1)));
+        let Some((_, url)) = best else {
+            break;
+        };
+
+        for author in writers.remove(url).unwrap_or_default() {
+            let urls = selected.get_mut(&author).unwrap();
+            if urls.len() < per_author {
+                urls.push(url.clone());
+            }
+        }
+    }
+
+    selected
+}
+
+/// Selects what of `filters` is to be asked for at `relay`, given write relays
+/// of authors in `outboxes` (NIP-65). Authors are asked for at their write
+/// relays, authors without any at user's `own` relays. Filters not limited
+/// to authors go to user's relays and to write relays of profiles they mention.
+pub fn route_filters(
+    filters: &[Filter],
+    outboxes: &HashMap<XOnlyPublicKey, Vec<Url>>,
+    relay: &Url,
+    own: bool,
+) -> Vec<Filter> {
+    let outbox = |pubkey: &str| {
+        XOnlyPublicKey::from_str(pubkey)
+            .ok()
+            .and_then(|pk| outboxes.get(&pk))
+            .filter(|urls| !urls.is_empty())
+    };
+
+    filters
+        .iter()
+        .filter_map(|f| match &f.authors {
+            Some(authors) => {
+                let authors = authors
+                    .iter()
+                    .filter(|a| outbox(&a.to_string()).map_or(own, |urls| urls.contains(relay)))
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                (!authors.is_empty()).then(|| {
+                    let mut f = f.clone();
+                    f.authors = Some(authors);
+                    f
+                })
+            }
+            None => {
+                let mentioned = f
+                    .pubkeys
+                    .iter()
+                    .flatten()
+                    .any(|pk| outbox(&pk.to_string()).is_some_and(|urls| urls.contains(relay)));
+
+                (own || mentioned).then(|| f.clone())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::*;
+
+    use super::*;
+
+    fn policy(url: &str, read: bool, write: bool) -> RelayPolicy {
+        RelayPolicy {
+            read,
+            write,
+            ..RelayPolicy::new(Url::parse(url).unwrap())
+        }
+    }
+
+    #[test]
+    fn relay_list_roundtrip() {
+        let policies = vec![
+            policy("wss://both.example.com", true, true),
+            policy("wss://read.example.com", true, false),
+            policy("wss://write.example.com", false, true),
+        ];
+
+        let event = EventBuilder::new(Kind::RelayList, "", &relay_list_tags(&policies))
+            .to_event(&Keys::generate())
+            .unwrap();
+
+        assert_eq!(parse_relay_list(&event), policies);
+    }
+
+    #[test]
+    fn authors_asked_at_their_write_relays() {
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+        let own = Url::parse("wss://own.example.com").unwrap();
+        let alices = Url::parse("wss://alice.example.com").unwrap();
+        let outboxes = HashMap::from([(alice, vec![alices.clone()]), (bob, vec![])]);
+
+        let notes = Filter::new().authors(vec![alice.to_string(), bob.to_string()]);
+        let hashtag = Filter::new().hashtags(vec!["nostr".to_string()]);
+        let mentions = Filter::new().pubkey(alice);
+        let filters = [notes, hashtag, mentions];
+
+        let authors = |filters: &[Filter]| {
+            filters
+                .iter()
+                .flat_map(|f| f.authors.iter().flatten())
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+        };
+        let hashtags = |filters: &[Filter]| filters.iter().any(|f| f.hashtags.is_some());
+        let mentions = |filters: &[Filter]| filters.iter().any(|f| f.pubkeys.is_some());
+
+        let at_alices = route_filters(&filters, &outboxes, &alices, false);
+        assert_eq!(authors(&at_alices), vec![alice.to_string()]);
+        assert!(!hashtags(&at_alices));
+        assert!(mentions(&at_alices));
+
+        // Bob has no write relays, so he is asked for at user's relays.
+        let at_own = route_filters(&filters, &outboxes, &own, true);
+        assert_eq!(authors(&at_own), vec![bob.to_string()]);
+        assert!(hashtags(&at_own));
+        assert!(mentions(&at_own));
+    }
+
+    #[test]
+    fn outboxes_cover_most_authors() {
+        let [alice, bob, carol, dave] = [(); 4].map(|_| Keys::generate().public_key());
+        let url = |s: &str| Url::parse(&format!("wss://{s}.example.com")).unwrap();
+
+        let write_relays = HashMap::from([
+            (alice, vec![url("own"), url("popular"), url("alice")]),
+            (bob, vec![url("bob"), url("popular")]),
+            (carol, vec![url("carol"), url("popular")]),
+            (dave, vec![url("dave")]),
+        ]);
+        let connected = HashSet::from([url("own")]);
+
+        let selected = select_outboxes(&write_relays, &connected, 1, 1);
+        assert_eq!(selected[&alice], vec![url("own")]);
+        assert_eq!(selected[&bob], vec![url("popular")]);
+        assert_eq!(selected[&carol], vec![url("popular")]);
+        assert!(selected[&dave].is_empty());
+
+        let selected = select_outboxes(&write_relays, &connected, 2, 3);
+        assert_eq!(selected[&alice], vec![url("own"), url("popular")]);
+        assert_eq!(selected[&bob], vec![url("popular"), url("bob")]);
+        assert_eq!(selected[&carol], vec![url("popular"), url("carol")]);
+        assert!(selected[&dave].is_empty());
+    }
+
+    #[test]
+    fn disabled_relays_not_listed() {
+        let mut disabled = policy("wss://both.example.com", true, true);
+        disabled.enabled = false;
+
+        assert!(relay_list_tags(&[disabled]).is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+//! Archive of stored events as newline-delimited JSON, one Nostr event per line.
+
+use std::io::ErrorKind;
+use std::path::Path;
+
+use nostr_sdk::prelude::{Event, Kind, Url};
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tracing::debug;
+
+use super::{EventStore, StoreError};
+use crate::nostr::subscriptions::Subscription;
+use crate::nostr::ReceivedEvent;
+
+/// Number of events read from store at once during export.
+const EXPORT_BATCH: usize = 500;
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(std::io::Error),
+    Store(StoreError),
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::Io(e) => write!(f, "Could not access archive: {e}"),
+            ArchiveError::Store(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for ArchiveError {
+    fn from(e: std::io::Error) -> Self {
+        ArchiveError::Io(e)
+    }
+}
+
+impl From<StoreError> for ArchiveError {
+    fn from(e: StoreError) -> Self {
+        ArchiveError::Store(e)
+    }
+}
+
+/// Result of import of an archive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Imported {
+    /// Number of events read and stored. Events that had been stored
+    /// already are counted too.
+    pub events: u64,
+    /// Number of lines that were not genuine events.
+    pub invalid: u64,
+}
+
+/// Writes all stored events that `subscription` accepts to file at `path`.
+/// Returns number of written events.
+pub async fn export_file(
+    store: &dyn EventStore,
+    subscription: &Subscription,
+    path: &Path,
+) -> Result<u64, ArchiveError> {
+    let mut file = BufWriter::new(File::create(path).await?);
+    export(store, subscription, &mut file).await
+}
+
+/// Imports events from file at `path`, which is remembered as their source.
+pub async fn import_file(store: &dyn EventStore, path: &Path) -> Result<Imported, ArchiveError> {
+    let source = Url::from_file_path(path).map_err(|_| {
+        std::io::Error::new(ErrorKind::InvalidInput, "path of archive is not absolute")
+    })?;
+    let file = BufReader::new(File::open(path).await?);
+    import(store, file, &source).await
+}
+
+/// Writes all stored events that `subscription` accepts to `writer`,
+/// oldest first. Returns number of written events.
+pub async fn export<W>(
+    store: &dyn EventStore,
+    subscription: &Subscription,
+    writer: &mut W,
+) -> Result<u64, ArchiveError>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    let mut count = 0;
+    let mut after = None;
+
+    loop {
+        let events = store
+            .events(subscription, after.as_ref(), EXPORT_BATCH)
+            .await?;
+
+        for event in &events {
+            let json = serde_json::to_string(event).map_err(StoreError::from)?;
+            writer.write_all(json.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+
+        count += events.len() as u64;
+
+        if events.len() < EXPORT_BATCH {
+            break;
+        }
+
+        after = events.into_iter().last();
+    }
+
+    writer.flush().await?;
+    Ok(count)
+}
+
+/// Reads events from `reader` and stores those with valid signature, remembering
+/// `source` as where they came from (though it is not a relay, see
+/// [`is_relay`](crate::relays::is_relay)). Metadata, contact lists and relay
+/// lists also replace the older ones of their authors.
+pub async fn import<R>(
+    store: &dyn EventStore,
+    reader: R,
+    source: &Url,
+) -> Result<Imported, ArchiveError>
+where
+    R: AsyncBufRead + Unpin + Send,
+{
+    let mut imported = Imported::default();
+    let mut lines = reader.lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event = match serde_json::from_str::<Event>(&line) {
+            Ok(event) if event.verify().is_ok() => event,
+            _ => {
+                debug!("Skipping invalid event in archive: {line}");
+                imported.invalid += 1;
+                continue;
+            }
+        };
+
+        match event.kind {
+            Kind::Metadata => store.store_metadata(&event).await?,
+            Kind::ContactList | Kind::RelayList => store.store_replaceable(&event).await?,
+            _ => {}
+        }
+
+        store
+            .store_event(&ReceivedEvent {
+                event,
+                relay: source.clone(),
+            })
+            .await?;
+
+        imported.events += 1;
+    }
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::prelude::*;
+
+    use super::*;
+    use crate::store::memory::MemoryStore;
+    use crate::store::SqliteStore;
+
+    const ARCHIVE: &str = include_str!(
+        "../../resources/b4ee4de98a07d143f989d0b2cdba70af0366a7167712f3099d7c7a750533f15b.json"
+    );
+
+    fn source() -> Url {
+        Url::parse("file:///archive.json").unwrap()
+    }
+
+    #[tokio::test]
+    async fn import_deduplicates_events() {
+        let store = MemoryStore::default();
+
+        let imported = import(&store, ARCHIVE.as_bytes(), &source()).await.unwrap();
+        assert_eq!(
+            imported,
+            Imported {
+                events: 147,
+                invalid: 0
+            }
+        );
+
+        let mut out = vec![];
+        let exported = export(&store, &Subscription::Sink, &mut out).await.unwrap();
+        assert_eq!(exported, 58);
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 58);
+    }
+
+    #[tokio::test]
+    async fn import_skips_forged_events() {
+        let store = MemoryStore::default();
+        let forged =
+            ARCHIVE
+                .lines()
+                .next()
+                .unwrap()
+                .replacen("\"content\":\"", "\"content\":\"forged ", 1);
+        let archive = format!("{forged}\nnot an event\n\n");
+
+        let imported = import(&store, archive.as_bytes(), &source()).await.unwrap();
+        assert_eq!(
+            imported,
+            Imported {
+                events: 0,
+                invalid: 2
+            }
+        );
+    }
+
+    /// Exports notes of `store`, imports them into `copy` and checks that
+    /// the copy exports the same and knows where the notes came from.
+    async fn round_trip(store: &dyn EventStore, copy: &dyn EventStore) {
+        import(store, ARCHIVE.as_bytes(), &source()).await.unwrap();
+
+        let notes = Subscription::Kinds(vec![Kind::TextNote]);
+        let mut out = vec![];
+        export(store, &notes, &mut out).await.unwrap();
+
+        let imported = import(copy, out.as_slice(), &source()).await.unwrap();
+        assert_eq!(imported.invalid, 0);
+
+        let mut again = vec![];
+        export(copy, &Subscription::Sink, &mut again).await.unwrap();
+        assert_eq!(out, again);
+
+        let out = String::from_utf8(out).unwrap();
+        let event: Event = serde_json::from_str(out.lines().next().unwrap()).unwrap();
+        let relays = copy.event_relays(event.id).await.unwrap();
+        assert_eq!(relays, vec![source()]);
+    }
+
+    #[tokio::test]
+    async fn export_round_trip() {
+        round_trip(&MemoryStore::default(), &MemoryStore::default()).await;
+    }
+
+    #[tokio::test]
+    async fn export_round_trip_sqlite() {
+        round_trip(
+            &SqliteStore::in_memory().await,
+            &SqliteStore::in_memory().await,
+        )
+        .await;
+    }
+
+    /// Imports metadata of one author, the newer first, into `store`
+    /// and checks that the older does not replace it.
+    async fn newer_metadata_kept(store: &dyn EventStore) {
+        let keys = Keys::generate();
+        let newer = EventBuilder::set_metadata(Metadata::new().name("newer"))
+            .to_event(&keys)
+            .unwrap();
+        let older = {
+            let mut unsigned = EventBuilder::set_metadata(Metadata::new().name("older"))
+                .to_unsigned_event(keys.public_key());
+            unsigned.created_at = Timestamp::from(newer.created_at.as_u64() - 60);
+            unsigned.id = EventId::new(
+                &unsigned.pubkey,
+                unsigned.created_at,
+                &unsigned.kind,
+                &unsigned.tags,
+                &unsigned.content,
+            );
+            unsigned.sign(&keys).unwrap()
+        };
+
+        let archive = format!("{}\n{}\n", newer.as_json(), older.as_json());
+        import(store, archive.as_bytes(), &source()).await.unwrap();
+
+        let stored = store.get_metadata(keys.public_key()).await.unwrap();
+        assert_eq!(stored.map(|e| e.id), Some(newer.id));
+    }
+
+    #[tokio::test]
+    async fn import_keeps_newer_metadata() {
+        newer_metadata_kept(&MemoryStore::default()).await;
+        newer_metadata_kept(&SqliteStore::in_memory().await).await;
+    }
+}
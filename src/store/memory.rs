@@ -0,0 +1,375 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use nostr_sdk::nostr::nips::nip11::RelayInformationDocument;
+use nostr_sdk::prelude::{Event, EventId, Kind, Tag, Timestamp, Url, XOnlyPublicKey};
+
+use super::{EventStore, Page, SavedLane, StoreError};
+use crate::config::RetentionConfig;
+use crate::nostr::preview::Preview;
+use crate::nostr::subscriptions::Subscription;
+use crate::nostr::{EventExt, ReceivedEvent};
+use crate::relays::RelayPolicy;
+
+/// Event store that keeps everything in memory, for tests.
+#[derive(Debug, Default)]
+pub struct MemoryStore(Mutex<Memory>);
+
+#[derive(Debug, Default)]
+struct Memory {
+    events: HashMap<EventId, Event>,
+    event_relays: HashMap<EventId, HashSet<Url>>,
+    replaceable: HashMap<(XOnlyPublicKey, Kind), Event>,
+    metadata: HashMap<XOnlyPublicKey, Event>,
+    nip05_verified: HashMap<XOnlyPublicKey, Timestamp>,
+    relays: BTreeMap<Url, RelayPolicy>,
+    relay_information: HashMap<Url, (Timestamp, RelayInformationDocument)>,
+    previews: HashMap<reqwest::Url, Preview>,
+    lanes: Vec<SavedLane>,
+    bookmarks: HashSet<EventId>,
+    drafts: HashMap<String, String>,
+}
+
+impl MemoryStore {
+    fn with<A>(&self, f: impl FnOnce(&mut Memory) -> A) -> Result<A, StoreError> {
+        Ok(f(&mut self.0.lock().unwrap()))
+    }
+}
+
+#[async_trait]
+impl EventStore for MemoryStore {
+    async fn store_event(&self, event: &ReceivedEvent) -> Result<(), StoreError> {
+        self.with(|m| {
+            m.events
+                .entry(event.event.id)
+                .or_insert_with(|| event.event.clone());
+            m.event_relays
+                .entry(event.event.id)
+                .or_default()
+                .insert(event.relay.clone());
+        })
+    }
+
+    async fn get_event(&self, id: EventId) -> Result<Option<Event>, StoreError> {
+        self.with(|m| m.events.get(&id).cloned())
+    }
+
+    async fn event_relays(&self, id: EventId) -> Result<Vec<Url>, StoreError> {
+        self.with(|m| {
+            m.event_relays
+                .get(&id)
+                .map(|rs| rs.iter().cloned().collect())
+                .unwrap_or_default()
+        })
+    }
+
+    async fn text_notes(
+        &self,
+        subscription: &Subscription,
+        page: Page,
+        limit: usize,
+    ) -> Result<Vec<Event>, StoreError> {
+        self.with(|m| {
+            let mut notes = m
+                .events
+                .values()
+                .filter(|e| e.kind == Kind::TextNote && subscription.accepts(e))
+                .filter(|e| match page {
+                    Page::Older(until) => e.created_at < until,
+                    Page::Newer(since) => e.created_at > since,
+                    Page::Latest => true,
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
+            notes.sort_by_key(|e| e.created_at);
+            if !matches!(page, Page::Newer(_)) {
+                notes.reverse();
+            }
+            notes.truncate(limit);
+            notes
+        })
+    }
+
+    async fn events(
+        &self,
+        subscription: &Subscription,
+        after: Option<&Event>,
+        limit: usize,
+    ) -> Result<Vec<Event>, StoreError> {
+        let after = after.map(|e| (e.created_at, e.id.to_hex()));
+
+        self.with(|m| {
+            let mut events = m
+                .events
+                .values()
+                .filter(|e| subscription.accepts(e))
+                .filter(|e| {
+                    after
+                        .as_ref()
+                        .map_or(true, |a| (e.created_at, e.id.to_hex()) > *a)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
+            events.sort_by_key(|e| (e.created_at, e.id.to_hex()));
+            events.truncate(limit);
+            events
+        })
+    }
+
+    async fn referring_events(&self, id: EventId, kind: Kind) -> Result<Vec<Event>, StoreError> {
+        self.with(|m| {
+            let mut events = m
+                .events
+                .values()
+                .filter(|e| e.kind == kind)
+                .filter(|e| {
+                    e.tags
+                        .iter()
+                        .any(|t| matches!(t, Tag::Event(e, ..) if e == &id))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            events.sort_by_key(|e| e.created_at);
+            events
+        })
+    }
+
+    async fn store_replaceable(&self, event: &Event) -> Result<(), StoreError> {
+        self.with(|m| {
+            let key = (event.pubkey, event.kind);
+            if m.replaceable
+                .get(&key)
+                .map_or(true, |e| e.created_at < event.created_at)
+            {
+                m.replaceable.insert(key, event.clone());
+            }
+        })
+    }
+
+    async fn get_replaceable(
+        &self,
+        author: XOnlyPublicKey,
+        kind: Kind,
+    ) -> Result<Option<Event>, StoreError> {
+        self.with(|m| m.replaceable.get(&(author, kind)).cloned())
+    }
+
+    async fn store_metadata(&self, event: &Event) -> Result<(), StoreError> {
+        self.with(|m| {
+            if m.metadata
+                .get(&event.pubkey)
+                .map_or(true, |e| e.created_at < event.created_at)
+            {
+                m.metadata.insert(event.pubkey, event.clone());
+            }
+        })
+    }
+
+    async fn get_metadata(&self, author: XOnlyPublicKey) -> Result<Option<Event>, StoreError> {
+        self.with(|m| m.metadata.get(&author).cloned())
+    }
+
+    async fn search_profiles(&self, text: &str, limit: usize) -> Result<Vec<Event>, StoreError> {
+        let words = text
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+
+        self.with(|m| {
+            m.metadata
+                .values()
+                .filter(|e| {
+                    let Some(metadata) = e.as_metadata() else {
+                        return false;
+                    };
+                    let fields = [
+                        metadata.name,
+                        metadata.display_name,
+                        metadata.about,
+                        metadata.nip05,
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .to_lowercase();
+                    words.iter().all(|w| fields.contains(w))
+                })
+                .take(limit)
+                .cloned()
+                .collect()
+        })
+    }
+
+    async fn nip05_verified(&self, author: XOnlyPublicKey) -> Result<Option<Duration>, StoreError> {
+        self.with(|m| {
+            m.nip05_verified
+                .get(&author)
+                .map(|t| Duration::from_secs(Timestamp::now().as_u64().saturating_sub(t.as_u64())))
+        })
+    }
+
+    async fn set_nip05_verified(&self, author: XOnlyPublicKey) -> Result<(), StoreError> {
+        self.with(|m| {
+            m.nip05_verified.insert(author, Timestamp::now());
+        })
+    }
+
+    async fn offer_relay(&self, url: &str) -> Result<(), StoreError> {
+        self.with(|m| {
+            if let Ok(url) = Url::parse(url) {
+                m.relays.entry(url.clone()).or_insert(RelayPolicy {
+                    url,
+                    enabled: false,
+                    read: true,
+                    write: true,
+                });
+            }
+        })
+    }
+
+    async fn relay_policies(&self) -> Result<Vec<RelayPolicy>, StoreError> {
+        self.with(|m| {
+            let mut policies = m.relays.values().cloned().collect::<Vec<_>>();
+            policies.sort_by_key(|p| !p.enabled);
+            policies
+        })
+    }
+
+    async fn set_relay_policy(&self, policy: &RelayPolicy) -> Result<(), StoreError> {
+        self.with(|m| {
+            m.relays.insert(policy.url.clone(), policy.clone());
+        })
+    }
+
+    async fn outdated_relay_information(
+        &self,
+        max_age: Duration,
+    ) -> Result<Vec<(Url, bool)>, StoreError> {
+        self.with(|m| {
+            let now = Timestamp::now().as_u64();
+            m.relays
+                .keys()
+                .map(|url| {
+                    let old = m.relay_information.get(url).map_or(true, |(t, _)| {
+                        now.saturating_sub(t.as_u64()) > max_age.as_secs()
+                    });
+                    (url.clone(), old)
+                })
+                .collect()
+        })
+    }
+
+    async fn store_relay_information(
+        &self,
+        url: &Url,
+        information: &RelayInformationDocument,
+    ) -> Result<(), StoreError> {
+        self.with(|m| {
+            m.relay_information
+                .insert(url.clone(), (Timestamp::now(), information.clone()));
+        })
+    }
+
+    async fn search_relays(&self) -> Result<Vec<Url>, StoreError> {
+        self.with(|m| {
+            m.relay_information
+                .iter()
+                .filter(|(_, (_, i))| i.supported_nips.iter().flatten().any(|n| *n == 50))
+                .map(|(url, _)| url.clone())
+                .collect()
+        })
+    }
+
+    async fn get_preview(&self, url: &reqwest::Url) -> Result<Option<Preview>, StoreError> {
+        self.with(|m| m.previews.get(url).cloned())
+    }
+
+    async fn store_preview(&self, preview: &Preview) -> Result<(), StoreError> {
+        self.with(|m| {
+            m.previews.insert(preview.url().clone(), preview.clone());
+        })
+    }
+
+    async fn lanes(&self) -> Result<Vec<SavedLane>, StoreError> {
+        self.with(|m| m.lanes.clone())
+    }
+
+    async fn save_lanes(&self, lanes: &[SavedLane]) -> Result<(), StoreError> {
+        self.with(|m| m.lanes = lanes.to_vec())
+    }
+
+    async fn set_bookmark(&self, id: EventId, bookmarked: bool) -> Result<(), StoreError> {
+        self.with(|m| {
+            if bookmarked {
+                m.bookmarks.insert(id);
+            } else {
+                m.bookmarks.remove(&id);
+            }
+        })
+    }
+
+    async fn draft(&self, key: &str) -> Result<Option<String>, StoreError> {
+        self.with(|m| m.drafts.get(key).cloned())
+    }
+
+    async fn save_draft(&self, key: &str, content: &str) -> Result<(), StoreError> {
+        self.with(|m| {
+            if content.is_empty() {
+                m.drafts.remove(key);
+            } else {
+                m.drafts.insert(key.to_string(), content.to_string());
+            }
+        })
+    }
+
+    /// Size of database is not known, so it is not limited.
+    async fn prune(
+        &self,
+        retention: &RetentionConfig,
+        keep_authors: &[XOnlyPublicKey],
+    ) -> Result<u64, StoreError> {
+        self.with(|m| {
+            let before = Timestamp::now()
+                .as_u64()
+                .saturating_sub(retention.max_age_days.saturating_mul(24 * 60 * 60));
+
+            let mut by_author: HashMap<XOnlyPublicKey, Vec<&Event>> = HashMap::new();
+            for e in m.events.values() {
+                by_author.entry(e.pubkey).or_default().push(e);
+            }
+
+            let mut pruned = HashSet::new();
+            for (author, mut events) in by_author {
+                if keep_authors.contains(&author) {
+                    continue;
+                }
+
+                events.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+                for (n, e) in events.into_iter().enumerate() {
+                    let old = retention.max_age_days > 0 && e.created_at.as_u64() < before;
+                    let many =
+                        retention.events_per_author > 0 && n as u64 >= retention.events_per_author;
+                    if (old || many) && !m.bookmarks.contains(&e.id) {
+                        pruned.insert(e.id);
+                    }
+                }
+            }
+
+            for id in &pruned {
+                m.events.remove(id);
+                m.event_relays.remove(id);
+            }
+
+            pruned.len() as u64
+        })
+    }
+
+    async fn compact(&self) -> Result<u64, StoreError> {
+        Ok(0)
+    }
+}
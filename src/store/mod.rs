@@ -0,0 +1,197 @@
+//! Storage of events and of everything else Gnostique keeps between sessions.
+//!
+//! [`EventStore`] is what the rest of the application works with, [`SqliteStore`]
+//! is its implementation backed by SQLite database.
+
+pub mod archive;
+#[cfg(test)]
+pub mod memory;
+mod sqlite;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use nostr_sdk::nostr::nips::nip11::RelayInformationDocument;
+use nostr_sdk::prelude::{Event, EventId, Kind, Timestamp, Url, XOnlyPublicKey};
+
+pub use self::sqlite::SqliteStore;
+use crate::config::RetentionConfig;
+use crate::nostr::preview::Preview;
+use crate::nostr::subscriptions::Subscription;
+use crate::nostr::ReceivedEvent;
+use crate::relays::RelayPolicy;
+
+/// Part of stored text notes adjacent to a point in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Page {
+    /// Notes created before the time, newest first.
+    Older(Timestamp),
+    /// Notes created after the time, oldest first.
+    Newer(Timestamp),
+    /// The newest notes, newest first.
+    Latest,
+}
+
+/// Lane as it is saved to be restored on next start.
+#[derive(Clone, Debug)]
+pub struct SavedLane {
+    pub subscription: Subscription,
+    pub focused: Option<EventId>,
+    pub width: i32,
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Database(sqlx::Error),
+    /// Stored data could not be read.
+    Invalid(String),
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::Database(e) => write!(f, "Database failed: {e}"),
+            StoreError::Invalid(e) => write!(f, "Invalid stored data: {e}"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        StoreError::Database(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Invalid(e.to_string())
+    }
+}
+
+#[async_trait]
+pub trait EventStore: Send + Sync {
+    /// Stores event of any kind, its tags and relay from which it arrived.
+    /// Does nothing when the event is already stored, except for
+    /// remembering the relay.
+    async fn store_event(&self, event: &ReceivedEvent) -> Result<(), StoreError>;
+
+    /// Stored event with `id`.
+    async fn get_event(&self, id: EventId) -> Result<Option<Event>, StoreError>;
+
+    /// Known relays from which event with `id` arrived.
+    async fn event_relays(&self, id: EventId) -> Result<Vec<Url>, StoreError>;
+
+    /// At most `limit` stored text notes of `page` that `subscription` accepts.
+    /// If the subscription requires search, only notes found by full-text
+    /// search are considered.
+    async fn text_notes(
+        &self,
+        subscription: &Subscription,
+        page: Page,
+        limit: usize,
+    ) -> Result<Vec<Event>, StoreError>;
+
+    /// At most `limit` stored events of any kind that `subscription` accepts,
+    /// oldest first. If `after` is given, only events following it in this
+    /// order are considered.
+    async fn events(
+        &self,
+        subscription: &Subscription,
+        after: Option<&Event>,
+        limit: usize,
+    ) -> Result<Vec<Event>, StoreError>;
+
+    /// Stored events of `kind` that refer to event `id` by an `e` tag,
+    /// such as its reactions, reposts or replies, oldest first.
+    async fn referring_events(&self, id: EventId, kind: Kind) -> Result<Vec<Event>, StoreError>;
+
+    /// Stores replaceable `event` unless a newer one of the same kind
+    /// from the same author is already stored.
+    async fn store_replaceable(&self, event: &Event) -> Result<(), StoreError>;
+
+    /// The latest replaceable event of `kind` from `author`.
+    async fn get_replaceable(
+        &self,
+        author: XOnlyPublicKey,
+        kind: Kind,
+    ) -> Result<Option<Event>, StoreError>;
+
+    /// Stores metadata `event` (kind 0), replacing the previous one of its author
+    /// if that is older.
+    async fn store_metadata(&self, event: &Event) -> Result<(), StoreError>;
+
+    /// Metadata event (kind 0) of `author`.
+    async fn get_metadata(&self, author: XOnlyPublicKey) -> Result<Option<Event>, StoreError>;
+
+    /// At most `limit` metadata events (kind 0) whose name, display name,
+    /// about or NIP-05 identifier contain all words of `text`, best first.
+    async fn search_profiles(&self, text: &str, limit: usize) -> Result<Vec<Event>, StoreError>;
+
+    /// Time since NIP-05 identifier of `author` was last verified, if ever.
+    async fn nip05_verified(&self, author: XOnlyPublicKey) -> Result<Option<Duration>, StoreError>;
+
+    /// Remembers that NIP-05 identifier of `author` has just been verified.
+    async fn set_nip05_verified(&self, author: XOnlyPublicKey) -> Result<(), StoreError>;
+
+    /// Remembers relay, unless it is known already.
+    async fn offer_relay(&self, url: &str) -> Result<(), StoreError>;
+
+    /// All known relays and how they are supposed to be used.
+    /// Enabled relays come first.
+    async fn relay_policies(&self) -> Result<Vec<RelayPolicy>, StoreError>;
+
+    /// Stores relay policy, replacing the previous one of the same relay.
+    async fn set_relay_policy(&self, policy: &RelayPolicy) -> Result<(), StoreError>;
+
+    /// All known relays, each with flag telling whether its information
+    /// document (NIP-11) is missing or older than `max_age`.
+    async fn outdated_relay_information(
+        &self,
+        max_age: Duration,
+    ) -> Result<Vec<(Url, bool)>, StoreError>;
+
+    /// Stores information document (NIP-11) of relay.
+    async fn store_relay_information(
+        &self,
+        url: &Url,
+        information: &RelayInformationDocument,
+    ) -> Result<(), StoreError>;
+
+    /// Relays whose information document (NIP-11) says they support search (NIP-50).
+    async fn search_relays(&self) -> Result<Vec<Url>, StoreError>;
+
+    /// Stored preview of link to `url`.
+    async fn get_preview(&self, url: &reqwest::Url) -> Result<Option<Preview>, StoreError>;
+
+    /// Stores link preview, replacing the previous one of the same URL.
+    async fn store_preview(&self, preview: &Preview) -> Result<(), StoreError>;
+
+    /// Lanes saved by [`EventStore::save_lanes`], in their order. Lanes
+    /// that cannot be restored anymore are skipped.
+    async fn lanes(&self) -> Result<Vec<SavedLane>, StoreError>;
+
+    /// Saves `lanes`, replacing the previously saved ones.
+    async fn save_lanes(&self, lanes: &[SavedLane]) -> Result<(), StoreError>;
+
+    /// Bookmarks event `id` (`true`) or removes its bookmark (`false`).
+    /// Bookmarked events are never pruned.
+    async fn set_bookmark(&self, id: EventId, bookmarked: bool) -> Result<(), StoreError>;
+
+    /// Content of draft saved under `key`, if there is any.
+    async fn draft(&self, key: &str) -> Result<Option<String>, StoreError>;
+
+    /// Saves draft of `content` under `key`, replacing the previous one.
+    /// Empty content removes the draft.
+    async fn save_draft(&self, key: &str, content: &str) -> Result<(), StoreError>;
+
+    /// Deletes events beyond limits of `retention`, except those of `keep_authors`
+    /// and bookmarked ones. Returns number of deleted events.
+    async fn prune(
+        &self,
+        retention: &RetentionConfig,
+        keep_authors: &[XOnlyPublicKey],
+    ) -> Result<u64, StoreError>;
+
+    /// Gives space left by deleted data back. Returns number of bytes reclaimed.
+    async fn compact(&self) -> Result<u64, StoreError>;
+}
@@ -0,0 +1,937 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::{future, StreamExt};
+use gtk::{gdk, glib};
+use nostr_sdk::nostr::nips::nip11::RelayInformationDocument;
+use nostr_sdk::prelude::{Event, EventId, Kind, Tag, Timestamp, Url, XOnlyPublicKey};
+use sqlx::{query, QueryBuilder, Sqlite, SqlitePool};
+use tracing::warn;
+
+use super::{EventStore, Page, SavedLane, StoreError};
+use crate::config::RetentionConfig;
+use crate::nostr::preview::{Preview, PreviewKind, Thumbnail};
+use crate::nostr::subscriptions::{Indexed, Subscription};
+use crate::nostr::ReceivedEvent;
+use crate::relays::RelayPolicy;
+
+/// Number of the oldest events deleted at once when database is too large.
+const PRUNE_BATCH: i64 = 1000;
+
+/// Maximum number of batches deleted by one pruning, whatever the size.
+const PRUNE_MAX_BATCHES: usize = 100;
+
+/// Event store backed by SQLite database.
+#[derive(Clone, Debug)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub fn new(pool: SqlitePool) -> SqliteStore {
+        SqliteStore { pool }
+    }
+
+    /// Store of a new migrated database in memory.
+    #[cfg(test)]
+    pub async fn in_memory() -> SqliteStore {
+        // Each connection would have its own database in memory.
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        SqliteStore { pool }
+    }
+}
+
+#[async_trait]
+impl EventStore for SqliteStore {
+    async fn store_event(&self, event: &ReceivedEvent) -> Result<(), StoreError> {
+        let ReceivedEvent { event, relay } = event;
+        let id = event.id.as_bytes().to_vec();
+        let pubkey = event.pubkey.serialize().to_vec();
+        let kind = event.kind.as_u64() as i64;
+        let created_at = event.created_at.as_i64();
+        let json = serde_json::to_string(event)?;
+        let relay_str = relay.to_string();
+
+        let mut tx = self.pool.begin().await?;
+
+        query!(
+            "INSERT INTO events (id, pubkey, kind, created_at, event) VALUES (?, ?, ?, ?, ?)",
+            id,
+            pubkey,
+            kind,
+            created_at,
+            json
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (name, value) in event.tags.iter().filter_map(indexed_tag) {
+            query!(
+                "INSERT INTO event_tags (event, name, value) VALUES (?, ?, ?)",
+                id,
+                name,
+                value
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        query!(
+            "INSERT INTO events_relays (event, relay) VALUES (?, ?)",
+            id,
+            relay_str
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(tx.commit().await?)
+    }
+
+    async fn get_event(&self, id: EventId) -> Result<Option<Event>, StoreError> {
+        let id_bytes = id.as_bytes().to_vec();
+
+        query!("SELECT event FROM events WHERE id = ?", id_bytes)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|r| serde_json::from_str::<Event>(&r.event))
+            .transpose()
+            .map_err(StoreError::from)
+    }
+
+    async fn event_relays(&self, id: EventId) -> Result<Vec<Url>, StoreError> {
+        let id: &[u8] = id.as_bytes();
+
+        // Sources need not be known relays, e.g. imported archives.
+        Ok(
+            query!("SELECT relay FROM events_relays WHERE event = ?", id)
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .filter_map(|r| Url::parse(&r.relay).ok())
+                .collect(),
+        )
+    }
+
+    async fn text_notes(
+        &self,
+        subscription: &Subscription,
+        page: Page,
+        limit: usize,
+    ) -> Result<Vec<Event>, StoreError> {
+        let search = subscription
+            .required_search()
+            .filter(|t| !t.trim().is_empty())
+            .map(|t| fts_query(&t));
+
+        // Only candidates that can be found by indexes are read from database.
+        let mut q = QueryBuilder::<Sqlite>::new("SELECT events.event FROM ");
+        match search {
+            Some(search) => {
+                q.push(
+                    r#"
+notes_search
+JOIN note_texts ON note_texts.id = notes_search.rowid
+JOIN events ON events.id = note_texts.event
+WHERE notes_search MATCH "#,
+                )
+                .push_bind(search)
+                .push(" AND ");
+            }
+            None => {
+                q.push("events WHERE ");
+            }
+        }
+
+        q.push("events.kind = 1");
+        if let Some(alternatives) = subscription.indexed() {
+            q.push(" AND ");
+            push_indexed(&mut q, alternatives);
+        }
+
+        match page {
+            Page::Older(_) | Page::Latest => q
+                .push(" AND events.created_at < ")
+                .push_bind(until(page))
+                .push(" ORDER BY events.created_at DESC"),
+            Page::Newer(since) => q
+                .push(" AND events.created_at > ")
+                .push_bind(since.as_i64())
+                .push(" ORDER BY events.created_at ASC"),
+        };
+
+        let events = q.build_query_scalar::<String>().fetch(&self.pool);
+
+        // What indexes cannot tell (e. g. negation) is evaluated on each note,
+        // so the notes are read only until there is enough of those it accepts.
+        events
+            .map(|r| -> Result<Event, StoreError> { Ok(serde_json::from_str(&r?)?) })
+            .filter(|r| future::ready(r.as_ref().map_or(true, |e| subscription.accepts(e))))
+            .take(limit)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    async fn events(
+        &self,
+        subscription: &Subscription,
+        after: Option<&Event>,
+        limit: usize,
+    ) -> Result<Vec<Event>, StoreError> {
+        let created_at = after.map_or(i64::MIN, |e| e.created_at.as_i64());
+        let id = after.map_or(vec![], |e| e.id.as_bytes().to_vec());
+
+        query!(
+            r#"
+SELECT event FROM events
+WHERE created_at > ? OR (created_at = ? AND id > ?)
+ORDER BY created_at, id"#,
+            created_at,
+            created_at,
+            id
+        )
+        .map(|r| r.event)
+        .fetch(&self.pool)
+        .map(|r| -> Result<Event, StoreError> { Ok(serde_json::from_str(&r?)?) })
+        .filter(|r| future::ready(r.as_ref().map_or(true, |e| subscription.accepts(e))))
+        .take(limit)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+    }
+
+    async fn referring_events(&self, id: EventId, kind: Kind) -> Result<Vec<Event>, StoreError> {
+        let id = id.to_hex();
+        let kind = kind.as_u64() as i64;
+
+        query!(
+            r#"
+SELECT event FROM events
+WHERE kind = ?
+  AND id IN (SELECT event FROM event_tags WHERE name = 'e' AND value = ?)
+ORDER BY created_at"#,
+            kind,
+            id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|r| Ok(serde_json::from_str::<Event>(&r.event)?))
+        .collect()
+    }
+
+    async fn store_replaceable(&self, event: &Event) -> Result<(), StoreError> {
+        let author = event.pubkey.serialize().to_vec();
+        let kind = event.kind.as_u64() as i64;
+        let created_at = event.created_at.as_i64();
+        let json = serde_json::to_string(event)?;
+
+        query!(
+            r#"
+INSERT INTO replaceable_events (author, kind, created_at, event) VALUES (?, ?, ?, ?)
+ON CONFLICT (author, kind) DO UPDATE SET
+  created_at = EXCLUDED.created_at,
+  event = EXCLUDED.event
+WHERE EXCLUDED.created_at > replaceable_events.created_at
+"#,
+            author,
+            kind,
+            created_at,
+            json
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_replaceable(
+        &self,
+        author: XOnlyPublicKey,
+        kind: Kind,
+    ) -> Result<Option<Event>, StoreError> {
+        let author = author.serialize().to_vec();
+        let kind = kind.as_u64() as i64;
+
+        query!(
+            "SELECT event FROM replaceable_events WHERE author = ? AND kind = ?",
+            author,
+            kind
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|r| serde_json::from_str::<Event>(&r.event))
+        .transpose()
+        .map_err(StoreError::from)
+    }
+
+    async fn store_metadata(&self, event: &Event) -> Result<(), StoreError> {
+        let pubkey_vec = event.pubkey.serialize().to_vec();
+        let json = serde_json::to_string(event)?;
+
+        query!(
+            r#"
+INSERT INTO metadata (author, event) VALUES (?, ?)
+ON CONFLICT (author) DO UPDATE SET event = EXCLUDED.event
+WHERE EXCLUDED.event ->> '$.created_at' > metadata.event ->> '$.created_at'
+"#,
+            pubkey_vec,
+            json
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_metadata(&self, author: XOnlyPublicKey) -> Result<Option<Event>, StoreError> {
+        let author: &[u8] = &author.serialize();
+
+        query!("SELECT event FROM metadata WHERE author = ?", author)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|r| serde_json::from_str::<Event>(&r.event))
+            .transpose()
+            .map_err(StoreError::from)
+    }
+
+    async fn search_profiles(&self, text: &str, limit: usize) -> Result<Vec<Event>, StoreError> {
+        let search = fts_query(text);
+        let limit = limit as i64;
+
+        query!(
+            r#"
+SELECT metadata.event FROM profiles_search
+JOIN profile_texts ON profile_texts.id = profiles_search.rowid
+JOIN metadata ON metadata.author = profile_texts.author
+WHERE profiles_search MATCH ?
+ORDER BY profiles_search.rank
+LIMIT ?"#,
+            search,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|r| Ok(serde_json::from_str(&r.event)?))
+        .collect()
+    }
+
+    async fn nip05_verified(&self, author: XOnlyPublicKey) -> Result<Option<Duration>, StoreError> {
+        let author = author.serialize().to_vec();
+
+        let seconds = query!(
+            r#"
+SELECT unixepoch('now') - unixepoch(nip05_verified) AS "seconds?: i64"
+FROM metadata WHERE author = ?"#,
+            author
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .and_then(|r| r.seconds);
+
+        Ok(seconds.map(|s| Duration::from_secs(s.max(0) as u64)))
+    }
+
+    async fn set_nip05_verified(&self, author: XOnlyPublicKey) -> Result<(), StoreError> {
+        let author = author.serialize().to_vec();
+
+        query!(
+            "UPDATE metadata SET nip05_verified = datetime('now') WHERE author = ?",
+            author
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn offer_relay(&self, url: &str) -> Result<(), StoreError> {
+        query!(
+            "INSERT INTO relays(url) VALUES (?) ON CONFLICT(url) DO NOTHING",
+            url
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn relay_policies(&self) -> Result<Vec<RelayPolicy>, StoreError> {
+        Ok(query!(
+            r#"
+SELECT url, enabled AS "enabled: bool", read AS "read: bool", write AS "write: bool"
+FROM relays
+ORDER BY enabled DESC, url
+"#
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(|r| {
+            Some(RelayPolicy {
+                url: Url::parse(&r.url).ok()?,
+                enabled: r.enabled,
+                read: r.read,
+                write: r.write,
+            })
+        })
+        .collect())
+    }
+
+    async fn set_relay_policy(&self, policy: &RelayPolicy) -> Result<(), StoreError> {
+        let url_s = policy.url.to_string();
+
+        query!(
+            r#"
+INSERT INTO relays(url, enabled, read, write) VALUES (?, ?, ?, ?)
+ON CONFLICT(url) DO UPDATE SET
+  enabled = EXCLUDED.enabled,
+  read = EXCLUDED.read,
+  write = EXCLUDED.write
+"#,
+            url_s,
+            policy.enabled,
+            policy.read,
+            policy.write
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn outdated_relay_information(
+        &self,
+        max_age: Duration,
+    ) -> Result<Vec<(Url, bool)>, StoreError> {
+        let max_age = max_age.as_secs() as i64;
+
+        Ok(query!(
+            r#"
+SELECT
+  url,
+  information IS NULL OR unixepoch('now') - unixepoch(updated) > ? AS "old: bool"
+FROM relays
+"#,
+            max_age
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(|r| Some((Url::parse(&r.url).ok()?, r.old)))
+        .collect())
+    }
+
+    async fn store_relay_information(
+        &self,
+        url: &Url,
+        information: &RelayInformationDocument,
+    ) -> Result<(), StoreError> {
+        let url_s = url.to_string();
+        let info_json = serde_json::to_string(information)?;
+
+        query!(
+            r#"
+INSERT INTO relays(url, information, updated)
+VALUES (?, ?, CURRENT_TIMESTAMP)
+ON CONFLICT(url) DO UPDATE SET
+  information = EXCLUDED.information,
+  updated = EXCLUDED.updated
+"#,
+            url_s,
+            info_json
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn search_relays(&self) -> Result<Vec<Url>, StoreError> {
+        Ok(query!(
+            r#"
+SELECT url FROM relays
+WHERE EXISTS (
+  SELECT 1 FROM json_each(relays.information, '$.supported_nips') WHERE value = 50
+)"#
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(|r| Url::parse(&r.url).ok())
+        .collect())
+    }
+
+    async fn get_preview(&self, url: &reqwest::Url) -> Result<Option<Preview>, StoreError> {
+        let url_s = url.to_string();
+
+        let record = query!(
+            r#"
+SELECT kind, title, description, thumbnail, thumbnail_url, error
+FROM previews
+WHERE url = ?
+"#,
+            url_s
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|r| {
+            let thumbnail = r.thumbnail.zip(r.thumbnail_url).and_then(|(bytes, t_url)| {
+                let texture = gdk::Texture::from_bytes(&glib::Bytes::from(&bytes)).ok()?;
+                Some(Thumbnail::new(texture, reqwest::Url::parse(&t_url).ok()?))
+            });
+
+            Preview::new(
+                url.clone(),
+                preview_kind(&r.kind),
+                r.title,
+                r.description,
+                thumbnail,
+                r.error,
+            )
+        }))
+    }
+
+    async fn store_preview(&self, preview: &Preview) -> Result<(), StoreError> {
+        let url = preview.url().to_string();
+        let kind = format!("{:?}", preview.kind());
+        let title = preview.title();
+        let description = preview.description().map(String::as_str);
+        let error = preview.error();
+        let thumbnail = preview
+            .thumbnail()
+            .map(|t| t.texture().save_to_png_bytes().to_vec());
+        let thumbnail_url = preview.thumbnail().map(|t| t.url());
+
+        query!(
+            r#"
+INSERT INTO previews (url, kind, title, description, thumbnail, thumbnail_url, error)
+VALUES (?, ?, ?, ?, ?, ?, ?)
+"#,
+            url,
+            kind,
+            title,
+            description,
+            thumbnail,
+            thumbnail_url,
+            error
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn lanes(&self) -> Result<Vec<SavedLane>, StoreError> {
+        Ok(
+            query!("SELECT query, focused, width FROM lanes ORDER BY position")
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .filter_map(|r| match r.query.parse::<Subscription>() {
+                    Ok(subscription) => Some(SavedLane {
+                        subscription,
+                        focused: r.focused.and_then(|f| EventId::from_slice(&f).ok()),
+                        width: r.width as i32,
+                    }),
+                    Err(e) => {
+                        warn!("Could not restore lane '{}': {e}", r.query);
+                        None
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    async fn save_lanes(&self, lanes: &[SavedLane]) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        query!("DELETE FROM lanes").execute(&mut *tx).await?;
+
+        for (position, lane) in lanes.iter().enumerate() {
+            let position = position as i64;
+            let query = lane.subscription.to_string();
+            let focused = lane.focused.map(|f| f.as_bytes().to_vec());
+
+            query!(
+                "INSERT INTO lanes (position, query, focused, width) VALUES (?, ?, ?, ?)",
+                position,
+                query,
+                focused,
+                lane.width
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(tx.commit().await?)
+    }
+
+    async fn set_bookmark(&self, id: EventId, bookmarked: bool) -> Result<(), StoreError> {
+        let id = id.as_bytes().to_vec();
+
+        if bookmarked {
+            query!("INSERT INTO bookmarks (event) VALUES (?)", id)
+                .execute(&self.pool)
+                .await?;
+        } else {
+            query!("DELETE FROM bookmarks WHERE event = ?", id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn draft(&self, key: &str) -> Result<Option<String>, StoreError> {
+        Ok(query!("SELECT content FROM drafts WHERE key = ?", key)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|r| r.content))
+    }
+
+    async fn save_draft(&self, key: &str, content: &str) -> Result<(), StoreError> {
+        if content.is_empty() {
+            query!("DELETE FROM drafts WHERE key = ?", key)
+                .execute(&self.pool)
+                .await?;
+        } else {
+            query!(
+                "INSERT INTO drafts (key, content) VALUES (?, ?)",
+                key,
+                content
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn prune(
+        &self,
+        retention: &RetentionConfig,
+        keep_authors: &[XOnlyPublicKey],
+    ) -> Result<u64, StoreError> {
+        // Authors are passed as JSON array of hex pubkeys.
+        let keep = serde_json::to_string(
+            &keep_authors
+                .iter()
+                .map(|pk| pk.to_string())
+                .collect::<Vec<_>>(),
+        )?;
+        let mut deleted = 0;
+
+        if retention.max_age_days > 0 {
+            let age = retention.max_age_days.saturating_mul(24 * 60 * 60);
+            let before = Timestamp::now()
+                .as_i64()
+                .saturating_sub(i64::try_from(age).unwrap_or(i64::MAX));
+
+            deleted += query!(
+                r#"
+DELETE FROM events
+WHERE created_at < ?
+  AND pubkey NOT IN (SELECT unhex(value) FROM json_each(?))
+  AND id NOT IN (SELECT event FROM bookmarks)"#,
+                before,
+                keep
+            )
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+
+        if retention.events_per_author > 0 {
+            let max = retention.events_per_author as i64;
+
+            deleted += query!(
+                r#"
+DELETE FROM events
+WHERE id IN (
+    SELECT id FROM (
+      SELECT id, row_number() OVER (PARTITION BY pubkey ORDER BY created_at DESC) AS n
+      FROM events
+    )
+    WHERE n > ?
+  )
+  AND pubkey NOT IN (SELECT unhex(value) FROM json_each(?))
+  AND id NOT IN (SELECT event FROM bookmarks)"#,
+                max,
+                keep
+            )
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+
+        if retention.database_mb > 0 {
+            let max = i64::try_from(retention.database_mb.saturating_mul(1024 * 1024))
+                .unwrap_or(i64::MAX);
+
+            // Only events are pruned, so only their size counts, not previews,
+            // metadata, indexes or free pages the file may be full of.
+            for _ in 0..PRUNE_MAX_BATCHES {
+                let size =
+                    query!(r#"SELECT coalesce(sum(length(event)), 0) AS "size!: i64" FROM events"#)
+                        .fetch_one(&self.pool)
+                        .await?
+                        .size;
+
+                if size <= max {
+                    break;
+                }
+
+                let batch = query!(
+                    r#"
+DELETE FROM events
+WHERE id IN (
+    SELECT id FROM events
+    WHERE pubkey NOT IN (SELECT unhex(value) FROM json_each(?))
+      AND id NOT IN (SELECT event FROM bookmarks)
+    ORDER BY created_at
+    LIMIT ?
+  )"#,
+                    keep,
+                    PRUNE_BATCH
+                )
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+                if batch == 0 {
+                    break;
+                }
+                deleted += batch;
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    async fn compact(&self) -> Result<u64, StoreError> {
+        let size = || {
+            query!(
+                r#"
+SELECT page_count * page_size AS "size!: i64"
+FROM pragma_page_count(), pragma_page_size()"#
+            )
+            .fetch_one(&self.pool)
+        };
+
+        let before = size().await?.size;
+
+        // Full-text indexes are merged into as few segments as possible.
+        query("INSERT INTO notes_search (notes_search) VALUES ('optimize')")
+            .execute(&self.pool)
+            .await?;
+        query("INSERT INTO profiles_search (profiles_search) VALUES ('optimize')")
+            .execute(&self.pool)
+            .await?;
+        query("VACUUM").execute(&self.pool).await?;
+
+        let after = size().await?.size;
+
+        Ok((before - after).max(0) as u64)
+    }
+}
+
+/// Upper bound of creation time of notes of `page`.
+fn until(page: Page) -> i64 {
+    match page {
+        Page::Older(until) => until.as_i64(),
+        _ => i64::MAX,
+    }
+}
+
+/// Pushes condition that events satisfy all constraints of at least
+/// one of `alternatives`, see [`Subscription::indexed`].
+fn push_indexed(q: &mut QueryBuilder<Sqlite>, alternatives: Vec<Vec<Indexed>>) {
+    q.push("(");
+    for (i, constraints) in alternatives.into_iter().enumerate() {
+        if i > 0 {
+            q.push(" OR ");
+        }
+        q.push("(");
+        for (j, constraint) in constraints.into_iter().enumerate() {
+            if j > 0 {
+                q.push(" AND ");
+            }
+            match constraint {
+                Indexed::Authors(authors) if authors.is_empty() => {
+                    q.push("0");
+                }
+                Indexed::Authors(authors) => {
+                    q.push("events.pubkey IN (");
+                    let mut list = q.separated(", ");
+                    for author in authors {
+                        list.push_bind(author.serialize().to_vec());
+                    }
+                    list.push_unseparated(")");
+                }
+                Indexed::Id(id) => {
+                    q.push("events.id = ").push_bind(id.as_bytes().to_vec());
+                }
+                Indexed::Thread(id) => {
+                    q.push("(events.id = ")
+                        .push_bind(id.as_bytes().to_vec())
+                        .push(" OR events.id IN (SELECT event FROM event_tags")
+                        .push(" WHERE name = 'e' AND value = ")
+                        .push_bind(id.to_hex())
+                        .push("))");
+                }
+                Indexed::Hashtag(tag) => {
+                    q.push("events.id IN (SELECT event FROM event_tags")
+                        .push(" WHERE name = 't' AND lower(value) = ")
+                        .push_bind(tag)
+                        .push(")");
+                }
+                Indexed::Since(t) => {
+                    q.push("events.created_at >= ").push_bind(t.as_i64());
+                }
+                Indexed::Until(t) => {
+                    q.push("events.created_at <= ").push_bind(t.as_i64());
+                }
+            }
+        }
+        q.push(")");
+    }
+    q.push(")");
+}
+
+/// Makes full-text query finding all words of `text`, each as a prefix.
+/// Words are quoted, so that nothing in them is taken for query syntax.
+fn fts_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Name and value of `tag` if it is one of those stored in `event_tags`
+/// (`e`, `p` or `t`).
+fn indexed_tag(tag: &Tag) -> Option<(String, String)> {
+    match tag.as_vec().as_slice() {
+        [name, value, ..] if matches!(name.as_str(), "e" | "p" | "t") => {
+            Some((name.clone(), value.clone()))
+        }
+        _ => None,
+    }
+}
+
+/// Reads kind of preview as it is stored by [`SqliteStore::store_preview`].
+fn preview_kind(kind: &str) -> PreviewKind {
+    match kind {
+        "Image" => PreviewKind::Image,
+        "Webpage" => PreviewKind::Webpage,
+        _ => PreviewKind::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use nostr_sdk::prelude::*;
+
+    use super::*;
+    use crate::follow::Follow;
+
+    async fn store_note(store: &SqliteStore, keys: &Keys, tags: &[Tag]) -> EventId {
+        let event = EventBuilder::new_text_note("Hello", tags)
+            .to_event(keys)
+            .unwrap();
+        let received = ReceivedEvent {
+            event: event.clone(),
+            relay: Url::parse("wss://relay.example.com").unwrap(),
+        };
+        store.store_event(&received).await.unwrap();
+        event.id
+    }
+
+    async fn notes(store: &SqliteStore, subscription: Subscription) -> HashSet<EventId> {
+        store
+            .text_notes(&subscription, Page::Latest, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn text_notes_by_indexes() {
+        let store = SqliteStore::in_memory().await;
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+
+        let root = store_note(&store, &alice, &[]).await;
+        let tagged = store_note(&store, &bob, &[Tag::Hashtag("Nostr".to_string())]).await;
+        let reply = store_note(&store, &bob, &[Tag::Event(root, None, Some(Marker::Root))]).await;
+
+        assert_eq!(
+            notes(
+                &store,
+                Subscription::Following(Follow::new().follow(alice.public_key()))
+            )
+            .await,
+            HashSet::from([root])
+        );
+        assert_eq!(
+            notes(&store, Subscription::Following(Follow::new())).await,
+            HashSet::new()
+        );
+        assert_eq!(
+            notes(&store, Subscription::hashtag("nostr")).await,
+            HashSet::from([tagged])
+        );
+        assert_eq!(
+            notes(&store, Subscription::thread(root)).await,
+            HashSet::from([root, reply])
+        );
+        assert_eq!(
+            notes(
+                &store,
+                Subscription::Author(bob.public_key()).and(!Subscription::hashtag("nostr"))
+            )
+            .await,
+            HashSet::from([reply])
+        );
+        assert_eq!(
+            notes(&store, Subscription::Sink).await,
+            HashSet::from([root, tagged, reply])
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_by_size_of_events() {
+        let store = SqliteStore::in_memory().await;
+        let alice = Keys::generate();
+        let note = store_note(&store, &alice, &[]).await;
+
+        // A database larger than the limit because of something else than events.
+        query("INSERT INTO drafts (key, content) VALUES ('new', ?)")
+            .bind("x".repeat(2 * 1024 * 1024))
+            .execute(&store.pool)
+            .await
+            .unwrap();
+
+        let retention = RetentionConfig {
+            max_age_days: 0,
+            events_per_author: 0,
+            database_mb: 1,
+            ..RetentionConfig::default()
+        };
+        assert_eq!(store.prune(&retention, &[]).await.unwrap(), 0);
+        assert!(store.get_event(note).await.unwrap().is_some());
+    }
+}
@@ -4,5 +4,7 @@ use crate::gnostique::Gnostique;
 #[derive(Debug)]
 pub enum AppInput {
     Unlocked(Gnostique),
+    ShowRelaySettings,
+    ShowIdentities,
     Quit,
 }
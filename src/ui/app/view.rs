@@ -4,8 +4,8 @@ use relm4::*;
 
 use super::model::*;
 use super::msg::*;
-use crate::ui::main::Main;
-use crate::ui::settings::Settings;
+use crate::ui::main::{Main, MainOutput};
+use crate::ui::settings::{Settings, SettingsInput};
 use crate::ui::unlock::{Unlock, UnlockResult};
 
 #[relm4::component(pub)]
@@ -67,15 +67,20 @@ impl Component for App {
         match message {
             AppInput::Quit => relm4::main_application().quit(),
             AppInput::Unlocked(gn) => {
-                let main = Main::builder().launch(gn).detach();
+                self.settings.emit(SettingsInput::Unlocked(gn.clone()));
+                let main =
+                    Main::builder()
+                        .launch(gn)
+                        .forward(sender.input_sender(), |out| match out {
+                            MainOutput::ShowRelaySettings => AppInput::ShowRelaySettings,
+                            MainOutput::ShowIdentities => AppInput::ShowIdentities,
+                        });
                 widgets.stack.add_named(main.widget(), Some("main"));
                 self.main = Some(main);
                 widgets.stack.set_visible_child_name("main");
             }
-            // AppInput::ShowSettings => self
-                // .settings
-                // .emit(SettingsInput::Show(self.gnostique.identities())),
-
+            AppInput::ShowRelaySettings => self.settings.emit(SettingsInput::ShowRelays),
+            AppInput::ShowIdentities => self.settings.emit(SettingsInput::ShowIdentities),
         }
 
         self.update_view(widgets, sender);
@@ -6,20 +6,28 @@ use std::sync::Arc;
 use gtk::gdk;
 use nostr_sdk::nostr::secp256k1::XOnlyPublicKey;
 use nostr_sdk::nostr::EventId;
-use nostr_sdk::Url;
+use nostr_sdk::{Timestamp, Url};
 use relm4::factory::FactoryVecDeque;
 use relm4::prelude::*;
 use tracing::trace;
 
+use crate::config::LanePreset;
+use crate::follow::Follow;
 use crate::nostr::content::DynamicContent;
 use crate::nostr::preview::Preview;
+use crate::nostr::reaction::Reaction;
 use crate::nostr::subscriptions::Subscription;
 use crate::nostr::{EventRef, Persona, Repost, TextNote};
+use crate::store::Page;
 use crate::ui::details::Details;
 use crate::ui::lane_header::LaneHeader;
 use crate::ui::link::InternalLink;
-use crate::ui::note::{Note, NoteInit};
+use crate::ui::note::{Note, NoteInit, Reacting};
 use crate::ui::profilebox::model::Profilebox;
+use crate::ui::writenote::model::ReplyTo;
+
+/// Number of notes held by a lane unless told otherwise.
+const DEFAULT_WINDOW: usize = 10;
 
 #[derive(Debug)]
 pub struct Lane {
@@ -33,6 +41,22 @@ pub struct Lane {
     /// it will be focused.
     pub(super) focused: Option<EventId>,
 
+    /// Width of the lane in pixels.
+    pub(super) width: i32,
+
+    /// Maximum number of notes held in the lane.
+    pub(super) window: usize,
+
+    /// Whether the lane shows the newest notes. Newly arriving notes
+    /// are only added to the lane if it does.
+    pub(super) live: bool,
+
+    /// Direction in which the user last asked for more notes.
+    pub(super) scroll: Scroll,
+
+    /// Whether a page of notes is being loaded.
+    pub(super) loading: bool,
+
     /// Dynamic index of this lane.
     pub(super) index: DynamicIndex,
 
@@ -41,6 +65,9 @@ pub struct Lane {
 
     pub(super) hash_index: HashMap<EventId, DynamicIndex>,
 
+    /// Profiles matching search of the lane, if it searches.
+    pub(super) found_profiles: Vec<Persona>,
+
     /// Component of profile box; exists only when the lane
     /// is of kind Profile.
     pub(super) profile_box: Option<Controller<Profilebox>>,
@@ -51,10 +78,23 @@ pub struct Lane {
 /// Initial object of a new lane.
 pub struct LaneInit {
     /// Initial subscription of the lane.
-    pub(super) subscription: Subscription,
+    pub subscription: Subscription,
 
     /// Focused event, if any.
     pub(super) focused: Option<EventId>,
+
+    /// Width of the lane, if it is not to be the default one.
+    pub(super) width: Option<i32>,
+
+    /// Maximum number of notes held in the lane.
+    pub(super) window: usize,
+}
+
+/// Direction of scrolling through history of a lane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scroll {
+    Newer,
+    Older,
 }
 
 impl LaneInit {
@@ -64,6 +104,8 @@ impl LaneInit {
         LaneInit {
             subscription,
             focused: None,
+            width: None,
+            window: DEFAULT_WINDOW,
         }
     }
 
@@ -71,6 +113,29 @@ impl LaneInit {
         LaneInit {
             subscription,
             focused: Some(focused),
+            width: None,
+            window: DEFAULT_WINDOW,
+        }
+    }
+
+    /// Sets width of the lane.
+    pub fn width(self, width: i32) -> LaneInit {
+        LaneInit {
+            width: Some(width),
+            ..self
+        }
+    }
+
+    /// Sets maximum number of notes held in the lane.
+    pub fn window(self, window: usize) -> LaneInit {
+        LaneInit { window, ..self }
+    }
+
+    /// Sets width of the lane unless it has been set already.
+    pub fn default_width(self, width: i32) -> LaneInit {
+        LaneInit {
+            width: self.width.or(Some(width)),
+            ..self
         }
     }
 }
@@ -97,10 +162,46 @@ pub enum LaneMsg {
     },
     Reaction {
         event: EventId,
-        reaction: String,
+        reaction: Reaction,
+        /// The reaction is by the current identity.
+        own: bool,
+        emoji: Option<Arc<gdk::Texture>>,
+    },
+    /// Author asked to delete their events of `ids`.
+    Deleted {
+        author: XOnlyPublicKey,
+        ids: Vec<EventId>,
     },
     Nip05Verified(XOnlyPublicKey),
     LinkClicked(InternalLink),
+    /// User wants to reply to a note.
+    Reply(ReplyTo),
+    /// User reacts to a note.
+    Reacting(Reacting),
+    /// Contact list of the current identity has changed.
+    FollowChanged(Follow),
+    /// Lane presets have changed.
+    Presets(Vec<LanePreset>),
+    /// Names of all identities, index of the current one
+    /// and whether it is read-only.
+    Identities(Vec<String>, usize, bool),
+    /// Change width of the lane by given number of pixels.
+    Resize(i32),
+    OpenLane(Subscription),
+    SavePreset(LanePreset),
+    /// User scrolled to the bottom of the lane.
+    LoadOlder,
+    /// User scrolled to the top of the lane.
+    LoadNewer,
+    /// Profiles matching search of the lane have been found.
+    FoundProfiles(Vec<Persona>),
+    /// All stored notes of the requested page have been sent, `complete`
+    /// tells whether there are no more of them.
+    PageLoaded {
+        complete: bool,
+    },
+    /// User wants to export stored events of the lane.
+    Export,
     CloseLane,
 }
 
@@ -112,7 +213,19 @@ pub enum LaneOutput {
     // DemandTextNote(EventRef),
     CloseLane(DynamicIndex),
     LinkClicked(InternalLink),
+    Reply(ReplyTo),
+    Reacting(Reacting),
     SubscriptionsChanged,
+    /// Follow (`true`) or unfollow (`false`) an author.
+    Follow(XOnlyPublicKey, bool),
+    OpenLane(Subscription),
+    SavePreset(LanePreset),
+    /// Width of the lane has changed.
+    LayoutChanged,
+    /// Lane needs a page of notes matching the subscription.
+    LoadPage(DynamicIndex, Subscription, Page),
+    /// Stored events matching the subscription are to be exported.
+    Export(Subscription),
 }
 
 impl Lane {
@@ -127,12 +240,25 @@ impl Lane {
         referenced_profiles: HashSet<Persona>,
     ) {
         let event_id = note.event().id;
+        let event_time = note.event().created_at;
+
+        // Threads are shown whole, other lanes are timelines of which
+        // only a window is held.
+        let subscribes_events = !self.subscription.events().is_empty();
+
+        // Timeline scrolled back in history does not show new notes,
+        // unless they are being loaded.
+        if !subscribes_events && !self.live && !(self.loading && self.scroll == Scroll::Newer) {
+            let newest = self.text_notes.iter().next().map(|n| n.time.timestamp());
+            if newest.map_or(false, |t| event_time.as_i64() > t) {
+                return;
+            }
+        }
 
         // Add note iff it has not been added yet (they may arrive multiple times).
         if let Entry::Vacant(e) = self.hash_index.entry(event_id) {
             let is_central = self.focused == Some(event_id);
             let is_profile = self.subscription.is_a_profile();
-            let event_time = note.event().created_at;
 
             let init = NoteInit {
                 note,
@@ -151,7 +277,6 @@ impl Lane {
             } else {
                 // Find index of first text note that was created later
                 // than the text note being inserted.
-                let subscribes_events = !self.subscription.events().is_empty();
                 let idx = self.text_notes.iter().position(|tn| {
                     let ord = tn.time.timestamp().cmp(&event_time.as_i64());
                     ord == if subscribes_events {
@@ -174,28 +299,104 @@ impl Lane {
             e.insert(di);
         }
 
-        // Remove oldest notes if there are too many already.
-        //
-        // TODO: The maximum number of notes to show should be configurable.
-        {
-            let mut g = self.text_notes.guard();
-
-            // if g.len() > 10 {
-            //     trace!(
-            //         "Lane {:?} has {} notes, removing some.",
-            //         self.subscription(),
-            //         g.len()
-            //     );
-            // };
-
-            while g.len() > 10 {
-                let _ = g.pop_back();
+        // Remove notes if there are too many already, from the end
+        // the user is scrolling away from.
+        let mut g = self.text_notes.guard();
+        while g.len() > self.window {
+            let evicted = if !subscribes_events && self.scroll == Scroll::Older {
+                self.live = false;
+                g.pop_front()
+            } else {
+                g.pop_back()
+            };
+
+            if let Some(note) = evicted {
+                trace!(
+                    "Lane {} evicts note {}.",
+                    self.subscription.to_string(),
+                    note.id()
+                );
+                self.hash_index.remove(&note.id());
             }
         }
     }
 
+    /// Asks for a page of notes older than the oldest one in the lane.
+    pub(super) fn load_older(&mut self) -> Option<LaneOutput> {
+        if self.loading || !self.subscription.events().is_empty() {
+            return None;
+        }
+
+        let until = self
+            .text_notes
+            .iter()
+            .last()
+            .map(|n| Timestamp::from(n.time.timestamp() as u64))
+            .unwrap_or_else(Timestamp::now);
+
+        self.loading = true;
+        self.scroll = Scroll::Older;
+
+        Some(LaneOutput::LoadPage(
+            self.index.clone(),
+            self.subscription.clone(),
+            Page::Older(until),
+        ))
+    }
+
+    /// Asks for a page of notes newer than the newest one in the lane,
+    /// unless the lane already shows the newest notes.
+    pub(super) fn load_newer(&mut self) -> Option<LaneOutput> {
+        if self.loading || self.live || !self.subscription.events().is_empty() {
+            return None;
+        }
+
+        let since = self
+            .text_notes
+            .iter()
+            .next()
+            .map(|n| Timestamp::from(n.time.timestamp() as u64))?;
+
+        self.loading = true;
+        self.scroll = Scroll::Newer;
+
+        Some(LaneOutput::LoadPage(
+            self.index.clone(),
+            self.subscription.clone(),
+            Page::Newer(since),
+        ))
+    }
+
     /// Returns a subscription of this lane, if it exists.
+    /// Links to found profiles, as markup.
+    pub(super) fn found_profiles_markup(&self) -> String {
+        let links = self
+            .found_profiles
+            .iter()
+            .map(|p| {
+                let name = p.show_name().unwrap_or_else(|| p.short_bech32(16));
+                format!(
+                    r#"<a href="gnostique:search?pubkey={}">{}</a>"#,
+                    p.pubkey,
+                    html_escape::encode_text(&name)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!("Profiles: {}", links.join(", "))
+    }
+
     pub fn subscription(&self) -> &Subscription {
         &self.subscription
     }
+
+    /// Event the lane is focused on, if any.
+    pub fn focused(&self) -> Option<EventId> {
+        self.focused
+    }
+
+    /// Width of the lane in pixels.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
 }
@@ -7,11 +7,18 @@ use crate::nostr::subscriptions::Subscription;
 use crate::nostr::{EventExt, Persona};
 use crate::ui::lane::model::*;
 use crate::ui::lane_header::{LaneHeader, LaneHeaderInput, LaneHeaderOutput};
+use crate::ui::link::InternalLink;
 use crate::ui::main::MainInput;
 use crate::ui::note::{NoteInput, NoteOutput};
 use crate::ui::profilebox;
 use crate::ui::profilebox::model::Profilebox;
 
+/// Width of a lane unless told otherwise.
+const DEFAULT_WIDTH: i32 = 600;
+
+/// Lane cannot be made narrower than this.
+const MIN_WIDTH: i32 = 200;
+
 #[relm4::factory(pub async)]
 impl AsyncFactoryComponent for Lane {
     type Init = LaneInit;
@@ -31,11 +38,33 @@ impl AsyncFactoryComponent for Lane {
 
             // PROFILE BOX (before text notes)
 
+            // PROFILES FOUND BY SEARCH
+            gtk::Label {
+                add_css_class: "found-profiles",
+                set_wrap: true,
+                set_xalign: 0.0,
+                #[watch] set_visible: !self.found_profiles.is_empty(),
+                #[watch] set_markup: &self.found_profiles_markup(),
+
+                connect_activate_link[sender] => move |_, uri| {
+                    if let Some(link) = InternalLink::from_url_str(uri) {
+                        sender.input(LaneMsg::LinkClicked(link));
+                        gtk::glib::Propagation::Proceed
+                    } else { gtk::glib::Propagation::Stop }
+                }
+            },
+
             // TEXT NOTES
             #[name = "text_notes"]
             gtk::ScrolledWindow {
                 set_hscrollbar_policy: gtk::PolicyType::Never,
-                set_min_content_width: 600,
+                #[watch]
+                set_min_content_width: self.width,
+                connect_edge_reached[sender] => move |_, position| match position {
+                    gtk::PositionType::Bottom => sender.input(LaneMsg::LoadOlder),
+                    gtk::PositionType::Top => sender.input(LaneMsg::LoadNewer),
+                    _ => {}
+                },
                 set_hexpand: true,
                 set_vexpand: true,
                 #[wrap(Some)]
@@ -52,27 +81,37 @@ impl AsyncFactoryComponent for Lane {
         let LaneInit {
             subscription,
             focused,
+            width,
+            window,
         } = init;
         let profile_box = if let Subscription::Profile(pubkey, relays) = &subscription {
             // Since persona does not include avatar bitmap, it has to be obtained
             // from outside. Once #0464b5d7fa3bbbad is solved, this should not be
             // needed anymore.
             sender.output(LaneOutput::DemandProfile(*pubkey, relays.clone()));
-            Some(Profilebox::builder().launch(*pubkey).detach())
+            let pubkey = *pubkey;
+            Some(
+                Profilebox::builder()
+                    .launch(pubkey)
+                    .forward(sender.output_sender(), move |out| match out {
+                        profilebox::Output::Follow(follow) => LaneOutput::Follow(pubkey, follow),
+                    }),
+            )
         } else {
             None
         };
 
         // Each lane has a header.
-        let header = {
-            let index = index.clone();
-            LaneHeader::builder().launch(subscription.clone()).forward(
-                sender.output_sender(),
-                move |out| match out {
-                    LaneHeaderOutput::CloseLane => LaneOutput::CloseLane(index.clone()),
-                },
-            )
-        };
+        let header = LaneHeader::builder().launch(subscription.clone()).forward(
+            sender.input_sender(),
+            |out| match out {
+                LaneHeaderOutput::CloseLane => LaneMsg::CloseLane,
+                LaneHeaderOutput::OpenLane(subscription) => LaneMsg::OpenLane(subscription),
+                LaneHeaderOutput::SavePreset(preset) => LaneMsg::SavePreset(preset),
+                LaneHeaderOutput::Resize(by) => LaneMsg::Resize(by),
+                LaneHeaderOutput::Export => LaneMsg::Export,
+            },
+        );
 
         let text_notes = FactoryVecDeque::builder(
             gtk::ListBox::builder()
@@ -83,6 +122,8 @@ impl AsyncFactoryComponent for Lane {
         .forward(sender.input_sender(), |msg| match msg {
             NoteOutput::ShowDetails(details) => LaneMsg::ShowDetails(details),
             NoteOutput::LinkClicked(link) => LaneMsg::LinkClicked(link),
+            NoteOutput::Reply(reply_to) => LaneMsg::Reply(reply_to),
+            NoteOutput::Reacting(reacting) => LaneMsg::Reacting(reacting),
         });
 
         // When a new lane is opened, it is passed a subscription, however Nostr client
@@ -93,6 +134,12 @@ impl AsyncFactoryComponent for Lane {
         Self {
             subscription,
             focused,
+            width: width.unwrap_or(DEFAULT_WIDTH),
+            window,
+            live: true,
+            scroll: Scroll::Newer,
+            loading: false,
+            found_profiles: vec![],
             profile_box,
             index: index.clone(),
             header,
@@ -132,7 +179,17 @@ impl AsyncFactoryComponent for Lane {
             }
             LaneOutput::CloseLane(id) => Some(MainInput::CloseLane(id)),
             LaneOutput::LinkClicked(link) => Some(MainInput::LinkClicked(link)),
+            LaneOutput::Reply(reply_to) => Some(MainInput::Reply(reply_to)),
+            LaneOutput::Reacting(reacting) => Some(MainInput::Reacting(reacting)),
             LaneOutput::SubscriptionsChanged => Some(MainInput::RefreshSubscriptions),
+            LaneOutput::Follow(pubkey, follow) => Some(MainInput::Follow(pubkey, follow)),
+            LaneOutput::OpenLane(subscription) => Some(MainInput::OpenLane(subscription)),
+            LaneOutput::SavePreset(preset) => Some(MainInput::SavePreset(preset)),
+            LaneOutput::LayoutChanged => Some(MainInput::SaveLanes),
+            LaneOutput::Export(subscription) => Some(MainInput::Export(subscription)),
+            LaneOutput::LoadPage(index, subscription, page) => {
+                Some(MainInput::LoadPage(index, subscription, page))
+            }
         }
     }
 
@@ -183,9 +240,70 @@ impl AsyncFactoryComponent for Lane {
                 });
             }
 
-            LaneMsg::Reaction { event, reaction } => self
+            LaneMsg::Reaction {
+                event,
+                reaction,
+                own,
+                emoji,
+            } => self.text_notes.broadcast(NoteInput::Reaction {
+                event,
+                reaction,
+                own,
+                emoji,
+            }),
+
+            LaneMsg::Deleted { author, ids } => self
                 .text_notes
-                .broadcast(NoteInput::Reaction { event, reaction }),
+                .broadcast(NoteInput::Deleted { author, ids }),
+
+            LaneMsg::FollowChanged(follow) => {
+                // A newly opened lane is told the current follow list, which it
+                // usually has already, so it resubscribes only on actual change.
+                if self.subscription.is_following() {
+                    let subscription = self.subscription.clone().with_follow(&follow);
+                    if subscription != self.subscription {
+                        self.subscription = subscription;
+                        sender.output(LaneOutput::SubscriptionsChanged);
+                    }
+                }
+
+                if let Some(p) = &self.profile_box {
+                    let following = follow.follows(&p.model().author.pubkey);
+                    p.emit(profilebox::Input::Following(following));
+                }
+            }
+
+            LaneMsg::Presets(presets) => self.header.emit(LaneHeaderInput::Presets(presets)),
+
+            LaneMsg::Identities(names, current, read_only) => self
+                .header
+                .emit(LaneHeaderInput::Identities(names, current, read_only)),
+
+            LaneMsg::LoadOlder => {
+                if let Some(out) = self.load_older() {
+                    sender.output(out);
+                }
+            }
+
+            LaneMsg::LoadNewer => {
+                if let Some(out) = self.load_newer() {
+                    sender.output(out);
+                }
+            }
+
+            LaneMsg::FoundProfiles(profiles) => self.found_profiles = profiles,
+
+            LaneMsg::PageLoaded { complete } => {
+                self.loading = false;
+                if complete && self.scroll == Scroll::Newer {
+                    self.live = true;
+                }
+            }
+
+            LaneMsg::Resize(by) => {
+                self.width = (self.width + by).max(MIN_WIDTH);
+                sender.output(LaneOutput::LayoutChanged);
+            }
 
             LaneMsg::Nip05Verified(pubkey) => {
                 self.text_notes.broadcast(NoteInput::Nip05Verified(pubkey))
@@ -253,7 +371,12 @@ impl AsyncFactoryComponent for Lane {
                 }
             }
             LaneMsg::LinkClicked(uri) => sender.output(LaneOutput::LinkClicked(uri)),
+            LaneMsg::Reply(reply_to) => sender.output(LaneOutput::Reply(reply_to)),
+            LaneMsg::Reacting(reacting) => sender.output(LaneOutput::Reacting(reacting)),
             LaneMsg::CloseLane => sender.output(LaneOutput::CloseLane(self.index.clone())),
+            LaneMsg::OpenLane(subscription) => sender.output(LaneOutput::OpenLane(subscription)),
+            LaneMsg::SavePreset(preset) => sender.output(LaneOutput::SavePreset(preset)),
+            LaneMsg::Export => sender.output(LaneOutput::Export(self.subscription.clone())),
         }
     }
 }
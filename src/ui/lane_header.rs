@@ -1,22 +1,71 @@
+use gtk::gio;
 use gtk::prelude::*;
+use relm4::actions::RelmAction;
 use relm4::*;
 
-use crate::app::action::EditProfile;
+use crate::app::action::{
+    CompactDatabase, EditProfile, ExportEvents, ImportEvents, ManageIdentities, OpenSink,
+    SwitchIdentity,
+};
+use crate::config::LanePreset;
 use crate::nostr::subscriptions::Subscription;
 
+/// How many pixels a lane is resized by at once.
+const RESIZE_STEP: i32 = 100;
+
 #[derive(Debug)]
 pub struct LaneHeader {
     title: String,
+
+    /// Query of a new lane.
+    query: gtk::EntryBuffer,
+
+    /// Name under which the query is to be saved as a preset.
+    preset_name: gtk::EntryBuffer,
+
+    /// Problem with the query, if any.
+    error: Option<String>,
+
+    presets: Vec<LanePreset>,
+
+    /// Names of presets for choosing.
+    preset_names: gtk::StringList,
+
+    /// Index of the chosen preset.
+    selected_preset: u32,
+
+    /// Name of the current identity.
+    identity: String,
+
+    /// Current identity cannot publish.
+    read_only: bool,
+
+    /// Menu items switching to each of the identities.
+    identities: gio::Menu,
 }
 
 #[derive(Clone, Debug)]
 pub enum LaneHeaderOutput {
     CloseLane,
+    OpenLane(Subscription),
+    SavePreset(LanePreset),
+    /// Make the lane wider (positive) or narrower (negative) by given pixels.
+    Resize(i32),
+    /// Export stored events of the lane.
+    Export,
 }
 
 #[derive(Clone, Debug)]
 pub enum LaneHeaderInput {
     ChangeTitle(String),
+    Presets(Vec<LanePreset>),
+    OpenQuery,
+    SavePreset,
+    SelectPreset(u32),
+    OpenPreset,
+    /// Names of all identities, index of the current one
+    /// and whether it is read-only.
+    Identities(Vec<String>, usize, bool),
 }
 
 #[relm4::component(pub)]
@@ -34,8 +83,68 @@ impl SimpleComponent for LaneHeader {
             set_start_widget = &gtk::Box {
                 gtk::Button::from_icon_name("mail-message-new-symbolic") {
                     set_has_frame: false,
+                    #[watch] set_sensitive: !model.read_only,
                     set_tooltip_text: Some("Write new text note with the current identity"),
                     // connect_clicked[sender] => move |_| { sender.output(()).unwrap() }
+                },
+                gtk::MenuButton {
+                    set_icon_name: "list-add-symbolic",
+                    set_has_frame: false,
+                    set_tooltip_text: Some("Open new lane"),
+
+                    #[wrap(Some)]
+                    set_popover = &gtk::Popover {
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 8,
+
+                            gtk::Entry {
+                                set_buffer: &model.query,
+                                set_width_chars: 40,
+                                set_placeholder_text: Some("#nostr & kind:1 & since:2d"),
+                                connect_activate => LaneHeaderInput::OpenQuery,
+                            },
+
+                            gtk::Label {
+                                add_css_class: "error",
+                                set_xalign: 0.0,
+                                #[watch] set_visible: model.error.is_some(),
+                                #[watch] set_label: model.error.as_deref().unwrap_or_default(),
+                            },
+
+                            gtk::Box {
+                                set_spacing: 8,
+
+                                gtk::Entry {
+                                    set_buffer: &model.preset_name,
+                                    set_hexpand: true,
+                                    set_placeholder_text: Some("Preset name"),
+                                    connect_activate => LaneHeaderInput::SavePreset,
+                                },
+
+                                gtk::Button::with_label("Save preset") {
+                                    connect_clicked => LaneHeaderInput::SavePreset,
+                                }
+                            },
+
+                            gtk::Box {
+                                set_spacing: 8,
+                                #[watch] set_visible: !model.presets.is_empty(),
+
+                                gtk::DropDown {
+                                    set_hexpand: true,
+                                    set_model: Some(&model.preset_names),
+                                    connect_selected_notify[sender] => move |d| {
+                                        sender.input(LaneHeaderInput::SelectPreset(d.selected()))
+                                    },
+                                },
+
+                                gtk::Button::with_label("Open") {
+                                    connect_clicked => LaneHeaderInput::OpenPreset,
+                                }
+                            }
+                        }
+                    }
                 }
             },
 
@@ -48,19 +157,45 @@ impl SimpleComponent for LaneHeader {
                     add_css_class: "name"
                 },
                 gtk::Label {
-                    set_text: "Main identity",
+                    #[watch] set_text: &model.identity,
+                    #[watch] set_tooltip_text: model.read_only.then_some("Read-only identity cannot publish"),
                     add_css_class: "identity"
                 }
             },
 
             #[wrap(Some)]
             set_end_widget = &gtk::Box {
+                gtk::Button::from_icon_name("zoom-out-symbolic") {
+                    set_has_frame: false,
+                    set_tooltip_text: Some("Make this lane narrower"),
+                    connect_clicked[sender] => move |_| {
+                        sender.output(LaneHeaderOutput::Resize(-RESIZE_STEP)).unwrap_or_default()
+                    }
+                },
+                gtk::Button::from_icon_name("zoom-in-symbolic") {
+                    set_has_frame: false,
+                    set_tooltip_text: Some("Make this lane wider"),
+                    connect_clicked[sender] => move |_| {
+                        sender.output(LaneHeaderOutput::Resize(RESIZE_STEP)).unwrap_or_default()
+                    }
+                },
+                gtk::Button::from_icon_name("document-save-symbolic") {
+                    set_has_frame: false,
+                    set_tooltip_text: Some("Export stored events of this lane"),
+                    connect_clicked[sender] => move |_| {
+                        sender.output(LaneHeaderOutput::Export).unwrap_or_default()
+                    }
+                },
                 gtk::Button::from_icon_name("open-menu-symbolic") {
                     set_has_frame: false,
                     set_tooltip_text: Some("Open menu to see list of actions"),
-                    connect_clicked => move |b| {
+                    connect_clicked[identities = model.identities.clone()] => move |b| {
+                        let menu = gio::Menu::new();
+                        menu.append_section(None, &main_menu);
+                        menu.append_section(Some("Switch identity"), &identities);
+
                         let popover = gtk::PopoverMenu::builder()
-                            .menu_model(&main_menu)
+                            .menu_model(&menu)
                             .has_arrow(false)
                             .build();
                         popover.set_parent(b);
@@ -80,7 +215,16 @@ impl SimpleComponent for LaneHeader {
 
     menu! {
         main_menu: {
-            "Edit profile" => EditProfile
+            section! {
+                "Edit profile" => EditProfile,
+                "Manage identities" => ManageIdentities,
+                "Open sink (debugging)" => OpenSink,
+            },
+            section! {
+                "Export events…" => ExportEvents,
+                "Import events…" => ImportEvents,
+                "Compact database" => CompactDatabase,
+            }
         }
     }
 
@@ -89,18 +233,104 @@ impl SimpleComponent for LaneHeader {
         _root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let title = "TODO".to_string();
+        let title = init.to_string();
 
-        let model = LaneHeader { title };
+        let model = LaneHeader {
+            title,
+            query: Default::default(),
+            preset_name: Default::default(),
+            error: None,
+            presets: vec![],
+            preset_names: gtk::StringList::new(&[]),
+            selected_preset: 0,
+            identity: String::new(),
+            read_only: false,
+            identities: gio::Menu::new(),
+        };
 
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
         match message {
             LaneHeaderInput::ChangeTitle(title) => self.title = title,
+
+            LaneHeaderInput::Presets(presets) => {
+                let names = presets.iter().map(|p| p.name.as_str()).collect::<Vec<_>>();
+                self.preset_names
+                    .splice(0, self.preset_names.n_items(), &names);
+                self.presets = presets;
+            }
+
+            LaneHeaderInput::OpenQuery => match self.query.text().parse::<Subscription>() {
+                Ok(subscription) => {
+                    self.error = None;
+                    self.query.set_text("");
+                    sender
+                        .output(LaneHeaderOutput::OpenLane(subscription))
+                        .unwrap_or_default();
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            },
+
+            LaneHeaderInput::SavePreset => {
+                let name = self.preset_name.text().trim().to_string();
+                let query = self.query.text().trim().to_string();
+
+                if name.is_empty() {
+                    self.error = Some("Preset has to have a name.".to_string());
+                } else if let Err(e) = query.parse::<Subscription>() {
+                    self.error = Some(e.to_string());
+                } else {
+                    self.error = None;
+                    self.preset_name.set_text("");
+                    sender
+                        .output(LaneHeaderOutput::SavePreset(LanePreset { name, query }))
+                        .unwrap_or_default();
+                }
+            }
+
+            LaneHeaderInput::SelectPreset(index) => self.selected_preset = index,
+
+            LaneHeaderInput::Identities(names, current, read_only) => {
+                self.identities.remove_all();
+                for (index, name) in names.iter().enumerate() {
+                    let label = if index == current {
+                        format!("✓ {name}")
+                    } else {
+                        name.clone()
+                    };
+                    self.identities.append_item(
+                        &RelmAction::<SwitchIdentity>::to_menu_item_with_target_value(
+                            &label,
+                            &(index as u32),
+                        ),
+                    );
+                }
+                let name = names.get(current).cloned().unwrap_or_default();
+                self.identity = if read_only {
+                    format!("{name} (read-only)")
+                } else {
+                    name
+                };
+                self.read_only = read_only;
+            }
+
+            LaneHeaderInput::OpenPreset => {
+                if let Some(preset) = self.presets.get(self.selected_preset as usize) {
+                    match preset.query.parse::<Subscription>() {
+                        Ok(subscription) => {
+                            self.error = None;
+                            sender
+                                .output(LaneHeaderOutput::OpenLane(subscription))
+                                .unwrap_or_default();
+                        }
+                        Err(e) => self.error = Some(format!("Preset '{}': {e}", preset.name)),
+                    }
+                }
+            }
         }
     }
 }
@@ -12,12 +12,15 @@ use crate::nostr::{EventRef, Persona};
 /// - gnostique:search?tag=TAG
 /// - gnostique:search?pubkey=PUBKEY&relay=RELAY1&relay=RELAY2
 /// - gnostique:search?event=EVENTID&relay=RELAY1&relay=RELAY2
+/// - gnostique:search?text=TEXT
 // TODO: Make this comment documenting again.
 #[derive(Debug, Clone)]
 pub enum InternalLink {
     Tag(String),
     Profile(Arc<Persona>, Vec<Url>),
     Event(EventRef),
+    /// Full-text search.
+    Search(String),
 }
 
 impl InternalLink {
@@ -47,6 +50,7 @@ impl InternalLink {
                     .ok()
                     .map(|event_id| Self::event(event_id, relays())),
                 "tag" => Some(InternalLink::Tag(v.clone().into_owned())),
+                "text" => Some(InternalLink::Search(v.clone().into_owned())),
                 _ => None,
             })
         }
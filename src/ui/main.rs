@@ -1,24 +1,37 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use gtk::gdk;
 use gtk::prelude::*;
+use gtk::{gdk, gio};
 use nostr_sdk::nostr::prelude::*;
+use relm4::actions::ActionName;
 use relm4::component::*;
 use relm4::factory::AsyncFactoryVecDeque;
 use relm4::prelude::DynamicIndex;
 use tracing::warn;
 
 use super::link::InternalLink;
+use crate::app::action;
+use crate::config::LanePreset;
 use crate::gnostique::Gnostique;
-use crate::incoming::Incoming;
+use crate::incoming::{stored_text_notes, Incoming};
+use crate::nostr::compose::content_tags;
+use crate::nostr::reaction::{self, Reaction};
 use crate::nostr::subscriptions::Subscription;
+use crate::nostr::{quote, reply_tags, repost, EventExt, Persona};
+use crate::relays::is_relay;
+use crate::store::archive::{self, Imported};
+use crate::store::{Page, SavedLane};
 use crate::ui::details::*;
 use crate::ui::editprofile::model::*;
 use crate::ui::lane::*;
+use crate::ui::note::Reacting;
 use crate::ui::statusbar::*;
 use crate::ui::writenote::model::*;
 
+/// Number of profiles shown by a search lane.
+const FOUND_PROFILES: usize = 10;
+
 pub struct Main {
     gnostique: Gnostique,
     lanes: AsyncFactoryVecDeque<Lane>,
@@ -26,6 +39,18 @@ pub struct Main {
     status_bar: Controller<StatusBar>,
     write_note: Controller<WriteNote>,
     edit_profile: Controller<EditProfile>,
+
+    /// File chooser of export or import, kept while it is open.
+    archive_dialog: Option<gtk::FileChooserNative>,
+
+    /// Actions of the main menu.
+    main_actions: gio::SimpleActionGroup,
+
+    /// Actions performed on authors.
+    author_actions: gio::SimpleActionGroup,
+
+    /// Actions performed on notes.
+    note_actions: gio::SimpleActionGroup,
 }
 
 #[derive(Debug)]
@@ -35,7 +60,14 @@ pub enum MainInput {
     WriteNote,
     EditProfile,
     UpdateProfile(Metadata),
-    Send(String),
+    /// Open composer of a reply to a note.
+    Reply(ReplyTo),
+    Reacting(Reacting),
+    Send {
+        content: String,
+        reply_to: Option<ReplyTo>,
+        tags: Vec<Tag>,
+    },
     Noop,
     MetadataBitmap {
         pubkey: XOnlyPublicKey,
@@ -47,13 +79,52 @@ pub enum MainInput {
     CloseLane(DynamicIndex),
     LinkClicked(InternalLink),
     RefreshSubscriptions,
+    ShowRelaySettings,
+    /// Follow (`true`) or unfollow (`false`) an author.
+    Follow(XOnlyPublicKey, bool),
+    /// Open lane with everything relays send.
+    OpenSink,
+    OpenLane(Subscription),
+    SavePreset(LanePreset),
+    /// Remember open lanes so that they can be restored on next start.
+    SaveLanes,
+    /// Load a page of notes of `subscription` into lane at the index.
+    LoadPage(DynamicIndex, Subscription, Page),
+    /// Bookmark (`true`) or remove bookmark of (`false`) an event.
+    Bookmark(EventId, bool),
+    /// Repost stored event.
+    Repost(EventId),
+    /// Open composer of a note quoting stored event.
+    Quote(EventId),
+    /// Give space of deleted data in database back.
+    CompactDatabase,
+    /// Let user choose file to export stored events matching `subscription` to.
+    Export(Subscription),
+    /// Let user choose file to import events from.
+    Import,
+    /// Export stored events matching `subscription` to the file.
+    ExportTo(Subscription, PathBuf),
+    /// Import events from the file.
+    ImportFrom(PathBuf),
+    /// Make identity with the index the current one.
+    SwitchIdentity(usize),
+    /// Open settings of identities.
+    ShowIdentities,
+}
+
+#[derive(Debug)]
+pub enum MainOutput {
+    /// User wants to manage relays.
+    ShowRelaySettings,
+    /// User wants to manage identities.
+    ShowIdentities,
 }
 
 #[relm4::component(pub async)]
 impl AsyncComponent for Main {
     type Init = Gnostique;
     type Input = MainInput;
-    type Output = ();
+    type Output = MainOutput;
     type CommandOutput = ();
 
     #[rustfmt::skip]
@@ -86,39 +157,79 @@ impl AsyncComponent for Main {
             sender.clone(),
         ));
 
+        relm4::spawn(crate::app::task::prune(gnostique.clone()));
+
         let mut model = Main {
             gnostique: gnostique.clone(),
             lanes: AsyncFactoryVecDeque::new(gtk::Box::default(), sender.input_sender()),
             details: DetailsWindow::builder().launch(()).detach(),
-            status_bar: StatusBar::builder().launch(gnostique).detach(),
+            status_bar: StatusBar::builder().launch(gnostique.clone()).forward(
+                sender.input_sender(),
+                |out| match out {
+                    StatusBarOutput::ShowRelays => MainInput::ShowRelaySettings,
+                },
+            ),
             edit_profile: EditProfile::builder()
                 .launch(())
                 .forward(sender.input_sender(), forward_edit_profile),
-            write_note: WriteNote::builder()
-                .launch(())
-                .forward(sender.input_sender(), |result| match result {
-                    WriteNoteResult::Send(c) => MainInput::Send(c),
+            write_note: WriteNote::builder().launch(gnostique.clone()).forward(
+                sender.input_sender(),
+                |result| match result {
+                    WriteNoteResult::Send {
+                        content,
+                        reply_to,
+                        tags,
+                    } => MainInput::Send {
+                        content,
+                        reply_to,
+                        tags,
+                    },
                     _ => MainInput::Noop,
-                }),
+                },
+            ),
+            archive_dialog: None,
+            main_actions: action::make_main_menu_actions(sender.clone()),
+            author_actions: action::make_author_actions(sender.clone()),
+            note_actions: action::make_note_actions(sender.clone()),
         };
+        model.enable_publishing();
 
         let lanes_box = model.lanes.widget();
         let status_bar = model.status_bar.widget();
         let widgets = view_output!();
 
-        model
-            .lanes
-            .guard()
-            .push_back(LaneInit::subscription(Subscription::Sink));
+        // Lanes are filled with what is stored before connecting to relays,
+        // so that there is something to read even when they are unreachable.
+        let saved_lanes = gnostique.saved_lanes().await;
+        if saved_lanes.is_empty() {
+            model
+                .open_lane(
+                    LaneInit::subscription(Subscription::Following(gnostique.follow())),
+                    &sender,
+                )
+                .await;
+        }
 
-        // widgets
-        //     .window
-        //     .insert_action_group("author", Some(&crate::app::action::make_author_actions()));
+        for SavedLane {
+            subscription,
+            focused,
+            width,
+        } in saved_lanes
+        {
+            let init = match focused {
+                Some(focused) => LaneInit::with_focused(subscription, focused),
+                None => LaneInit::subscription(subscription),
+            };
+            model.open_lane(init.width(width), &sender).await;
+        }
 
-        // widgets.window.insert_action_group(
-        //     "main",
-        //     Some(&crate::app::action::make_main_menu_actions(sender)),
-        // );
+        root.insert_action_group("author", Some(&model.author_actions));
+
+        root.insert_action_group("note", Some(&model.note_actions));
+
+        root.insert_action_group("main", Some(&model.main_actions));
+
+        relm4::spawn(async move { gnostique.connect().await });
 
         AsyncComponentParts { model, widgets }
     }
@@ -128,7 +239,7 @@ impl AsyncComponent for Main {
         widgets: &mut Self::Widgets,
         msg: Self::Input,
         sender: AsyncComponentSender<Self>,
-        _root: &Self::Root,
+        root: &Self::Root,
     ) {
         match msg {
             MainInput::Incoming(Incoming::TextNote {
@@ -168,11 +279,16 @@ impl AsyncComponent for Main {
                 }
             }
 
-            MainInput::Incoming(Incoming::Reaction { event_id, content }) => {
-                self.lanes.broadcast(LaneMsg::Reaction {
-                    event: event_id,
-                    reaction: content,
-                })
+            MainInput::Incoming(Incoming::Reaction {
+                event_id,
+                reaction,
+                emoji,
+            }) => self
+                .lanes
+                .broadcast(self.reaction_msg(event_id, reaction, emoji)),
+
+            MainInput::Incoming(Incoming::Deleted { author, ids }) => {
+                self.lanes.broadcast(LaneMsg::Deleted { author, ids })
             }
 
             MainInput::Incoming(Incoming::Metadata { persona, avatar }) => {
@@ -203,18 +319,164 @@ impl AsyncComponent for Main {
                 self.lanes.broadcast(LaneMsg::Preview(p));
             }
 
+            MainInput::Incoming(Incoming::RelaysChanged) => {
+                sender.input(MainInput::RefreshSubscriptions);
+            }
+
+            MainInput::Incoming(Incoming::Signer(status)) => {
+                self.status_bar
+                    .emit(StatusBarInput::Message(status.to_string()));
+            }
+
+            MainInput::Incoming(Incoming::IdentitiesChanged) => {
+                self.enable_publishing();
+                self.lanes.broadcast(self.identities_msg());
+            }
+
+            MainInput::Incoming(Incoming::Follow(follow)) => {
+                let authors = follow.pubkeys().copied().collect();
+                self.gnostique.demand().relay_lists(authors).await;
+                self.lanes.broadcast(LaneMsg::FollowChanged(follow));
+            }
+
+            MainInput::Follow(..) if self.refuse_read_only("change whom it follows") => {}
+
+            MainInput::Follow(pubkey, follow) => {
+                let gnostique = self.gnostique.clone();
+                let status_bar = self.status_bar.sender().clone();
+                relm4::spawn(async move {
+                    if let Err(e) = gnostique.set_following(pubkey, follow).await {
+                        warn!("Could not publish contact list: {e}");
+                        status_bar.emit(StatusBarInput::Message(e.to_string()));
+                    }
+                });
+            }
+
+            MainInput::Bookmark(id, bookmarked) => {
+                if let Err(e) = self.gnostique.store().set_bookmark(id, bookmarked).await {
+                    warn!("Could not change bookmark: {e}");
+                }
+            }
+
+            MainInput::CompactDatabase => {
+                let gnostique = self.gnostique.clone();
+                let status_bar = self.status_bar.sender().clone();
+                status_bar.emit(StatusBarInput::Message("Compacting database…".to_string()));
+
+                relm4::spawn(async move {
+                    let message = match gnostique.store().compact().await {
+                        Ok(bytes) => format!(
+                            "Database compacted, {:.1} MB reclaimed.",
+                            bytes as f64 / 1024.0 / 1024.0
+                        ),
+                        Err(e) => format!("Could not compact database: {e}"),
+                    };
+                    status_bar.emit(StatusBarInput::Message(message));
+                });
+            }
+
+            MainInput::SwitchIdentity(index) => {
+                if let Some(follow) = self.gnostique.switch_identity(index).await {
+                    self.enable_publishing();
+
+                    // Lanes of the previous identity make no sense anymore.
+                    self.lanes.guard().clear();
+                    self.open_lane(
+                        LaneInit::subscription(Subscription::Following(follow)),
+                        &sender,
+                    )
+                    .await;
+
+                    let gnostique = self.gnostique.clone();
+                    relm4::spawn(async move { gnostique.request_identity().await });
+
+                    sender.input(MainInput::RefreshSubscriptions);
+                }
+            }
+
+            MainInput::Export(subscription) => {
+                self.choose_archive(root, gtk::FileChooserAction::Save, &sender, move |path| {
+                    MainInput::ExportTo(subscription.clone(), path)
+                });
+            }
+
+            MainInput::Import => {
+                self.choose_archive(root, gtk::FileChooserAction::Open, &sender, |path| {
+                    MainInput::ImportFrom(path)
+                });
+            }
+
+            MainInput::ExportTo(subscription, path) => {
+                let gnostique = self.gnostique.clone();
+                let status_bar = self.status_bar.sender().clone();
+                status_bar.emit(StatusBarInput::Message("Exporting events…".to_string()));
+
+                relm4::spawn(async move {
+                    let message =
+                        match archive::export_file(gnostique.store(), &subscription, &path).await {
+                            Ok(count) => format!("Exported {count} events."),
+                            Err(e) => format!("Could not export events: {e}"),
+                        };
+                    status_bar.emit(StatusBarInput::Message(message));
+                });
+            }
+
+            MainInput::ImportFrom(path) => {
+                let gnostique = self.gnostique.clone();
+                let status_bar = self.status_bar.sender().clone();
+                status_bar.emit(StatusBarInput::Message("Importing events…".to_string()));
+
+                relm4::spawn(async move {
+                    let message = match archive::import_file(gnostique.store(), &path).await {
+                        Ok(Imported { events, invalid: 0 }) => format!("Imported {events} events."),
+                        Ok(Imported { events, invalid }) => {
+                            format!("Imported {events} events, skipped {invalid} invalid ones.")
+                        }
+                        Err(e) => format!("Could not import events: {e}"),
+                    };
+                    status_bar.emit(StatusBarInput::Message(message));
+                });
+            }
+
+            MainInput::ShowRelaySettings => sender
+                .output(MainOutput::ShowRelaySettings)
+                .unwrap_or_default(),
+
+            MainInput::ShowIdentities => sender
+                .output(MainOutput::ShowIdentities)
+                .unwrap_or_default(),
+
+            MainInput::WriteNote if self.refuse_read_only("write notes") => {}
+
             MainInput::WriteNote => self.write_note.emit(WriteNoteInput::Show),
 
+            MainInput::Reply(_) if self.refuse_read_only("reply") => {}
+
+            MainInput::Reply(reply_to) => self.write_note.emit(WriteNoteInput::Reply(reply_to)),
+
             MainInput::CloseLane(id) => {
                 self.lanes.guard().remove(id.current_index());
                 sender.input(MainInput::RefreshSubscriptions);
             }
 
-            MainInput::RefreshSubscriptions => {
-                let client = self.gnostique.client();
-                let relays = client.relays().await;
-                let relays = relays.values();
+            MainInput::SaveLanes => {
+                let lanes = self
+                    .lanes
+                    .iter()
+                    .flatten()
+                    .map(|l| SavedLane {
+                        subscription: l.subscription().clone(),
+                        focused: l.focused(),
+                        width: l.width(),
+                    })
+                    .collect::<Vec<_>>();
+
+                if let Err(e) = self.gnostique.store().save_lanes(&lanes).await {
+                    warn!("Could not save lanes: {e}");
+                }
+            }
 
+            MainInput::RefreshSubscriptions => {
                 let lane_subs = self
                     .lanes
                     .iter()
@@ -223,74 +485,151 @@ impl AsyncComponent for Main {
 
                 tracing::info!("Subscribing to {lane_subs:?}");
 
-                if let Some(subs) = lane_subs {
-                    let sub_filter = subs.to_filters();
-                    for relay in relays {
-                        let _ = relay.subscribe(sub_filter.clone(), None).await;
+                // Subscriptions change when lanes are opened, closed or
+                // extended, which is also when their layout is to be saved.
+                sender.input(MainInput::SaveLanes);
 
-                        let active_subs = relay.subscriptions().await;
-                        tracing::debug!("On {} subscribed to {:#?}", relay.url(), active_subs);
-                    }
+                // Looking up where authors write to takes a while with many of them.
+                if let Some(subs) = lane_subs {
+                    let gnostique = self.gnostique.clone();
+                    relm4::spawn(async move { gnostique.subscribe(subs).await });
                 }
             }
 
+            MainInput::LoadPage(index, subscription, page) => {
+                self.load_page(index.current_index(), &subscription, page, &sender)
+                    .await;
+            }
+
             MainInput::LinkClicked(InternalLink::Tag(tag)) => {
-                self.lanes
-                    .guard()
-                    .push_back(LaneInit::subscription(Subscription::hashtag(tag)));
+                self.open_lane(LaneInit::subscription(Subscription::hashtag(tag)), &sender)
+                    .await;
             }
 
             MainInput::LinkClicked(InternalLink::Event(event)) => {
-                self.lanes.guard().push_back(LaneInit::with_focused(
-                    Subscription::thread(event.id()),
-                    event.id(),
-                ));
+                self.open_lane(
+                    LaneInit::with_focused(Subscription::thread(event.id()), event.id()),
+                    &sender,
+                )
+                .await;
             }
 
             MainInput::LinkClicked(InternalLink::Profile(persona, relays)) => {
-                self.lanes
-                    .guard()
-                    .push_back(LaneInit::subscription(Subscription::profile(
-                        persona.pubkey,
-                        relays,
-                    )));
+                self.open_lane(
+                    LaneInit::subscription(Subscription::profile(persona.pubkey, relays)),
+                    &sender,
+                )
+                .await;
+            }
+
+            MainInput::LinkClicked(InternalLink::Search(text)) => {
+                self.open_lane(LaneInit::subscription(Subscription::search(text)), &sender)
+                    .await;
+            }
+
+            MainInput::OpenSink => {
+                self.open_lane(LaneInit::subscription(Subscription::Sink), &sender)
+                    .await;
+            }
+
+            MainInput::OpenLane(subscription) => {
+                let subscription = subscription.with_follow(&self.gnostique.follow());
+                self.open_lane(LaneInit::subscription(subscription), &sender)
+                    .await;
             }
 
+            MainInput::SavePreset(preset) => match self.gnostique.save_lane_preset(preset).await {
+                Ok(()) => self
+                    .lanes
+                    .broadcast(LaneMsg::Presets(self.gnostique.lane_presets())),
+                Err(e) => warn!("Could not save lane preset: {e}"),
+            },
+
             MainInput::Noop => {}
 
+            MainInput::EditProfile if self.refuse_read_only("edit its profile") => {}
+
             MainInput::EditProfile => self.edit_profile.emit(EditProfileInput::Show),
 
             MainInput::DemandProfile(pubkey, relays) => {
-                let demand = self.gnostique.demand().clone();
-                relm4::spawn(async move { demand.metadata(pubkey, relays).await })
-                    .await
-                    .unwrap();
+                let gnostique = self.gnostique.clone();
+                relm4::spawn(async move {
+                    let mut outbox = gnostique.connect_outbox(pubkey).await;
+                    outbox.extend(relays);
+                    gnostique.demand().metadata(pubkey, outbox).await
+                })
+                .await
+                .unwrap();
             }
 
+            MainInput::UpdateProfile(_) | MainInput::Send { .. }
+                if self.refuse_read_only("publish") => {}
+
             MainInput::UpdateProfile(metadata) => {
-                let client = self.gnostique.client().clone();
-                relm4::spawn(async move { client.set_metadata(metadata).await })
-                    .await
-                    .unwrap()
-                    .unwrap();
+                self.publish(EventBuilder::set_metadata(metadata), "profile");
             }
 
-            MainInput::Send(c) => {
-                let client = self.gnostique.client().clone();
-                relm4::spawn(async move {
-                    client
-                        .publish_text_note(
-                            c,
-                            &[Tag::Generic(
-                                TagKind::Custom("client".to_string()),
-                                vec!["Gnostique".to_string()],
-                            )],
-                        )
-                        .await
-                })
-                .await
-                .unwrap()
-                .unwrap();
+            MainInput::Send {
+                content,
+                reply_to,
+                mut tags,
+            } => {
+                tags.push(Tag::Generic(
+                    TagKind::Custom("client".to_string()),
+                    vec!["Gnostique".to_string()],
+                ));
+                let what = match reply_to {
+                    Some(ReplyTo { event, relays }) => {
+                        tags.extend(reply_tags(&event, relays.first(), self.gnostique.pubkey()));
+                        "reply"
+                    }
+                    None => "note",
+                };
+                // Mentions and hashtags.
+                for tag in content_tags(&content) {
+                    if !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+                self.publish(EventBuilder::new_text_note(content, &tags), what);
+            }
+
+            MainInput::Repost(_) | MainInput::Quote(_) if self.refuse_read_only("repost") => {}
+
+            MainInput::Repost(id) => {
+                if let Some((event, relays)) = self.stored_event(id).await {
+                    self.publish(repost(&event, relays.first()), "repost");
+                }
+            }
+
+            MainInput::Quote(id) => {
+                if let Some((event, relays)) = self.stored_event(id).await {
+                    let (reference, tag) = quote(&event, &relays);
+                    self.write_note
+                        .emit(WriteNoteInput::Quote { id, reference, tag });
+                }
+            }
+
+            MainInput::Reacting(_) if self.refuse_read_only("react") => {}
+
+            MainInput::Reacting(Reacting::React {
+                event,
+                relays,
+                content,
+                emoji,
+                replaces,
+            }) => {
+                if let Some(id) = replaces {
+                    self.publish(reaction::deletion(&[id]), "retraction of reaction");
+                }
+                self.publish(
+                    reaction::reaction(&event, relays.first(), &content, emoji.as_ref()),
+                    "reaction",
+                );
+            }
+
+            MainInput::Reacting(Reacting::Retract(id)) => {
+                self.publish(reaction::deletion(&[id]), "retraction of reaction")
             }
 
             MainInput::ShowDetail(details) => self.details.emit(DetailsWindowInput::Show(details)),
@@ -317,6 +656,309 @@ impl AsyncComponent for Main {
     }
 }
 
+impl Main {
+    /// Message telling lanes names of identities and which one is current.
+    fn identities_msg(&self) -> LaneMsg {
+        let names = self
+            .gnostique
+            .identities()
+            .iter()
+            .map(|i| i.name().to_string())
+            .collect();
+
+        LaneMsg::Identities(
+            names,
+            self.gnostique.current_identity(),
+            self.gnostique.read_only(),
+        )
+    }
+
+    /// Enables actions that publish events only if the current identity can sign them.
+    fn enable_publishing(&self) {
+        let enabled = !self.gnostique.read_only();
+        let actions = [
+            (&self.main_actions, action::EditProfile::NAME),
+            (&self.author_actions, action::FollowAuthor::NAME),
+            (&self.author_actions, action::UnfollowAuthor::NAME),
+            (&self.note_actions, action::RepostNote::NAME),
+            (&self.note_actions, action::QuoteNote::NAME),
+        ];
+
+        for (group, name) in actions {
+            if let Some(action) = group
+                .lookup_action(name)
+                .and_downcast::<gio::SimpleAction>()
+            {
+                action.set_enabled(enabled);
+            }
+        }
+    }
+
+    /// Publishes event in the background, which may take a while when remote
+    /// signer waits for approval. Failure is shown in status bar.
+    fn publish(&self, builder: EventBuilder, what: &'static str) {
+        let gnostique = self.gnostique.clone();
+        let status_bar = self.status_bar.sender().clone();
+
+        relm4::spawn(async move {
+            if let Err(e) = gnostique.publish(builder).await {
+                warn!("Could not publish {what}: {e}");
+                status_bar.emit(StatusBarInput::Message(format!(
+                    "Could not publish {what}: {e}"
+                )));
+            }
+        });
+    }
+
+    /// Message telling lanes about `reaction` to event of `event_id`,
+    /// with image of custom emoji loaded from `emoji` file.
+    fn reaction_msg(
+        &self,
+        event_id: EventId,
+        reaction: Reaction,
+        emoji: Option<PathBuf>,
+    ) -> LaneMsg {
+        let emoji = emoji.and_then(|file| match gdk::Texture::from_filename(&file) {
+            Ok(bitmap) => Some(Arc::new(bitmap)),
+            Err(e) => {
+                warn!("Could not load '{:?}': {}", file, e);
+                None
+            }
+        });
+
+        LaneMsg::Reaction {
+            event: event_id,
+            own: reaction.author == self.gnostique.pubkey(),
+            reaction,
+            emoji,
+        }
+    }
+
+    /// Stored event of `id` with relays where it was seen.
+    async fn stored_event(&self, id: EventId) -> Option<(Event, Vec<Url>)> {
+        match self.gnostique.store().get_event(id).await {
+            Ok(Some(event)) => {
+                let relays = self
+                    .gnostique
+                    .store()
+                    .event_relays(id)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(is_relay)
+                    .collect();
+                Some((event, relays))
+            }
+            Ok(None) => {
+                warn!("Event {id} is not stored.");
+                None
+            }
+            Err(e) => {
+                warn!("Could not load event {id}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Whether author of stored `event` asked to delete it.
+    async fn is_deleted(&self, event: &Event) -> bool {
+        self.gnostique
+            .store()
+            .referring_events(event.id, Kind::EventDeletion)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .any(|d| d.pubkey == event.pubkey)
+    }
+
+    /// Tells user that the current identity cannot do `what` if it is read-only.
+    /// Returns whether it is.
+    fn refuse_read_only(&self, what: &str) -> bool {
+        let read_only = self.gnostique.read_only();
+        if read_only {
+            self.status_bar.emit(StatusBarInput::Message(format!(
+                "Read-only identity cannot {what}."
+            )));
+        }
+        read_only
+    }
+
+    /// Lets user choose file of an archive of events. Once chosen, its path
+    /// is sent back as input made by `chosen`.
+    fn choose_archive<F>(
+        &mut self,
+        root: &gtk::Box,
+        action: gtk::FileChooserAction,
+        sender: &AsyncComponentSender<Self>,
+        chosen: F,
+    ) where
+        F: Fn(PathBuf) -> MainInput + 'static,
+    {
+        let (title, accept) = match action {
+            gtk::FileChooserAction::Save => ("Export events", "Export"),
+            _ => ("Import events", "Import"),
+        };
+
+        let dialog = gtk::FileChooserNative::new(
+            Some(title),
+            root.root().and_downcast_ref::<gtk::Window>(),
+            action,
+            Some(accept),
+            None,
+        );
+
+        if action == gtk::FileChooserAction::Save {
+            dialog.set_current_name("events.jsonl");
+        }
+
+        let sender = sender.clone();
+        dialog.connect_response(move |d, response| {
+            if response == gtk::ResponseType::Accept {
+                if let Some(path) = d.file().and_then(|f| f.path()) {
+                    sender.input(chosen(path));
+                }
+            }
+        });
+
+        dialog.show();
+        self.archive_dialog = Some(dialog);
+    }
+
+    /// Opens a new lane, tells it what it needs to know about the session
+    /// and fills it with the newest stored notes.
+    async fn open_lane(&mut self, init: LaneInit, sender: &AsyncComponentSender<Self>) {
+        let config = self.gnostique.config();
+        let init = init
+            .default_width(config.ui.lane_width)
+            .window(config.cache.lane_notes);
+        let subscription = init.subscription.clone();
+        let index = self.lanes.guard().push_back(init).current_index();
+
+        self.lanes
+            .send(index, LaneMsg::Presets(self.gnostique.lane_presets()));
+        self.lanes
+            .send(index, LaneMsg::FollowChanged(self.gnostique.follow()));
+        self.lanes.send(index, self.identities_msg());
+
+        self.load_page(index, &subscription, Page::Latest, sender)
+            .await;
+    }
+
+    /// Sends stored notes of `page` of `subscription` to lane at `index`
+    /// and asks relays for older notes they may have.
+    async fn load_page(
+        &self,
+        index: usize,
+        subscription: &Subscription,
+        page: Page,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        // A page is half of what a lane holds so that there is
+        // something to scroll back to after loading.
+        let limit = (self.gnostique.config().cache.lane_notes / 2).max(1);
+
+        let events = match self
+            .gnostique
+            .store()
+            .text_notes(subscription, page, limit)
+            .await
+        {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Could not load stored notes: {e}");
+                vec![]
+            }
+        };
+        let complete = events.len() < limit;
+
+        for incoming in stored_text_notes(&self.gnostique, events).await {
+            if let Incoming::TextNote {
+                note,
+                content,
+                relays,
+                avatar,
+                repost,
+                referenced_notes,
+                referenced_profiles,
+            } = incoming
+            {
+                if let (Some(url), Some(file)) = (note.author().avatar.clone(), avatar) {
+                    sender.input(MainInput::MetadataBitmap {
+                        pubkey: note.author().pubkey,
+                        url,
+                        file,
+                    });
+                }
+
+                let id = note.event().id;
+
+                self.lanes.send(
+                    index,
+                    LaneMsg::NewTextNote {
+                        note,
+                        content: Arc::new(content),
+                        relays,
+                        repost,
+                        referenced_notes,
+                        referenced_profiles,
+                    },
+                );
+
+                let reactions = self
+                    .gnostique
+                    .store()
+                    .referring_events(id, Kind::Reaction)
+                    .await
+                    .unwrap_or_default();
+                for event in reactions {
+                    if self.is_deleted(&event).await {
+                        continue;
+                    }
+                    if let Some((to, reaction)) =
+                        Reaction::from_event(&event).filter(|(to, _)| *to == id)
+                    {
+                        let emoji = reaction
+                            .emoji
+                            .as_ref()
+                            .and_then(|e| self.gnostique.download().cached(&e.url));
+                        self.lanes
+                            .send(index, self.reaction_msg(to, reaction, emoji));
+                    }
+                }
+            }
+        }
+
+        // Relays may know more of the history than the database does,
+        // their notes arrive the usual way.
+        if let Page::Older(until) = page {
+            let search_relays = self.gnostique.search_relays().await;
+            self.gnostique
+                .demand()
+                .history(subscription.to_filters_until(until, limit), &search_relays)
+                .await;
+        }
+
+        // Search lanes show also the profiles that match.
+        if let (Page::Latest, Some(text)) = (page, subscription.required_search()) {
+            let profiles = self
+                .gnostique
+                .store()
+                .search_profiles(&text, FOUND_PROFILES)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Could not search profiles: {e}");
+                    vec![]
+                })
+                .into_iter()
+                .filter_map(|e| Some(Persona::from_metadata(e.pubkey, e.as_metadata()?)))
+                .collect();
+            self.lanes.send(index, LaneMsg::FoundProfiles(profiles));
+        }
+
+        self.lanes.send(index, LaneMsg::PageLoaded { complete });
+    }
+}
+
 /// Translates result of [`edit profile`](editprofile::component) dialog to [`Msg`].
 fn forward_edit_profile(result: EditProfileResult) -> MainInput {
     match result {
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
@@ -9,8 +10,10 @@ use relm4::prelude::*;
 use relm4::JoinHandle;
 use tracing::trace;
 
+use super::msg::Reacting;
 use super::view::NoteWidgets;
 use crate::nostr::content::DynamicContent;
+use crate::nostr::reaction::{CustomEmoji, Reaction, LIKE};
 use crate::nostr::*;
 use crate::ui::replies::{Replies, RepliesInput};
 use crate::ui::widgets::quote::Quote;
@@ -24,8 +27,12 @@ pub struct Note {
     pub(super) nip05_verified: bool,
     pub(super) show_hidden_buttons: bool,
     pub(super) avatar: Arc<gdk::Texture>,
-    pub(super) likes: u32,
-    pub(super) dislikes: u32,
+    /// Reactions to this note, including own.
+    pub(super) reactions: Vec<Reaction>,
+    /// Reaction of the current identity.
+    pub(super) own_reaction: Option<Reaction>,
+    /// Images of custom emoji by their URL.
+    pub(super) emojis: HashMap<Url, Arc<gdk::Texture>>,
     pub time: DateTime<Utc>,
     pub(super) event: Arc<Event>,
     pub(super) relays: Vec<Url>,
@@ -50,6 +57,53 @@ impl Drop for Note {
 }
 
 impl Note {
+    /// ID of the event of this note.
+    pub fn id(&self) -> EventId {
+        self.event.id
+    }
+
+    pub(super) fn likes(&self) -> usize {
+        self.reactions.iter().filter(|r| r.is_like()).count()
+    }
+
+    pub(super) fn dislikes(&self) -> usize {
+        self.reactions.iter().filter(|r| r.is_dislike()).count()
+    }
+
+    /// Reactions other than likes and dislikes, each only once, with number
+    /// of times it was given.
+    pub(super) fn other_reactions(&self) -> Vec<(&Reaction, usize)> {
+        let mut others: Vec<(&Reaction, usize)> = vec![];
+        for r in self
+            .reactions
+            .iter()
+            .filter(|r| !r.is_like() && !r.is_dislike())
+        {
+            match others.iter_mut().find(|(o, _)| o.same_as(r)) {
+                Some((_, count)) => *count += 1,
+                None => others.push((r, 1)),
+            }
+        }
+        others
+    }
+
+    /// What to do when user reacts by `content`: take back own reaction
+    /// if it is the same, react (instead of the own one) otherwise.
+    pub(super) fn reacting(&self, content: String, emoji: Option<CustomEmoji>) -> Reacting {
+        match &self.own_reaction {
+            Some(own) if own.content == content || (own.is_like() && content == LIKE) => {
+                Reacting::Retract(own.id)
+            }
+            own => Reacting::React {
+                event: self.event.clone(),
+                relays: self.relays.clone(),
+                content,
+                emoji,
+                replaces: own.as_ref().map(|o| o.id),
+            },
+        }
+    }
+
     pub(super) fn receive(
         &mut self,
         widgets: &NoteWidgets,
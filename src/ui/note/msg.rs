@@ -6,9 +6,11 @@ use nostr_sdk::prelude::*;
 
 use crate::nostr::content::DynamicContent;
 use crate::nostr::preview::Preview;
+use crate::nostr::reaction::{CustomEmoji, Reaction};
 use crate::nostr::*;
 use crate::ui::details::Details;
 use crate::ui::link::InternalLink;
+use crate::ui::writenote::model::ReplyTo;
 
 /// Initial
 pub struct NoteInit {
@@ -34,6 +36,8 @@ pub enum NoteInput {
     FocusOut,
     /// Show this note's details.
     ShowDetails,
+    /// Write a reply to this note.
+    Reply,
     /// (New) avatar bitmap is available.
     MetadataBitmap {
         pubkey: XOnlyPublicKey,
@@ -42,7 +46,21 @@ pub enum NoteInput {
     },
     Reaction {
         event: EventId,
-        reaction: String,
+        reaction: Reaction,
+        /// The reaction is by the current identity.
+        own: bool,
+        /// Image of custom emoji of the reaction.
+        emoji: Option<Arc<gdk::Texture>>,
+    },
+    /// Author asked to delete their events of `ids`.
+    Deleted {
+        author: XOnlyPublicKey,
+        ids: Vec<EventId>,
+    },
+    /// User reacts by `content` or takes back their reaction if it is the same.
+    React {
+        content: String,
+        emoji: Option<CustomEmoji>,
     },
 
     Nip05Verified(XOnlyPublicKey),
@@ -62,4 +80,21 @@ pub enum NoteInput {
 pub enum NoteOutput {
     ShowDetails(Details),
     LinkClicked(InternalLink),
+    Reply(ReplyTo),
+    Reacting(Reacting),
+}
+
+/// What user does with their reaction to a note.
+#[derive(Clone, Debug)]
+pub enum Reacting {
+    /// React to `event` by `content`, replacing earlier reaction, if any.
+    React {
+        event: Arc<Event>,
+        relays: Vec<Url>,
+        content: String,
+        emoji: Option<CustomEmoji>,
+        replaces: Option<EventId>,
+    },
+    /// Take back own reaction of this ID.
+    Retract(EventId),
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use chrono::{TimeZone, Utc};
@@ -10,6 +11,7 @@ use relm4::prelude::*;
 use super::model::*;
 use super::msg::*;
 use crate::app::action::*;
+use crate::nostr::reaction::{DISLIKE, LIKE};
 use crate::nostr::*;
 use crate::ui::details::Details;
 use crate::ui::link::InternalLink;
@@ -17,6 +19,7 @@ use crate::ui::replies::RepliesInput;
 use crate::ui::widgets::author::Author;
 use crate::ui::widgets::preview::Preview;
 use crate::ui::widgets::quote::Quote;
+use crate::ui::writenote::model::ReplyTo;
 
 /*
     +-------------------------------------+
@@ -134,9 +137,21 @@ impl FactoryComponent for Note {
                 set_hexpand: true,
                 add_css_class: "reactions",
 
+                attach[0, 1, 1, 1] =
+                    &gtk::Button {
+                        set_halign: gtk::Align::Center,
+                        set_tooltip_text: Some("Reply"),
+                        connect_clicked => NoteInput::Reply,
+                        gtk::Image {
+                            set_icon_name: Some("mail-reply-sender-symbolic"),
+                            set_pixel_size: 12,
+                        },
+                    },
                 attach[1, 1, 1, 1] =
                     &gtk::Button {
                         set_halign: gtk::Align::Center,
+                        #[watch] set_class_active: ("own", self.own_reaction.as_ref().is_some_and(|r| r.is_like())),
+                        connect_clicked => NoteInput::React { content: LIKE.to_string(), emoji: None },
                         gtk::Box {
                             set_orientation: gtk::Orientation::Horizontal,
                             set_spacing: 4,
@@ -145,14 +160,16 @@ impl FactoryComponent for Note {
                                 set_pixel_size: 12,
                             },
                             gtk::Label {
-                                #[watch] set_label: &self.likes.to_string(),
-                                #[watch] set_visible: self.likes > 0
+                                #[watch] set_label: &self.likes().to_string(),
+                                #[watch] set_visible: self.likes() > 0
                             }
                         }
                     },
                 attach[2, 1, 1, 1] =
                     &gtk::Button {
                         set_halign: gtk::Align::Center,
+                        #[watch] set_class_active: ("own", self.own_reaction.as_ref().is_some_and(|r| r.is_dislike())),
+                        connect_clicked => NoteInput::React { content: DISLIKE.to_string(), emoji: None },
                         gtk::Box {
                             set_orientation: gtk::Orientation::Horizontal,
                             set_spacing: 4,
@@ -161,24 +178,45 @@ impl FactoryComponent for Note {
                                 set_pixel_size: 12,
                             },
                             gtk::Label {
-                                #[watch] set_label: &self.dislikes.to_string(),
-                                #[watch] set_visible: self.dislikes > 0
+                                #[watch] set_label: &self.dislikes().to_string(),
+                                #[watch] set_visible: self.dislikes() > 0
                             }
                         }
                     },
                 attach[3, 1, 1, 1] =
-                    &gtk::Box {
-                        set_orientation: gtk::Orientation::Horizontal,
-                        set_spacing: 4,
+                    &gtk::MenuButton {
                         set_halign: gtk::Align::Center,
-
-                        gtk::Button::from_icon_name("gnostique-repost-symbolic") { }
+                        set_tooltip_text: Some("React by emoji"),
+                        set_icon_name: "face-smile-symbolic",
+                        #[wrap(Some)]
+                        set_popover = &gtk::EmojiChooser {
+                            connect_emoji_picked[sender] => move |_, emoji| {
+                                sender.input(NoteInput::React { content: emoji.to_string(), emoji: None })
+                            }
+                        }
                     },
                 attach[4, 1, 1, 1] =
+                    &gtk::MenuButton {
+                        set_halign: gtk::Align::Center,
+                        set_tooltip_text: Some("Repost or quote"),
+                        set_icon_name: "gnostique-repost-symbolic",
+                        set_menu_model: Some(&repost_menu)
+                    },
+                attach[5, 1, 1, 1] =
                     &gtk::MenuButton {
                         set_halign: gtk::Align::Center,
                         set_icon_name: "content-loading-symbolic",
                         set_menu_model: Some(&note_menu)
+                    },
+
+                // Emoji and other reactions, filled in by `show_other_reactions`.
+                #[name(other_reactions)]
+                attach[0, 2, 6, 1] =
+                    &gtk::FlowBox {
+                        set_selection_mode: gtk::SelectionMode::None,
+                        set_max_children_per_line: 12,
+                        add_css_class: "other-reactions",
+                        #[watch] set_visible: !self.other_reactions().is_empty(),
                     }
             },
 
@@ -220,11 +258,28 @@ impl FactoryComponent for Note {
 
     menu! {
         author_menu: {
-            "Copy pubkey as hex" => CopyText(self.author.pubkey.to_string()),
-            "Copy pubkey as bech32" => CopyText(self.author.pubkey.to_bech32().unwrap()),
+            section! {
+                "Follow" => FollowAuthor(self.author.pubkey.to_string()),
+                "Unfollow" => UnfollowAuthor(self.author.pubkey.to_string()),
+            },
+            section! {
+                "Copy pubkey as hex" => CopyText(self.author.pubkey.to_string()),
+                "Copy pubkey as bech32" => CopyText(self.author.pubkey.to_bech32().unwrap()),
+            }
+        },
+
+        repost_menu: {
+            section! {
+                "Repost" => RepostNote(self.event.id.to_hex()),
+                "Quote" => QuoteNote(self.event.id.to_hex()),
+            }
         },
 
         note_menu: {
+            section! {
+                "Bookmark" => BookmarkNote(self.event.id.to_hex()),
+                "Remove bookmark" => UnbookmarkNote(self.event.id.to_hex()),
+            },
             section! {
                 "Copy event ID as hex" => CopyText(self.event.id.to_hex()),
                 "Copy event ID as bech32" => CopyText(self.event.id.to_bech32().unwrap())
@@ -259,8 +314,9 @@ impl FactoryComponent for Note {
             content: (*init.content).clone(),
             show_hidden_buttons: false,
             avatar: ANONYMOUS_USER.clone(),
-            likes: 0,
-            dislikes: 0,
+            reactions: vec![],
+            own_reaction: None,
+            emojis: HashMap::new(),
             time: Utc.timestamp_opt(event.created_at.as_i64(), 0).unwrap(),
             event,
             relays: init.relays,
@@ -366,15 +422,42 @@ impl FactoryComponent for Note {
                     replies.emit(RepliesInput::Nip05Verified(pubkey));
                 };
             }
-            NoteInput::Reaction { event, reaction } => {
-                if self.event.id == event {
-                    if reaction == "+" || reaction == "🤙" {
-                        self.likes += 1;
-                    } else if reaction == "-" {
-                        self.dislikes += 1;
+            NoteInput::Reaction {
+                event,
+                reaction,
+                own,
+                emoji,
+            } => {
+                if self.event.id == event && !self.reactions.iter().any(|r| r.id == reaction.id) {
+                    if let (Some(custom), Some(bitmap)) = (&reaction.emoji, emoji) {
+                        self.emojis.insert(custom.url.clone(), bitmap);
                     }
+                    if own {
+                        self.own_reaction = Some(reaction.clone());
+                    }
+                    self.reactions.push(reaction);
+                    self.show_other_reactions(&widgets.other_reactions, &sender);
+                }
+            }
+            NoteInput::Deleted { author, ids } => {
+                let count = self.reactions.len();
+                self.reactions
+                    .retain(|r| r.author != author || !ids.contains(&r.id));
+
+                if count != self.reactions.len() {
+                    if self
+                        .own_reaction
+                        .as_ref()
+                        .is_some_and(|r| ids.contains(&r.id))
+                    {
+                        self.own_reaction = None;
+                    }
+                    self.show_other_reactions(&widgets.other_reactions, &sender);
                 }
             }
+            NoteInput::React { content, emoji } => {
+                sender.output(NoteOutput::Reacting(self.reacting(content, emoji)));
+            }
             NoteInput::ShowDetails => {
                 let event_json = match &self.repost {
                     Some(repost) => serde_json::to_string_pretty(repost.event()).unwrap(),
@@ -386,9 +469,70 @@ impl FactoryComponent for Note {
                 };
                 sender.output(NoteOutput::ShowDetails(details));
             }
+            NoteInput::Reply => {
+                sender.output(NoteOutput::Reply(ReplyTo {
+                    event: self.event.clone(),
+                    relays: self.relays.clone(),
+                }));
+            }
             NoteInput::Tick => self.age = self.format_age(),
         }
 
         self.update_view(widgets, sender);
     }
 }
+
+impl Note {
+    /// Fills `container` by buttons of reactions other than likes and dislikes.
+    /// Clicking one reacts the same way.
+    fn show_other_reactions(&self, container: &gtk::FlowBox, sender: &FactorySender<Self>) {
+        while let Some(child) = container.first_child() {
+            container.remove(&child);
+        }
+
+        for (reaction, count) in self.other_reactions() {
+            let own = self
+                .own_reaction
+                .as_ref()
+                .is_some_and(|r| r.same_as(reaction));
+            let bitmap = reaction
+                .emoji
+                .as_ref()
+                .and_then(|e| self.emojis.get(&e.url));
+
+            relm4::view! {
+                button = gtk::Button {
+                    set_tooltip_text: Some(&reaction.content),
+                    set_class_active: ("own", own),
+                    connect_clicked[sender, content = reaction.content.clone(), emoji = reaction.emoji.clone()] => move |_| {
+                        sender.input(NoteInput::React {
+                            content: content.clone(),
+                            emoji: emoji.clone(),
+                        })
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 4,
+
+                        gtk::Image {
+                            set_pixel_size: 16,
+                            set_visible: bitmap.is_some(),
+                            set_paintable: bitmap.map(|b| b.as_ref()),
+                        },
+                        gtk::Label {
+                            set_label: &reaction.content,
+                            set_visible: bitmap.is_none(),
+                        },
+                        gtk::Label {
+                            set_label: &count.to_string(),
+                            set_visible: count > 1,
+                        }
+                    }
+                }
+            }
+
+            container.insert(&button, -1);
+        }
+    }
+}
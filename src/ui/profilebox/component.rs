@@ -4,13 +4,13 @@ use gtk::prelude::*;
 use nostr_sdk::prelude::{ToBech32, XOnlyPublicKey};
 use relm4::*;
 
-use super::model::{Input, Profilebox};
+use super::model::{Input, Output, Profilebox};
 use crate::nostr::Persona;
 
 #[relm4::component(pub)]
 impl Component for Profilebox {
     type Input = Input;
-    type Output = ();
+    type Output = Output;
     type Init = XOnlyPublicKey;
     type CommandOutput = ();
 
@@ -69,6 +69,12 @@ impl Component for Profilebox {
                     add_css_class: "about",
                     #[watch] set_label?: &model.author.about.as_ref(),
                 },
+
+                gtk::Button {
+                    set_halign: gtk::Align::Start,
+                    #[watch] set_label: if model.following { "Unfollow" } else { "Follow" },
+                    connect_clicked => Input::ToggleFollow,
+                },
             },
         }
     }
@@ -84,7 +90,7 @@ impl Component for Profilebox {
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>, _root: &Self::Root) {
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
         match message {
             Input::UpdatedProfile { author } => self.author = author,
             Input::MetadataBitmap { bitmap, url } => {
@@ -94,6 +100,10 @@ impl Component for Profilebox {
                     self.banner = Some(bitmap)
                 }
             }
+            Input::Following(following) => self.following = following,
+            Input::ToggleFollow => sender
+                .output(Output::Follow(!self.following))
+                .unwrap_or_default(),
         }
     }
 }
@@ -1,4 +1,4 @@
 pub mod component;
 pub mod model;
 
-pub use model::Input;
+pub use model::{Input, Output};
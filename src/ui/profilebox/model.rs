@@ -10,6 +10,9 @@ pub struct Profilebox {
     pub author: Arc<Persona>,
     pub avatar: Arc<Texture>,
     pub banner: Option<Arc<Texture>>,
+
+    /// Whether the current identity follows the author.
+    pub following: bool,
 }
 
 impl Profilebox {
@@ -18,6 +21,7 @@ impl Profilebox {
             author: persona,
             avatar: ANONYMOUS_USER.clone(),
             banner: None,
+            following: false,
         }
     }
 }
@@ -26,4 +30,12 @@ impl Profilebox {
 pub enum Input {
     UpdatedProfile { author: Arc<Persona> },
     MetadataBitmap { url: Url, bitmap: Arc<Texture> },
+    Following(bool),
+    ToggleFollow,
+}
+
+#[derive(Debug)]
+pub enum Output {
+    /// Follow (`true`) or unfollow (`false`) the author.
+    Follow(bool),
 }
@@ -1,17 +1,19 @@
 use bip39::Mnemonic;
 use gtk::glib::clone;
 use gtk::prelude::*;
+use nostr_sdk::prelude::ToBech32;
 use nostr_sdk::secp256k1::rand::rngs::OsRng;
 use nostr_sdk::secp256k1::rand::Rng;
 use relm4::prelude::*;
 use secrecy::ExposeSecret;
 
-use crate::identity::Identity;
+use crate::identity::{Identity, KeyFormat};
 
 #[derive(Debug, PartialEq, Eq)]
 enum RadioActive {
     Mnemonic,
     Existing,
+    Bunker,
     Readonly,
 }
 
@@ -26,23 +28,84 @@ pub struct Edit {
     /// GTK Text Buffer for mnemonic.
     mnemonic_buffer: gtk::TextBuffer,
 
+    /// Imported secret key as nsec, hex or ncryptsec.
+    key_buffer: gtk::EntryBuffer,
+
+    /// URI of remote signer (NIP-46).
+    bunker_buffer: gtk::EntryBuffer,
+
+    /// Public key of read-only identity as npub, nprofile or hex.
+    pubkey_buffer: gtk::EntryBuffer,
+
+    /// Problem with the identity, if any.
+    error: Option<String>,
+
+    /// Identity is being made (decryption of key takes a while).
+    busy: bool,
+
+    /// Number of edits started, so that identity made for an earlier
+    /// one is told apart.
+    edits: usize,
+
     /// Index of identity that is currently being edited.
     editing_index: Option<DynamicIndex>,
+
+    /// Identity as it was before editing.
+    original: Option<Identity>,
 }
 
 impl Edit {
-    /// Make an identity of this editing dialog.
-    fn to_identity(&self) -> Option<Identity> {
+    /// Makes a function that makes an identity of this editing dialog.
+    /// `password` decrypts imported ncryptsec key, which takes a while,
+    /// so the function is called outside of the UI thread.
+    fn identity_maker(&self, password: &str) -> IdentityMaker {
         let name = self.name_buffer.text().to_string();
-        let buffer = &self.mnemonic_buffer;
-        let text = buffer
-            .text(&buffer.start_iter(), &buffer.end_iter(), true)
-            .to_string();
-        let mnemonic = Mnemonic::parse(text).ok()?;
-        Some(Identity::from_bip39_mnemonic(&name, &mnemonic))
+
+        match self.radio_active {
+            RadioActive::Existing => {
+                let secret = self.key_buffer.text().to_string();
+                let password = password.to_string();
+                Box::new(move || {
+                    Identity::import(&name, &secret, &password).map_err(|e| e.to_string())
+                })
+            }
+            RadioActive::Bunker => {
+                let uri = self.bunker_buffer.text().to_string();
+
+                // Signer already knows this client, unless the URI changed.
+                if let Some(original) = &self.original {
+                    if original.bunker().map(|(u, _)| u.to_string()).as_deref() == Some(uri.trim())
+                    {
+                        let renamed = original.renamed(&name);
+                        return Box::new(move || Ok(renamed));
+                    }
+                }
+
+                Box::new(move || Identity::import_bunker(&name, &uri).map_err(|e| e.to_string()))
+            }
+            RadioActive::Readonly => {
+                let pubkey = self.pubkey_buffer.text().to_string();
+                Box::new(move || {
+                    Identity::import_read_only(&name, &pubkey).map_err(|e| e.to_string())
+                })
+            }
+            RadioActive::Mnemonic => {
+                let buffer = &self.mnemonic_buffer;
+                let text = buffer
+                    .text(&buffer.start_iter(), &buffer.end_iter(), true)
+                    .to_string();
+                Box::new(move || {
+                    let mnemonic = Mnemonic::parse(text).map_err(|e| e.to_string())?;
+                    Ok(Identity::from_bip39_mnemonic(&name, &mnemonic))
+                })
+            }
+        }
     }
 }
 
+/// Function making identity, see [`Edit::identity_maker`].
+type IdentityMaker = Box<dyn FnOnce() -> Result<Identity, String> + Send>;
+
 #[derive(Debug)]
 pub enum EditInput {
     /// Radio buttons toggled.
@@ -64,6 +127,12 @@ pub enum EditInput {
     Finished,
 }
 
+#[derive(Debug)]
+pub enum EditCmd {
+    /// Identity made during edit of given number.
+    Made(usize, Result<Identity, String>),
+}
+
 #[derive(Debug)]
 pub enum EditOutput {
     Canceled,
@@ -79,7 +148,7 @@ impl Component for Edit {
     type Init = ();
     type Input = EditInput;
     type Output = EditOutput;
-    type CommandOutput = ();
+    type CommandOutput = EditCmd;
 
     view! {
         gtk::Box {
@@ -165,7 +234,7 @@ impl Component for Edit {
                         set_orientation: gtk::Orientation::Vertical,
 
                         #[name(existing_radio)]
-                        gtk::CheckButton::with_label("Open existing identity") {
+                        gtk::CheckButton::with_label("Import secret key") {
                             set_group: Some(&mnemonic_radio),
                             connect_toggled => EditInput::Toggled
                         },
@@ -175,23 +244,73 @@ impl Component for Edit {
                             #[watch]
                             set_sensitive: model.radio_active == RadioActive::Existing,
                             set_hexpand: true,
-                            set_icon_from_icon_name: (gtk::EntryIconPosition::Secondary, Some("folder-open-symbolic"))
+                            set_buffer: &model.key_buffer,
+                            set_visibility: false,
+                            set_placeholder_text: Some("nsec1…, hex or ncryptsec1…"),
+                        },
+
+                        #[name(key_password)]
+                        gtk::PasswordEntry {
+                            #[watch]
+                            set_sensitive: model.radio_active == RadioActive::Existing,
+                            set_hexpand: true,
+                            set_show_peek_icon: true,
+                            set_tooltip_text: Some("Password of ncryptsec key"),
                         }
                     },
                     attach[0, 3, 2, 1] = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+
+                        #[name(bunker_radio)]
+                        gtk::CheckButton::with_label("Remote signer") {
+                            set_group: Some(&mnemonic_radio),
+                            connect_toggled => EditInput::Toggled
+                        },
+
+                        gtk::Entry {
+                            #[watch]
+                            set_sensitive: model.radio_active == RadioActive::Bunker,
+                            set_hexpand: true,
+                            set_buffer: &model.bunker_buffer,
+                            set_placeholder_text: Some("bunker://…?relay=wss://…"),
+                        },
+                    },
+                    attach[0, 4, 2, 1] = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+
                         #[name(readonly_radio)]
                         gtk::CheckButton::with_label("Read-only identity") {
                             set_group: Some(&mnemonic_radio),
                             connect_toggled => EditInput::Toggled
                         },
+
+                        gtk::Entry {
+                            #[watch]
+                            set_sensitive: model.radio_active == RadioActive::Readonly,
+                            set_hexpand: true,
+                            set_buffer: &model.pubkey_buffer,
+                            set_placeholder_text: Some("npub1…, nprofile1… or hex"),
+                        },
                     },
-                    attach[0, 4, 2, 1] = &gtk::Box {
+                    attach[0, 5, 2, 1] = &gtk::Label {
+                        add_css_class: "error",
+                        set_xalign: 0.0,
+                        set_wrap: true,
+                        #[watch] set_visible: model.error.is_some(),
+                        #[watch] set_label: model.error.as_deref().unwrap_or_default(),
+                    },
+                    attach[0, 6, 2, 1] = &gtk::Box {
                         set_halign: gtk::Align::End,
                         set_spacing: 8,
                         add_css_class: "buttons",
 
+                        gtk::Spinner {
+                            #[watch] set_spinning: model.busy,
+                        },
+
                         #[name(ok)]
                         gtk::Button {
+                            #[watch] set_visible: !model.busy,
                             add_css_class: "suggested-action",
                             set_label: "OK",
                             connect_clicked[sender] => move |_| {
@@ -227,7 +346,14 @@ impl Component for Edit {
             radio_active: RadioActive::Mnemonic,
             name_buffer: gtk::EntryBuffer::default(),
             mnemonic_buffer,
+            key_buffer: gtk::EntryBuffer::default(),
+            bunker_buffer: gtk::EntryBuffer::default(),
+            pubkey_buffer: gtk::EntryBuffer::default(),
+            error: None,
+            busy: false,
+            edits: 0,
             editing_index: None,
+            original: None,
         };
 
         let widgets = view_output!();
@@ -276,11 +402,17 @@ impl Component for Edit {
 
             EditInput::Toggled => {
                 if widgets.mnemonic_radio.is_active() {
-                    self.radio_active = RadioActive::Mnemonic
+                    self.radio_active = RadioActive::Mnemonic;
+                    sender.input(EditInput::MnemonicRefreshed);
                 } else if widgets.existing_radio.is_active() {
-                    self.radio_active = RadioActive::Existing
+                    self.radio_active = RadioActive::Existing;
+                    widgets.ok.set_sensitive(true);
+                } else if widgets.bunker_radio.is_active() {
+                    self.radio_active = RadioActive::Bunker;
+                    widgets.ok.set_sensitive(true);
                 } else {
-                    self.radio_active = RadioActive::Readonly
+                    self.radio_active = RadioActive::Readonly;
+                    widgets.ok.set_sensitive(true);
                 }
             }
             EditInput::RefreshMnemonic => {
@@ -290,21 +422,85 @@ impl Component for Edit {
             }
             EditInput::Edit { identity, index } => {
                 self.name_buffer.set_text(identity.name());
-                self.mnemonic_buffer
-                    .set_text(identity.mnemonic().expose_secret().reveal());
+                widgets.key_password.set_text("");
+                self.error = None;
+                self.busy = false;
+                self.edits += 1;
+
+                self.mnemonic_buffer.set_text("");
+                self.key_buffer.set_text("");
+                self.bunker_buffer.set_text("");
+                self.pubkey_buffer.set_text("");
+
+                if let Some(mnemonic) = identity.mnemonic() {
+                    self.mnemonic_buffer
+                        .set_text(mnemonic.expose_secret().reveal());
+                    widgets.mnemonic_radio.set_active(true);
+                } else if let Some((uri, _)) = identity.bunker() {
+                    self.bunker_buffer.set_text(uri.to_string());
+                    widgets.bunker_radio.set_active(true);
+                } else if identity.is_read_only() {
+                    self.pubkey_buffer.set_text(
+                        &identity
+                            .nostr_key()
+                            .and_then(|k| k.public_key().to_bech32().ok())
+                            .unwrap_or_default(),
+                    );
+                    widgets.readonly_radio.set_active(true);
+                } else {
+                    self.key_buffer
+                        .set_text(identity.export(KeyFormat::Nsec, "").unwrap_or_default());
+                    widgets.existing_radio.set_active(true);
+                }
+
                 self.editing_index = Some(index);
-                sender.input(EditInput::MnemonicRefreshed);
+                self.original = Some(identity);
+                sender.input(EditInput::Toggled);
+            }
+            EditInput::Finished if !self.busy => {
+                let make = self.identity_maker(&widgets.key_password.text());
+                self.busy = true;
+                self.error = None;
+                let edit = self.edits;
+                sender.oneshot_command(async move {
+                    let result = tokio::task::spawn_blocking(make)
+                        .await
+                        .unwrap_or_else(|e| Err(e.to_string()));
+                    EditCmd::Made(edit, result)
+                });
             }
-            EditInput::Finished => {
-                if let Some(index) = self.editing_index.take() {
-                    if let Some(new_identity) = self.to_identity() {
-                        sender
-                            .output(EditOutput::Finished {
-                                index,
-                                new_identity,
-                            })
-                            .unwrap_or_default();
-                    };
+            EditInput::Finished => {}
+        }
+
+        self.update_view(widgets, sender);
+    }
+
+    fn update_cmd_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            // The dialog might have been reopened in the meantime.
+            EditCmd::Made(edit, _) if edit != self.edits => {}
+            EditCmd::Made(_, result) => {
+                self.busy = false;
+                match result {
+                    Ok(new_identity) => {
+                        self.error = None;
+                        if let Some(index) = self.editing_index.take() {
+                            widgets.key_password.set_text("");
+                            sender
+                                .output(EditOutput::Finished {
+                                    index,
+                                    new_identity,
+                                })
+                                .unwrap_or_default();
+                        }
+                    }
+                    Err(e) => self.error = Some(e),
                 }
             }
         }
@@ -0,0 +1,243 @@
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+use crate::identity::{Identity, KeyFormat};
+
+/// Formats offered in the drop-down, in its order.
+const FORMATS: [(&str, KeyFormat); 3] = [
+    ("nsec", KeyFormat::Nsec),
+    ("hex", KeyFormat::Hex),
+    ("ncryptsec (encrypted by password)", KeyFormat::Ncryptsec),
+];
+
+/// Page that shows secret key of an identity, once the user confirms
+/// they really want to see it.
+#[derive(Debug)]
+pub struct Export {
+    identity: Option<Identity>,
+
+    /// User confirmed that the secret can be shown.
+    confirmed: bool,
+
+    /// Secret key is being exported (encryption takes a while).
+    busy: bool,
+
+    /// Exported secret key.
+    secret: Option<String>,
+
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ExportInput {
+    /// Show export page of this identity.
+    Show(Identity),
+    Confirm(bool),
+    /// Export secret key in the chosen format.
+    Reveal,
+    Close,
+}
+
+#[derive(Debug)]
+pub enum ExportOutput {
+    Closed,
+}
+
+#[derive(Debug)]
+pub enum ExportCmd {
+    Exported(Result<String, String>),
+}
+
+#[relm4::component(pub)]
+impl Component for Export {
+    type Init = ();
+    type Input = ExportInput;
+    type Output = ExportOutput;
+    type CommandOutput = ExportCmd;
+
+    view! {
+        gtk::Box {
+            set_halign: gtk::Align::Center,
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 18,
+            set_widget_name: "export",
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 8,
+                add_css_class: "formbox",
+                add_css_class: "form",
+
+                gtk::Grid {
+                    set_column_spacing: 16,
+                    set_row_spacing: 16,
+
+                    attach[0, 0, 2, 1] = &gtk::Label {
+                        #[watch]
+                        set_label: &format!(
+                            "Export secret key of {}",
+                            model.identity.as_ref().map(|i| i.name()).unwrap_or_default()
+                        ),
+                        set_xalign: 0.0,
+                        add_css_class: "label"
+                    },
+
+                    attach[0, 1, 1, 1] = &gtk::Label {
+                        set_text: "Format:",
+                        set_xalign: 1.0,
+                    },
+
+                    #[name(format)]
+                    attach[1, 1, 1, 1] = &gtk::DropDown::from_strings(&FORMATS.map(|(name, _)| name)) {
+                        set_hexpand: true,
+                    },
+
+                    attach[0, 2, 1, 1] = &gtk::Label {
+                        set_text: "Password:",
+                        set_xalign: 1.0,
+                    },
+
+                    #[name(password)]
+                    attach[1, 2, 1, 1] = &gtk::PasswordEntry {
+                        set_hexpand: true,
+                        set_show_peek_icon: true,
+                        set_tooltip_text: Some("Password to encrypt ncryptsec key by"),
+                    },
+
+                    #[name(confirm)]
+                    attach[0, 3, 2, 1] = &gtk::CheckButton::with_label(
+                        "I understand that anyone who sees the secret key can act as this identity."
+                    ) {
+                        connect_toggled[sender] => move |c| {
+                            sender.input(ExportInput::Confirm(c.is_active()))
+                        },
+                    },
+
+                    attach[0, 4, 2, 1] = &gtk::Label {
+                        add_css_class: "monospace",
+                        set_selectable: true,
+                        set_wrap: true,
+                        set_wrap_mode: gtk::pango::WrapMode::Char,
+                        set_xalign: 0.0,
+                        #[watch] set_visible: model.secret.is_some(),
+                        #[watch] set_label: model.secret.as_deref().unwrap_or_default(),
+                    },
+
+                    attach[0, 5, 2, 1] = &gtk::Label {
+                        add_css_class: "error",
+                        set_xalign: 0.0,
+                        set_wrap: true,
+                        #[watch] set_visible: model.error.is_some(),
+                        #[watch] set_label: model.error.as_deref().unwrap_or_default(),
+                    },
+
+                    attach[0, 6, 2, 1] = &gtk::Box {
+                        set_halign: gtk::Align::End,
+                        set_spacing: 8,
+
+                        gtk::Spinner {
+                            #[watch] set_spinning: model.busy,
+                        },
+
+                        gtk::Button::with_label("Show secret key") {
+                            #[watch] set_sensitive: model.confirmed && !model.busy,
+                            connect_clicked => ExportInput::Reveal,
+                        },
+
+                        gtk::Button::with_label("Close") {
+                            connect_clicked => ExportInput::Close,
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Export {
+            identity: None,
+            confirmed: false,
+            busy: false,
+            secret: None,
+            error: None,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            ExportInput::Show(identity) => {
+                self.identity = Some(identity);
+                self.secret = None;
+                self.error = None;
+                widgets.password.set_text("");
+                widgets.confirm.set_active(false);
+            }
+            ExportInput::Confirm(confirmed) => {
+                self.confirmed = confirmed;
+                if !confirmed {
+                    self.secret = None;
+                }
+            }
+            ExportInput::Reveal => {
+                let (_, format) = FORMATS[widgets.format.selected() as usize];
+                let password = widgets.password.text().to_string();
+
+                if format == KeyFormat::Ncryptsec && password.is_empty() {
+                    self.error = Some("Enter password to encrypt the key by.".to_string());
+                } else if let Some(identity) = self.identity.clone() {
+                    self.busy = true;
+                    self.error = None;
+                    sender.oneshot_command(async move {
+                        let result = identity
+                            .export(format, &password)
+                            .map_err(|e| e.to_string());
+                        ExportCmd::Exported(result)
+                    });
+                }
+            }
+            ExportInput::Close => {
+                self.identity = None;
+                self.secret = None;
+                widgets.password.set_text("");
+                widgets.confirm.set_active(false);
+                sender.output(ExportOutput::Closed).unwrap_or_default();
+            }
+        }
+
+        self.update_view(widgets, sender);
+    }
+
+    fn update_cmd(
+        &mut self,
+        message: Self::CommandOutput,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            ExportCmd::Exported(result) => {
+                self.busy = false;
+                // The page might have been closed in the meantime.
+                if self.identity.is_some() {
+                    match result {
+                        Ok(secret) => self.secret = Some(secret),
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -24,6 +24,7 @@ pub enum IdentityInput {
 pub enum IdentityOutput {
     Remove(DynamicIndex),
     Edit(DynamicIndex),
+    Export(DynamicIndex),
 }
 
 #[relm4::factory(pub)]
@@ -61,6 +62,16 @@ impl FactoryComponent for IdentityBox {
                 }
             },
 
+            gtk::Button::from_icon_name("dialog-password-symbolic") {
+                add_css_class: "flat",
+                #[watch]
+                set_visible: self.identity.keeps_secret(),
+                set_tooltip_text: Some("Export secret key"),
+                connect_clicked[sender, index] => move |_| {
+                    sender.output(IdentityOutput::Export(index.clone()))
+                }
+            },
+
             gtk::Button::from_icon_name("list-remove-symbolic") {
                 add_css_class: "flat",
                 inline_css: "color: red",
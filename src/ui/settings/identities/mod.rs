@@ -1,4 +1,5 @@
 pub mod edit;
+pub mod export;
 pub mod identity;
 pub mod model;
 pub mod view;
@@ -2,7 +2,9 @@ use relm4::factory::FactoryVecDeque;
 use relm4::prelude::*;
 
 use super::edit::Edit;
+use super::export::Export;
 use super::identity::IdentityBox;
+use crate::gnostique::Gnostique;
 use crate::identity::Identity;
 
 #[derive(Debug)]
@@ -16,6 +18,14 @@ pub struct Identities {
     pub(super) identity_counter: usize,
 
     pub(super) edit: Controller<Edit>,
+
+    pub(super) export: Controller<Export>,
+
+    /// Session whose identities are managed, once it is unlocked.
+    pub(super) gnostique: Option<Gnostique>,
+
+    /// Problem with the last change, if any.
+    pub(super) error: Option<String>,
 }
 
 #[derive(Debug)]
@@ -29,6 +39,12 @@ pub enum IdentitiesInput {
     /// Edit identity with given index.
     Edit(DynamicIndex),
 
+    /// Export secret key of identity with given index.
+    Export(DynamicIndex),
+
+    /// Export page was closed.
+    ExportClosed,
+
     /// Editing was canceled.
     EditCanceled,
 
@@ -38,4 +54,40 @@ pub enum IdentitiesInput {
         new_identity: Identity,
     },
     Load(Vec<Identity>),
+
+    /// Gnostique session was unlocked, its identities can be managed now.
+    Unlocked(Gnostique),
+
+    /// Load identities of the session again.
+    Reload,
+
+    /// Encrypt identities by the newly entered password.
+    ChangePassword,
+}
+
+#[derive(Debug)]
+pub enum IdentitiesCmd {
+    /// Identities were saved, or not.
+    Saved(Result<(), String>),
+
+    /// Password was changed, or not.
+    PasswordChanged(Result<(), String>),
+}
+
+impl Identities {
+    /// Saves identities as they are in the list.
+    pub(super) fn save(&self, sender: &ComponentSender<Self>) {
+        if let Some(gnostique) = self.gnostique.clone() {
+            let identities = self
+                .identities
+                .iter()
+                .map(|i| i.identity.clone())
+                .collect::<Vec<_>>();
+
+            sender.oneshot_command(async move {
+                let result = gnostique.set_identities(identities).await;
+                IdentitiesCmd::Saved(result.map_err(|e| e.to_string()))
+            });
+        }
+    }
 }
@@ -1,10 +1,12 @@
 use gtk::prelude::*;
 use relm4::factory::FactoryVecDeque;
 use relm4::prelude::*;
+use secrecy::Secret;
 
 use super::edit::{Edit, EditInput, EditOutput};
+use super::export::{Export, ExportInput, ExportOutput};
 use super::identity::IdentityInit;
-use super::model::{Identities, IdentitiesInput};
+use super::model::{Identities, IdentitiesCmd, IdentitiesInput};
 use crate::ui::settings::identities::identity::IdentityOutput;
 
 /// Component that contains a settings section for managing identities.
@@ -14,7 +16,7 @@ impl Component for Identities {
     type Init = ();
     type Input = IdentitiesInput;
     type Output = ();
-    type CommandOutput = ();
+    type CommandOutput = IdentitiesCmd;
 
     view! {
         gtk::Stack {
@@ -57,11 +59,71 @@ impl Component for Identities {
                             connect_clicked[sender] => move |_| sender.input(IdentitiesInput::Add),
                         }
                     }
-                }
+                },
+
+                gtk::Label {
+                    add_css_class: "error",
+                    set_xalign: 0.0,
+                    #[watch] set_visible: model.error.is_some(),
+                    #[watch] set_label: model.error.as_deref().unwrap_or_default(),
+                },
+
+                gtk::Label {
+                    set_label: "Password of identities:",
+                    set_halign: gtk::Align::Start,
+                    set_xalign: 0.0,
+                },
+
+                gtk::Grid {
+                    set_column_spacing: 16,
+                    set_row_spacing: 8,
+
+                    attach[0, 0, 1, 1] = &gtk::Label {
+                        set_text: "Current password:",
+                        set_xalign: 1.0,
+                    },
+
+                    #[name(old_password)]
+                    attach[1, 0, 1, 1] = &gtk::PasswordEntry {
+                        set_hexpand: true,
+                        set_show_peek_icon: true,
+                    },
+
+                    attach[0, 1, 1, 1] = &gtk::Label {
+                        set_text: "New password:",
+                        set_xalign: 1.0,
+                    },
+
+                    #[name(new_password)]
+                    attach[1, 1, 1, 1] = &gtk::PasswordEntry {
+                        set_hexpand: true,
+                        set_show_peek_icon: true,
+                    },
+
+                    attach[0, 2, 1, 1] = &gtk::Label {
+                        set_text: "Repeat new password:",
+                        set_xalign: 1.0,
+                    },
+
+                    #[name(repeat_password)]
+                    attach[1, 2, 1, 1] = &gtk::PasswordEntry {
+                        set_hexpand: true,
+                        set_show_peek_icon: true,
+                        connect_activate => IdentitiesInput::ChangePassword,
+                    },
+
+                    attach[1, 3, 1, 1] = &gtk::Button::with_label("Change password") {
+                        set_halign: gtk::Align::End,
+                        connect_clicked => IdentitiesInput::ChangePassword,
+                    },
+                },
             },
 
             #[local_ref]
             edit -> gtk::Box { },
+
+            #[local_ref]
+            export -> gtk::Box { },
         }
 
     }
@@ -77,8 +139,11 @@ impl Component for Identities {
                 .forward(sender.input_sender(), |msg| match msg {
                     IdentityOutput::Remove(idx) => IdentitiesInput::Remove(idx),
                     IdentityOutput::Edit(idx) => IdentitiesInput::Edit(idx),
+                    IdentityOutput::Export(idx) => IdentitiesInput::Export(idx),
                 }),
             identity_counter: 1,
+            gnostique: None,
+            error: None,
             edit: Edit::builder().launch(()).forward(
                 sender.input_sender(),
                 |result| match result {
@@ -92,10 +157,16 @@ impl Component for Identities {
                     },
                 },
             ),
+            export: Export::builder()
+                .launch(())
+                .forward(sender.input_sender(), |result| match result {
+                    ExportOutput::Closed => IdentitiesInput::ExportClosed,
+                }),
         };
 
         let identities = model.identities.widget();
         let edit = model.edit.widget();
+        let export = model.export.widget();
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
@@ -105,7 +176,7 @@ impl Component for Identities {
         &mut self,
         widgets: &mut Self::Widgets,
         message: Self::Input,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
         stack: &Self::Root,
     ) {
         match message {
@@ -114,9 +185,11 @@ impl Component for Identities {
                     .guard()
                     .push_back(IdentityInit::New(self.identity_counter));
                 self.identity_counter += 1;
+                self.save(&sender);
             }
             IdentitiesInput::Remove(idx) => {
                 self.identities.guard().remove(idx.current_index());
+                self.save(&sender);
             }
             IdentitiesInput::Edit(idx) => {
                 if let Some(id) = self.identities.get(idx.current_index()) {
@@ -127,7 +200,13 @@ impl Component for Identities {
                     stack.set_visible_child(&widgets.edit);
                 }
             }
-            IdentitiesInput::EditCanceled => {
+            IdentitiesInput::Export(idx) => {
+                if let Some(id) = self.identities.get(idx.current_index()) {
+                    self.export.emit(ExportInput::Show(id.identity.clone()));
+                    stack.set_visible_child(&widgets.export);
+                }
+            }
+            IdentitiesInput::EditCanceled | IdentitiesInput::ExportClosed => {
                 stack.set_visible_child(&widgets.overview);
             }
             IdentitiesInput::EditFinished {
@@ -137,6 +216,7 @@ impl Component for Identities {
                 if let Some(id) = self.identities.guard().get_mut(index.current_index()) {
                     id.identity = new_identity
                 }
+                self.save(&sender);
                 stack.set_visible_child(&widgets.overview);
             }
             IdentitiesInput::Load(identities) => {
@@ -146,6 +226,60 @@ impl Component for Identities {
                     q.push_back(IdentityInit::Existing(i));
                 }
             }
+            IdentitiesInput::Unlocked(gnostique) => {
+                self.gnostique = Some(gnostique);
+                sender.input(IdentitiesInput::Reload);
+            }
+            IdentitiesInput::Reload => {
+                if let Some(gnostique) = &self.gnostique {
+                    sender.input(IdentitiesInput::Load(gnostique.identities()));
+                }
+            }
+            IdentitiesInput::ChangePassword => {
+                let old = widgets.old_password.text().to_string();
+                let new = widgets.new_password.text().to_string();
+                let repeated = widgets.repeat_password.text().to_string();
+
+                if new.is_empty() {
+                    self.error = Some("New password cannot be empty.".to_string());
+                } else if new != repeated {
+                    self.error = Some("New passwords do not match.".to_string());
+                } else if let Some(gnostique) = self.gnostique.clone() {
+                    self.error = None;
+                    for entry in [
+                        &widgets.old_password,
+                        &widgets.new_password,
+                        &widgets.repeat_password,
+                    ] {
+                        entry.set_text("");
+                    }
+
+                    sender.oneshot_command(async move {
+                        let result = gnostique
+                            .change_password(Secret::new(old), Secret::new(new))
+                            .await;
+                        IdentitiesCmd::PasswordChanged(result.map_err(|e| e.to_string()))
+                    });
+                }
+            }
+        }
+
+        self.update_view(widgets, sender);
+    }
+
+    fn update_cmd(
+        &mut self,
+        message: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            IdentitiesCmd::Saved(result) => {
+                self.error = result.err();
+                // List shows what is really kept, even if saving failed.
+                sender.input(IdentitiesInput::Reload);
+            }
+            IdentitiesCmd::PasswordChanged(result) => self.error = result.err(),
         }
     }
 }
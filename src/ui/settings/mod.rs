@@ -1,5 +1,6 @@
 mod identities;
 mod model;
+mod relays;
 mod view;
 
 pub use model::{Settings, SettingsInput};
@@ -1,18 +1,28 @@
 use relm4::Controller;
 
 use super::identities::model::Identities;
+use super::relays::Relays;
+use crate::gnostique::Gnostique;
 use crate::identity::Identity;
 
 #[derive(Debug)]
 pub struct Settings {
     pub visible: bool,
     pub identities: Controller<Identities>,
+    pub relays: Controller<Relays>,
 }
 
 #[derive(Debug)]
 pub enum SettingsInput {
+    /// Gnostique session was unlocked, settings related
+    /// to it can be managed now.
+    Unlocked(Gnostique),
     /// Display the settings dialog.
     Show(Vec<Identity>),
+    /// Display the settings dialog with identities of the session.
+    ShowIdentities,
+    /// Display the settings dialog with relays.
+    ShowRelays,
     /// Hide the settings dialog.
     Hide,
 }
@@ -0,0 +1,5 @@
+pub mod model;
+pub mod relay;
+pub mod view;
+
+pub use model::Relays;
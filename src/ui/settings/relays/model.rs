@@ -0,0 +1,39 @@
+use relm4::factory::FactoryVecDeque;
+
+use super::relay::RelayBox;
+use crate::gnostique::Gnostique;
+use crate::relays::RelayPolicy;
+
+#[derive(Debug)]
+pub struct Relays {
+    /// Session whose relays are managed. Relays can be managed
+    /// only after Gnostique has been unlocked.
+    pub(super) gnostique: Option<Gnostique>,
+
+    /// List of relay widgets.
+    pub(super) relays: FactoryVecDeque<RelayBox>,
+
+    /// GTK Entry Buffer for URL of a relay to be added.
+    pub(super) new_url: gtk::EntryBuffer,
+}
+
+#[derive(Debug)]
+pub enum RelaysInput {
+    /// Load relays of a Gnostique session.
+    Load(Gnostique),
+
+    /// Add relay whose URL is in the entry.
+    Add,
+
+    /// User changed how a relay is supposed to be used.
+    Changed(RelayPolicy),
+}
+
+#[derive(Debug)]
+pub enum RelaysCmd {
+    /// Relays were loaded from database.
+    Loaded(Vec<RelayPolicy>),
+
+    /// Changed relays were applied to client.
+    Applied,
+}
@@ -0,0 +1,82 @@
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+use crate::relays::RelayPolicy;
+
+/// One row in the list of relays.
+#[derive(Debug)]
+pub struct RelayBox {
+    pub policy: RelayPolicy,
+}
+
+#[derive(Debug)]
+pub enum RelayInput {
+    Enable(bool),
+    Read(bool),
+    Write(bool),
+}
+
+#[derive(Debug)]
+pub enum RelayOutput {
+    Changed(RelayPolicy),
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for RelayBox {
+    type Init = RelayPolicy;
+    type Input = RelayInput;
+    type Output = RelayOutput;
+    type CommandOutput = ();
+    type ParentWidget = gtk::Box;
+
+    view! {
+        root = gtk::Box {
+            set_orientation: gtk::Orientation::Horizontal,
+            set_spacing: 8,
+            add_css_class: "relay",
+
+            gtk::Switch::new() {
+                set_valign: gtk::Align::Center,
+                set_active: self.policy.enabled,
+                set_tooltip_text: Some("Use this relay"),
+                connect_state_set[sender] => move |_, state| {
+                    sender.input(RelayInput::Enable(state));
+                    gtk::glib::Propagation::Proceed
+                }
+            },
+
+            gtk::Label {
+                set_text: self.policy.url.as_str(),
+                set_hexpand: true,
+                set_xalign: 0.0,
+                set_ellipsize: gtk::pango::EllipsizeMode::Middle,
+            },
+
+            gtk::CheckButton::with_label("Read") {
+                set_active: self.policy.read,
+                #[watch] set_sensitive: self.policy.enabled,
+                connect_toggled[sender] => move |b| sender.input(RelayInput::Read(b.is_active()))
+            },
+
+            gtk::CheckButton::with_label("Write") {
+                set_active: self.policy.write,
+                #[watch] set_sensitive: self.policy.enabled,
+                connect_toggled[sender] => move |b| sender.input(RelayInput::Write(b.is_active()))
+            },
+        }
+    }
+
+    fn init_model(policy: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        RelayBox { policy }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
+        match message {
+            RelayInput::Enable(enabled) => self.policy.enabled = enabled,
+            RelayInput::Read(read) => self.policy.read = read,
+            RelayInput::Write(write) => self.policy.write = write,
+        }
+
+        sender.output(RelayOutput::Changed(self.policy.clone()));
+    }
+}
@@ -0,0 +1,143 @@
+use gtk::prelude::*;
+use relm4::factory::FactoryVecDeque;
+use relm4::prelude::*;
+
+use super::model::{Relays, RelaysCmd, RelaysInput};
+use super::relay::RelayOutput;
+use crate::relays::RelayPolicy;
+
+/// Component that contains a settings section for managing relays.
+/// It is only displayed as part of Settings.
+#[relm4::component(pub)]
+impl Component for Relays {
+    type Init = ();
+    type Input = RelaysInput;
+    type Output = ();
+    type CommandOutput = RelaysCmd;
+
+    view! {
+        gtk::Box {
+            set_valign: gtk::Align::Start,
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 12,
+            set_widget_name: "relays",
+            set_hexpand: true,
+
+            gtk::Label {
+                set_label: "List of relays:",
+                set_halign: gtk::Align::Start,
+                set_xalign: 0.0,
+            },
+
+            gtk::ScrolledWindow {
+                set_min_content_height: 400,
+
+                #[local_ref]
+                relays -> gtk::Box {
+                    set_vexpand: true,
+                    set_hexpand: true,
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 12,
+                    set_widget_name: "relay-list"
+                },
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 8,
+
+                gtk::Entry {
+                    set_buffer: &model.new_url,
+                    set_hexpand: true,
+                    set_placeholder_text: Some("wss://"),
+                    connect_activate[sender] => move |_| sender.input(RelaysInput::Add),
+                },
+
+                gtk::Button::with_label("Add") {
+                    #[watch] set_sensitive: model.gnostique.is_some(),
+                    connect_clicked[sender] => move |_| sender.input(RelaysInput::Add),
+                }
+            }
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Relays {
+            gnostique: None,
+            relays: FactoryVecDeque::builder(gtk::Box::default())
+                .launch()
+                .forward(sender.input_sender(), |msg| match msg {
+                    RelayOutput::Changed(policy) => RelaysInput::Changed(policy),
+                }),
+            new_url: gtk::EntryBuffer::default(),
+        };
+
+        let relays = model.relays.widget();
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match message {
+            RelaysInput::Load(gnostique) => {
+                self.gnostique = Some(gnostique.clone());
+                sender.oneshot_command(async move {
+                    RelaysCmd::Loaded(gnostique.store().relay_policies().await.unwrap_or_default())
+                });
+            }
+            RelaysInput::Add => {
+                if let Some(url) = RelayPolicy::parse_url(self.new_url.text().as_str()) {
+                    self.new_url.set_text("");
+
+                    if self.relays.iter().all(|r| r.policy.url != url) {
+                        let policy = RelayPolicy::new(url);
+                        self.relays.guard().push_front(policy.clone());
+                        sender.input(RelaysInput::Changed(policy));
+                    }
+                }
+            }
+            RelaysInput::Changed(policy) => {
+                if let Some(gnostique) = self.gnostique.clone() {
+                    sender.oneshot_command(async move {
+                        if let Err(e) = gnostique.store().set_relay_policy(&policy).await {
+                            tracing::warn!("Could not save relay policy: {e}");
+                        }
+                        gnostique.apply_relays().await;
+                        match gnostique.store().relay_policies().await {
+                            Ok(policies) => {
+                                if let Err(e) = gnostique.publish_relay_list(&policies).await {
+                                    tracing::warn!("Could not publish relay list: {e}");
+                                }
+                            }
+                            Err(e) => tracing::warn!("Could not load relay policies: {e}"),
+                        }
+                        RelaysCmd::Applied
+                    });
+                }
+            }
+        }
+    }
+
+    fn update_cmd(
+        &mut self,
+        message: Self::CommandOutput,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            RelaysCmd::Loaded(policies) => {
+                let mut relays = self.relays.guard();
+                relays.clear();
+                for p in policies {
+                    relays.push_back(p);
+                }
+            }
+            RelaysCmd::Applied => {}
+        }
+    }
+}
@@ -4,12 +4,15 @@ use relm4::prelude::*;
 use super::identities::model::IdentitiesInput;
 use super::identities::Identities;
 use super::model::{Settings, SettingsInput};
+use super::relays::model::RelaysInput;
+use super::relays::Relays;
 
 #[relm4::component(pub)]
-impl SimpleComponent for Settings {
+impl Component for Settings {
     type Init = ();
     type Input = SettingsInput;
     type Output = ();
+    type CommandOutput = ();
 
     view! {
         gtk::Window {
@@ -42,8 +45,8 @@ impl SimpleComponent for Settings {
                         set_vexpand: true,
 
                         add_child = &gtk::Box { } -> { set_title: "Appearance" },
-                        #[local_ref] add_child = identities -> gtk::Stack { } -> { set_title: "Identities" },
-                        add_child = &gtk::Box { } -> { set_title: "Relays" },
+                        #[local_ref] add_child = identities -> gtk::Stack { } -> { set_title: "Identities", set_name: "identities" },
+                        #[local_ref] add_child = relays -> gtk::Box { } -> { set_title: "Relays", set_name: "relays" },
                         add_child = &gtk::Box { } -> { set_title: "Nostr" },
                         add_child = &gtk::Box { } -> { set_title: "Privacy" },
                         add_child = &gtk::Box { } -> { set_title: "Addressbook" },
@@ -77,22 +80,46 @@ impl SimpleComponent for Settings {
         let model = Settings {
             visible: false,
             identities: Identities::builder().launch(()).detach(),
+            relays: Relays::builder().launch(()).detach(),
         };
 
         let identities = model.identities.widget();
+        let relays = model.relays.widget();
 
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
         match message {
             SettingsInput::Hide => self.visible = false,
+            SettingsInput::Unlocked(gnostique) => {
+                self.identities
+                    .emit(IdentitiesInput::Unlocked(gnostique.clone()));
+                self.relays.emit(RelaysInput::Load(gnostique));
+            }
             SettingsInput::Show(identities) => {
                 self.identities.emit(IdentitiesInput::Load(identities));
                 self.visible = true;
             }
+            SettingsInput::ShowIdentities => {
+                self.identities.emit(IdentitiesInput::Reload);
+                widgets.stack.set_visible_child_name("identities");
+                self.visible = true;
+            }
+            SettingsInput::ShowRelays => {
+                widgets.stack.set_visible_child_name("relays");
+                self.visible = true;
+            }
         }
+
+        self.update_view(widgets, sender);
     }
 }
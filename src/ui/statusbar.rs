@@ -19,17 +19,27 @@ pub struct RelayStatus {
 #[derive(Debug)]
 pub struct StatusBar {
     relay_status: Option<RelayStatus>,
+    /// Latest message for user, if any.
+    message: Option<String>,
 }
 
 #[derive(Debug)]
 pub enum StatusBarInput {
     UpdateRelayStatus(RelayStatus),
+    /// Show message to user.
+    Message(String),
+}
+
+#[derive(Debug)]
+pub enum StatusBarOutput {
+    /// User clicked on relay status.
+    ShowRelays,
 }
 
 #[relm4::component(pub)]
 impl SimpleComponent for StatusBar {
     type Input = StatusBarInput;
-    type Output = ();
+    type Output = StatusBarOutput;
     type Init = Gnostique;
 
     #[rusfmt::skip]
@@ -39,9 +49,12 @@ impl SimpleComponent for StatusBar {
             set_hexpand: true,
             add_css_class: "statusbar",
 
-            // filler
-            gtk::Box {
+            // message, fills the rest
+            gtk::Label {
                 set_hexpand: true,
+                set_xalign: 0.0,
+                add_css_class: "message",
+                #[watch] set_label: model.message.as_deref().unwrap_or_default(),
             },
 
             gtk::Box {
@@ -51,6 +64,9 @@ impl SimpleComponent for StatusBar {
 
                 gtk::Button {
                     #[watch] set_tooltip_markup: Some(&model.format_relay_status_tooltip()),
+                    connect_clicked[sender] => move |_| {
+                        sender.output(StatusBarOutput::ShowRelays).unwrap_or_default()
+                    },
                     #[wrap(Some)]
                     set_child = &gtk::Label {
                         #[watch] set_markup?: &model.format_relay_status(),
@@ -67,7 +83,10 @@ impl SimpleComponent for StatusBar {
     ) -> ComponentParts<Self> {
         relm4::spawn(update_relay_status(gnostique.client().clone(), sender));
 
-        let model = StatusBar { relay_status: None };
+        let model = StatusBar {
+            relay_status: None,
+            message: None,
+        };
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
@@ -76,6 +95,7 @@ impl SimpleComponent for StatusBar {
     fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
         match message {
             StatusBarInput::UpdateRelayStatus(status) => self.relay_status = Some(status),
+            StatusBarInput::Message(message) => self.message = Some(message),
         }
     }
 }
@@ -133,7 +153,7 @@ impl StatusBar {
                 &status
             };
 
-            format!("<b>Status of relays:</b>\n\n{status}")
+            format!("<b>Status of relays:</b>\n\n{status}\n\n<i>Click to manage relays.</i>")
         } else {
             "Could not obtain status of relays.".to_string()
         }
@@ -12,6 +12,7 @@ use crate::gnostique::{make_gnostique, Gnostique};
 
 #[derive(Clone, Debug)]
 pub struct Conf {
+    config: Config,
     dirs: ProjectDirs,
     pool: SqlitePool,
     id_file: PathBuf,
@@ -151,15 +152,16 @@ impl Component for Unlock {
             tokio::fs::create_dir_all(dirs.data_dir()).await.unwrap();
             tokio::fs::create_dir_all(dirs.config_dir()).await.unwrap();
 
-            let config = Config {
-                db_file: dirs.data_dir().join("databaze.db"),
+            let config = match Config::load(&dirs).await {
+                Ok(config) => config,
+                Err(e) => return UnlockCmd::Error(e.to_string()),
             };
 
             let pool = SqlitePoolOptions::new()
                 .max_connections(5)
                 .connect_with(
                     SqliteConnectOptions::new()
-                        .filename(config.db_file)
+                        .filename(&config.db_file)
                         .create_if_missing(true),
                 )
                 .await
@@ -172,6 +174,7 @@ impl Component for Unlock {
             let id_exists = tokio::fs::try_exists(&id_file).await.unwrap();
 
             let conf = Conf {
+                config,
                 dirs,
                 pool,
                 id_file,
@@ -203,11 +206,13 @@ impl Component for Unlock {
             }
 
             UnlockCmd::UnlockIdentity(conf) => {
+                apply_ui_config(&conf.config);
                 *self = Unlock::Loaded(conf);
                 sender.input(UnlockInput::RequestPassword);
             }
 
             UnlockCmd::CreateIdentity(conf) => {
+                apply_ui_config(&conf.config);
                 *self = Unlock::Loaded(conf);
                 sender.input(UnlockInput::RequestPassword);
             }
@@ -231,7 +236,15 @@ impl Component for Unlock {
                 if let Unlock::Loaded(conf) = self {
                     let conf = conf.clone();
                     sender.oneshot_command(async {
-                        match make_gnostique(conf.dirs, conf.pool, conf.id_file, password).await {
+                        match make_gnostique(
+                            conf.config,
+                            conf.dirs,
+                            conf.pool,
+                            conf.id_file,
+                            password,
+                        )
+                        .await
+                        {
                             Ok(gn) => UnlockCmd::Unlocked(gn),
                             Err(e) => UnlockCmd::Error(e),
                         }
@@ -243,3 +256,10 @@ impl Component for Unlock {
         self.update_view(widgets, sender);
     }
 }
+
+/// Applies user interface preferences from configuration.
+fn apply_ui_config(config: &Config) {
+    if let Some(settings) = gtk::Settings::default() {
+        settings.set_gtk_application_prefer_dark_theme(config.ui.dark_theme);
+    }
+}
@@ -1,13 +1,29 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use gtk::prelude::*;
 use relm4::*;
+use tracing::warn;
 
 use super::model::*;
+use crate::gnostique::Gnostique;
+use crate::nostr::{compose, EventExt, Persona};
+
+/// How many profiles are suggested when writing a mention.
+const SUGGESTIONS: usize = 8;
+
+/// Draft is saved when user does not type for this long.
+const SAVE_DRAFT_AFTER: Duration = Duration::from_secs(1);
+
+/// Key of draft of a new note.
+const NOTE_DRAFT: &str = "note";
 
 #[relm4::component(pub)]
-impl SimpleComponent for WriteNote {
-    type Init = ();
+impl Component for WriteNote {
+    type Init = Gnostique;
     type Input = WriteNoteInput;
     type Output = WriteNoteResult;
+    type CommandOutput = WriteNoteCmd;
 
     view! {
         gtk::Window {
@@ -26,13 +42,36 @@ impl SimpleComponent for WriteNote {
                     set_row_spacing: 16,
 
                     attach[0, 0, 1, 1] = &gtk::Label {
+                        set_text: "Replying to",
+                        set_xalign: 1.0,
+                        set_valign: gtk::Align::Start,
+                        add_css_class: "label",
+                        #[watch] set_visible: model.reply_to.is_some(),
+                    },
+
+                    attach[1, 0, 1, 1] = &gtk::Label {
+                        set_xalign: 0.0,
+                        set_wrap: true,
+                        set_wrap_mode: gtk::pango::WrapMode::WordChar,
+                        set_lines: 4,
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
+                        add_css_class: "quote",
+                        #[watch] set_visible: model.reply_to.is_some(),
+                        #[watch] set_label: model
+                            .reply_to
+                            .as_ref()
+                            .map(|r| r.event.content.as_str())
+                            .unwrap_or_default(),
+                    },
+
+                    attach[0, 1, 1, 1] = &gtk::Label {
                         set_text: "Content",
                         set_xalign: 1.0,
                         set_valign: gtk::Align::Start,
                         add_css_class: "label",
                     },
 
-                    attach[1, 0, 1, 1] = &gtk::ScrolledWindow {
+                    attach[1, 1, 1, 1] = &gtk::ScrolledWindow {
                         set_hexpand: true,
                         set_hscrollbar_policy: gtk::PolicyType::Never,
                         set_min_content_height: 180,
@@ -47,6 +86,35 @@ impl SimpleComponent for WriteNote {
                             add_css_class: "multiline",
                         }
                     },
+
+                    // Profiles matching mention being written, filled in by `show_suggestions`.
+                    #[name(suggestions)]
+                    attach[1, 2, 1, 1] = &gtk::ListBox {
+                        set_activate_on_single_click: true,
+                        add_css_class: "suggestions",
+                        #[watch] set_visible: !model.suggestions.is_empty(),
+                        connect_row_activated[sender] => move |_, row| {
+                            sender.input(WriteNoteInput::Complete(row.index() as usize))
+                        },
+                    },
+
+                    attach[0, 3, 1, 1] = &gtk::Label {
+                        set_text: "Preview",
+                        set_xalign: 1.0,
+                        set_valign: gtk::Align::Start,
+                        add_css_class: "label",
+                        #[watch] set_visible: !model.preview.is_empty(),
+                    },
+
+                    attach[1, 3, 1, 1] = &gtk::Label {
+                        set_xalign: 0.0,
+                        set_wrap: true,
+                        set_wrap_mode: gtk::pango::WrapMode::WordChar,
+                        set_selectable: true,
+                        add_css_class: "preview-content",
+                        #[watch] set_visible: !model.preview.is_empty(),
+                        #[watch] set_markup: &model.preview,
+                    },
                 },
 
                 gtk::Box {
@@ -70,40 +138,241 @@ impl SimpleComponent for WriteNote {
     }
 
     fn init(
-        _init: Self::Init,
+        gnostique: Self::Init,
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let model = WriteNote {
+            gnostique,
             visible: false,
             buffer: gtk::TextBuffer::new(None),
+            reply_to: None,
+            quote: None,
+            draft_key: NOTE_DRAFT.to_string(),
+            changes: 0,
+            suggestions: vec![],
+            mentioned: vec![],
+            preview: String::new(),
         };
+
+        let input = sender.input_sender().clone();
+        model
+            .buffer
+            .connect_changed(move |_| input.emit(WriteNoteInput::Changed));
+
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
         match message {
             WriteNoteInput::Hide => {
                 self.visible = false;
                 self.buffer.set_text("");
+                self.reply_to = None;
+                self.quote = None;
+                self.mentioned.clear();
+            }
+            WriteNoteInput::Show => {
+                self.reply_to = None;
+                self.quote = None;
+                self.open(NOTE_DRAFT.to_string(), &sender);
+            }
+            WriteNoteInput::Quote { id, reference, tag } => {
+                // Quote goes below what user is going to write.
+                self.buffer.set_text(&format!("\n\n{reference}"));
+                self.buffer.place_cursor(&self.buffer.start_iter());
+                self.reply_to = None;
+                self.quote = Some((reference, tag));
+                self.open(format!("quote:{}", id.to_hex()), &sender);
+            }
+            WriteNoteInput::Reply(reply_to) => {
+                let key = format!("reply:{}", reply_to.event.id.to_hex());
+                self.reply_to = Some(reply_to);
+                self.open(key, &sender);
+            }
+            WriteNoteInput::Changed => {
+                let content = self.content();
+                self.preview = compose::preview(&content, &self.mentioned);
+                self.suggest(&sender);
+
+                self.changes += 1;
+                let changes = self.changes;
+                sender.oneshot_command(async move {
+                    tokio::time::sleep(SAVE_DRAFT_AFTER).await;
+                    WriteNoteCmd::SaveDraft(changes)
+                });
+            }
+            WriteNoteInput::Complete(index) => {
+                if let Some(persona) = self.suggestions.get(index).cloned() {
+                    self.complete_mention(&persona);
+                    self.mentioned.push(persona);
+                    self.suggestions.clear();
+                }
             }
-            WriteNoteInput::Show => self.visible = true,
             WriteNoteInput::Cancel => {
+                self.save_draft("");
                 sender.output(WriteNoteResult::Cancel).unwrap_or_default();
                 sender.input(WriteNoteInput::Hide)
             }
             WriteNoteInput::Send => {
-                let content = self
-                    .buffer
-                    .text(&self.buffer.start_iter(), &self.buffer.end_iter(), true)
-                    .to_string();
+                let content = self.content();
+                self.save_draft("");
                 sender
-                    .output(WriteNoteResult::Send(content))
+                    .output(WriteNoteResult::Send {
+                        // The quote tag is not needed if user removed the reference.
+                        tags: self
+                            .quote
+                            .take()
+                            .filter(|(reference, _)| content.contains(reference.as_str()))
+                            .map(|(_, tag)| tag)
+                            .into_iter()
+                            .collect(),
+                        content,
+                        reply_to: self.reply_to.take(),
+                    })
                     .unwrap_or_default();
                 sender.input(WriteNoteInput::Hide)
             }
         }
+
+        self.show_suggestions(&widgets.suggestions);
+        self.update_view(widgets, sender);
+    }
+
+    fn update_cmd_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            WriteNoteCmd::Draft(key, Some(draft)) if key == self.draft_key && self.visible => {
+                self.buffer.set_text(&draft);
+            }
+            WriteNoteCmd::Draft(..) => {}
+            WriteNoteCmd::Suggestions(personas) => {
+                self.suggestions = personas.into_iter().map(Arc::new).collect();
+            }
+            WriteNoteCmd::SaveDraft(changes) if changes == self.changes && self.visible => {
+                self.save_draft(&self.content());
+            }
+            WriteNoteCmd::SaveDraft(_) => {}
+        }
+
+        self.show_suggestions(&widgets.suggestions);
+        self.update_view(widgets, sender);
+    }
+}
+
+impl WriteNote {
+    fn content(&self) -> String {
+        self.buffer
+            .text(&self.buffer.start_iter(), &self.buffer.end_iter(), true)
+            .to_string()
+    }
+
+    /// Shows the window to write what is saved under draft `key`,
+    /// and restores the draft if there is any.
+    fn open(&mut self, key: String, sender: &ComponentSender<Self>) {
+        self.draft_key = key.clone();
+        self.visible = true;
+
+        let gnostique = self.gnostique.clone();
+        sender.oneshot_command(async move {
+            let draft = gnostique.store().draft(&key).await.unwrap_or_else(|e| {
+                warn!("Could not load draft: {e}");
+                None
+            });
+            WriteNoteCmd::Draft(key, draft)
+        });
+    }
+
+    /// Saves `content` as draft in the background, empty content removes it.
+    fn save_draft(&self, content: &str) {
+        let gnostique = self.gnostique.clone();
+        let key = self.draft_key.clone();
+        let content = content.to_string();
+
+        relm4::spawn(async move {
+            if let Err(e) = gnostique.store().save_draft(&key, &content).await {
+                warn!("Could not save draft: {e}");
+            }
+        });
+    }
+
+    /// Text from the start to the cursor.
+    fn before_cursor(&self) -> String {
+        let cursor = self.buffer.iter_at_mark(&self.buffer.get_insert());
+        self.buffer
+            .text(&self.buffer.start_iter(), &cursor, true)
+            .to_string()
+    }
+
+    /// Looks for profiles matching mention being written, if any.
+    fn suggest(&mut self, sender: &ComponentSender<Self>) {
+        match compose::mention_at(&self.before_cursor()) {
+            Some(written) => {
+                let gnostique = self.gnostique.clone();
+                let written = written.to_string();
+                sender.oneshot_command(async move {
+                    let personas = gnostique
+                        .store()
+                        .search_profiles(&written, SUGGESTIONS)
+                        .await
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|e| Some(Persona::from_metadata(e.pubkey, e.as_metadata()?)))
+                        .collect();
+                    WriteNoteCmd::Suggestions(personas)
+                });
+            }
+            None => self.suggestions.clear(),
+        }
+    }
+
+    /// Replaces mention being written before the cursor by reference to `persona`.
+    fn complete_mention(&self, persona: &Persona) {
+        let before = self.before_cursor();
+        let Some(written) = compose::mention_at(&before) else {
+            return;
+        };
+
+        let mut end = self.buffer.iter_at_mark(&self.buffer.get_insert());
+        let mut start = self.buffer.iter_at_mark(&self.buffer.get_insert());
+        // Including the `@`.
+        start.backward_chars(written.chars().count() as i32 + 1);
+
+        self.buffer.delete(&mut start, &mut end);
+        self.buffer.insert(
+            &mut start,
+            &format!("{} ", compose::mention(persona.pubkey)),
+        );
+    }
+
+    /// Fills `list` by rows of suggested profiles.
+    fn show_suggestions(&self, list: &gtk::ListBox) {
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+
+        for persona in &self.suggestions {
+            let label = gtk::Label::new(None);
+            label.set_xalign(0.0);
+            label.set_markup(&format!(
+                "{}  {}",
+                html_escape::encode_text(&persona.show_name().unwrap_or_default()),
+                persona.short_bech32(12)
+            ));
+            list.append(&label);
+        }
     }
 }
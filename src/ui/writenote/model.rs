@@ -1,7 +1,37 @@
+use std::sync::Arc;
+
+use nostr_sdk::prelude::*;
+
+use crate::gnostique::Gnostique;
+use crate::nostr::Persona;
+
 #[derive(Debug)]
 pub struct WriteNote {
+    pub gnostique: Gnostique,
     pub visible: bool,
     pub buffer: gtk::TextBuffer,
+    /// Note being replied to, if this is a reply.
+    pub reply_to: Option<ReplyTo>,
+    /// Reference of quoted event put into the content and its `q` tag.
+    pub quote: Option<(String, Tag)>,
+    /// Key under which draft of what is being written is saved.
+    pub draft_key: String,
+    /// Number of changes of the content so far. Draft is saved only
+    /// once the user stops typing, i. e. there are no more changes.
+    pub changes: usize,
+    /// Profiles matching the mention being written.
+    pub suggestions: Vec<Arc<Persona>>,
+    /// Profiles mentioned in the note, so that preview shows their names.
+    pub mentioned: Vec<Arc<Persona>>,
+    /// Markup of the note as it will be shown.
+    pub preview: String,
+}
+
+/// Note being replied to together with relays where it has been seen.
+#[derive(Clone, Debug)]
+pub struct ReplyTo {
+    pub event: Arc<Event>,
+    pub relays: Vec<Url>,
 }
 
 #[derive(Debug)]
@@ -9,11 +39,37 @@ pub enum WriteNoteInput {
     Hide,
     Cancel,
     Show,
+    Reply(ReplyTo),
+    /// Write a note quoting event `id` by `reference` with `tag`.
+    Quote {
+        id: EventId,
+        reference: String,
+        tag: Tag,
+    },
+    /// Content has been changed.
+    Changed,
+    /// Complete the mention being written by the suggested profile of this index.
+    Complete(usize),
     Send,
 }
 
 #[derive(Debug)]
 pub enum WriteNoteResult {
     Cancel,
-    Send(String),
+    Send {
+        content: String,
+        reply_to: Option<ReplyTo>,
+        /// Additional tags of the note.
+        tags: Vec<Tag>,
+    },
+}
+
+#[derive(Debug)]
+pub enum WriteNoteCmd {
+    /// Stored draft of the key has been loaded.
+    Draft(String, Option<String>),
+    /// Profiles matching the mention being written have been found.
+    Suggestions(Vec<Persona>),
+    /// Save draft if there has been no other change since this many changes.
+    SaveDraft(usize),
 }